@@ -0,0 +1,71 @@
+//! Comments collected from the trivia of a source file.
+
+use lily_lexer::{
+    indentation::Tabs,
+    lex_with_trivia,
+    types::{TokenK, TriviaK},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Comment<'a> {
+    pub(crate) begin: usize,
+    pub(crate) text: &'a str,
+    /// Whether a blank line separates the comment from what precedes it.
+    pub(crate) blank_before: bool,
+    /// Whether a blank line separates the comment from what follows it.
+    pub(crate) blank_after: bool,
+    /// Whether the comment follows a token on the same line.
+    pub(crate) trailing: bool,
+}
+
+impl Comment<'_> {
+    pub(crate) fn is_line(&self) -> bool {
+        self.text.starts_with("--")
+    }
+}
+
+pub(crate) fn comments(source: &str, tabs: Tabs) -> Vec<Comment<'_>> {
+    let mut comments: Vec<Comment> = vec![];
+    let mut newlines = 0;
+    let mut after_comment = false;
+    let mut after_token = false;
+    for token in lex_with_trivia(source, tabs) {
+        match token.kind {
+            TokenK::Trivia(TriviaK::Newline) => newlines += 1,
+            TokenK::Trivia(TriviaK::Whitespace) | TokenK::Layout(_) => (),
+            TokenK::Trivia(TriviaK::BlockComment | TriviaK::DocComment | TriviaK::LineComment) => {
+                if after_comment {
+                    if let Some(previous) = comments.last_mut() {
+                        previous.blank_after = newlines > 1;
+                    }
+                }
+                let text = &source[token.begin..token.end];
+                comments.push(Comment {
+                    begin: token.begin,
+                    text: if text.starts_with("--") {
+                        text.trim_end()
+                    } else {
+                        text
+                    },
+                    blank_before: newlines > 1,
+                    blank_after: false,
+                    trailing: after_token && newlines == 0,
+                });
+                newlines = 0;
+                after_comment = true;
+                after_token = false;
+            }
+            _ => {
+                if after_comment {
+                    if let Some(previous) = comments.last_mut() {
+                        previous.blank_after = newlines > 1;
+                    }
+                }
+                newlines = 0;
+                after_comment = false;
+                after_token = true;
+            }
+        }
+    }
+    comments
+}
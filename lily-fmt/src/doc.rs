@@ -0,0 +1,147 @@
+//! A Wadler-style document algebra and its renderer.
+
+#[derive(Debug, Clone)]
+pub(crate) enum Doc {
+    Text(String),
+    /// Breaks into a new line, or renders as the given text when flat.
+    Line(&'static str),
+    /// Always breaks, along with every group around it.
+    HardLine,
+    Nest(usize, Box<Doc>),
+    /// Renders flat if it fits on the line and contains no hard line, which
+    /// is cached when the group is built.
+    Group(Box<Doc>, bool),
+    Concat(Vec<Doc>),
+}
+
+pub(crate) fn nil() -> Doc {
+    Doc::Concat(vec![])
+}
+
+pub(crate) fn text(text: impl Into<String>) -> Doc {
+    Doc::Text(text.into())
+}
+
+pub(crate) fn line() -> Doc {
+    Doc::Line(" ")
+}
+
+pub(crate) fn softline() -> Doc {
+    Doc::Line("")
+}
+
+pub(crate) fn hardline() -> Doc {
+    Doc::HardLine
+}
+
+pub(crate) fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+}
+
+pub(crate) fn group(doc: Doc) -> Doc {
+    let hard = doc.has_hardline();
+    Doc::Group(Box::new(doc), hard)
+}
+
+pub(crate) fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+    Doc::Concat(docs.into_iter().collect())
+}
+
+impl Doc {
+    pub(crate) fn has_hardline(&self) -> bool {
+        match self {
+            Doc::Text(text) => text.contains('\n'),
+            Doc::Line(_) => false,
+            Doc::HardLine => true,
+            Doc::Nest(_, doc) => doc.has_hardline(),
+            Doc::Group(_, hard) => *hard,
+            Doc::Concat(docs) => docs.iter().any(Doc::has_hardline),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Renders a document, breaking the groups that do not fit in `width`.
+pub(crate) fn render(doc: &Doc, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                output.push_str(text);
+                column = match text.rfind('\n') {
+                    Some(index) => text[index + 1..].chars().count(),
+                    None => column + text.chars().count(),
+                };
+            }
+            Doc::Line(flat) if mode == Mode::Flat => {
+                output.push_str(flat);
+                column += flat.len();
+            }
+            Doc::Line(_) | Doc::HardLine => {
+                output.truncate(output.trim_end_matches(' ').len());
+                output.push('\n');
+                output.extend(std::iter::repeat_n(' ', indent));
+                column = indent;
+            }
+            Doc::Nest(extra, doc) => stack.push((indent + extra, mode, doc)),
+            Doc::Group(doc, hard) => {
+                let mode = if mode == Mode::Flat
+                    || !hard && fits(width.saturating_sub(column), doc, &stack)
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((indent, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+
+    output.truncate(output.trim_end_matches(' ').len());
+    output
+}
+
+/// Whether a group rendered flat, along with what follows it up to the next
+/// line break, fits in the remaining width.
+fn fits(remaining: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut remaining = remaining as isize;
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => {
+                remaining -= text.chars().count() as isize;
+                if remaining < 0 {
+                    return false;
+                }
+            }
+            Doc::Line(flat) if mode == Mode::Flat => remaining -= flat.len() as isize,
+            Doc::Line(_) | Doc::HardLine => return true,
+            Doc::Nest(_, doc) => stack.push((mode, doc)),
+            Doc::Group(doc, hard) => {
+                stack.push((if *hard { Mode::Break } else { mode }, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
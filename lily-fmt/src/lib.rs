@@ -0,0 +1,89 @@
+//! A formatter that prints a parsed module back into canonical source.
+//!
+//! Top-level items are separated by blank lines, except for runs of imports,
+//! runs of fixity declarations, and a type declaration followed by the value
+//! it annotates. Comments are kept, attached to the node that follows them.
+
+use lily_lexer::indentation::Tabs;
+use lily_parser::{
+    errors::ParseError,
+    parse_top_level_with_tabs,
+    types::{Domain, FixityMap, Module},
+    visit::VisitorMut,
+};
+
+use self::{parens::Parenthesize, printer::Printer};
+
+mod comments;
+mod doc;
+mod parens;
+mod printer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// The column that lines are broken at, where possible.
+    pub width: usize,
+    /// The number of spaces per level of indentation, at least one.
+    pub indent: usize,
+    /// How tabs in the source count towards layout. The output is always
+    /// indented with spaces.
+    pub tabs: Tabs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            indent: 2,
+            tabs: Tabs::default(),
+        }
+    }
+}
+
+/// Formats a source file, or returns the errors that prevent it from being
+/// formatted.
+pub fn format(source: &str, config: &Config) -> Result<String, Vec<ParseError>> {
+    let (module, errors) = parse_top_level_with_tabs(source, config.tabs);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(format_module(source, &module, config))
+}
+
+/// Formats a module that was parsed from `source`, which the comments are
+/// taken from.
+///
+/// Declarations that failed to parse are copied from the source as they
+/// are. Parentheses are added wherever the tree cannot be printed without
+/// them, such that parsing the output gives back an equal module.
+pub fn format_module(source: &str, module: &Module, config: &Config) -> String {
+    let mut module = module.clone();
+    parenthesize(&mut module);
+
+    let indent = config.indent.max(1);
+    let doc = Printer::new(source, indent, config.tabs).module(&module);
+    doc::render(&doc, config.width)
+}
+
+/// Adds the parentheses that a module needs in order to be printed, such as
+/// around operators that are nested against their fixities, or blocks where
+/// the grammar only allows an atom.
+///
+/// Modules that come from the parser are left unchanged, so parsing the
+/// output of [`format_module`] gives back the parenthesized module.
+pub fn parenthesize(module: &mut Module) {
+    let mut value_fixities = FixityMap::default();
+    let mut type_fixities = FixityMap::default();
+    for fixity in &module.fixities {
+        match fixity.domain {
+            Domain::Type => type_fixities.insert(fixity.operator, fixity.clone()),
+            Domain::Value => value_fixities.insert(fixity.operator, fixity.clone()),
+        };
+    }
+
+    Parenthesize {
+        value_fixities: &value_fixities,
+        type_fixities: &type_fixities,
+    }
+    .visit_module_mut(module);
+}
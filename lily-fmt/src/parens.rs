@@ -0,0 +1,322 @@
+//! Inserts the parentheses that a tree needs in order to be printed.
+//!
+//! Trees that come from the parser already carry them as `Parenthesized`
+//! nodes, so this pass leaves them unchanged. Trees that are built by hand
+//! may nest operators against their fixities, or put a block where the
+//! grammar only allows an atom.
+
+use lily_lexer::symbol::Symbol;
+use lily_parser::{
+    types::{
+        Associativity, CaseArm, DoStatement, DoStatementK, Expression, ExpressionK, Fixity,
+        FixityMap, GreaterPattern, GreaterPatternK, Ty, TyK,
+    },
+    visit::{
+        walk_case_arm_mut, walk_do_statement_mut, walk_expression_mut, walk_greater_pattern_mut,
+        walk_ty_mut, VisitorMut,
+    },
+};
+
+pub(crate) struct Parenthesize<'a> {
+    pub(crate) value_fixities: &'a FixityMap,
+    pub(crate) type_fixities: &'a FixityMap,
+}
+
+/// Strips the module qualifier from an operator, like the parser does when
+/// it looks up a fixity.
+fn unqualified(operator: &str) -> &str {
+    let mut operator = operator;
+    while operator.starts_with(|c: char| c.is_uppercase()) {
+        match operator.find('.') {
+            Some(index) if index + 1 < operator.len() => operator = &operator[index + 1..],
+            _ => break,
+        }
+    }
+    operator
+}
+
+fn lookup(fixities: &FixityMap, operator: Symbol) -> Option<&Fixity> {
+    let name = unqualified(operator.as_str());
+    if name.len() == operator.as_str().len() {
+        fixities.get(&operator)
+    } else {
+        fixities.get(&Symbol::intern(name))
+    }
+}
+
+/// Whether an operator with `child` fixity can be the left operand of one
+/// with `parent` fixity without parentheses.
+fn fits_left(parent: &Fixity, child: &Fixity) -> bool {
+    parent.as_pair().0 < child.as_pair().1 && associates(parent, child)
+}
+
+/// Whether an operator with `child` fixity can be the right operand of one
+/// with `parent` fixity without parentheses.
+fn fits_right(parent: &Fixity, child: &Fixity) -> bool {
+    child.as_pair().0 >= parent.as_pair().1 && associates(parent, child)
+}
+
+/// Whether an operator with `parent` fixity can follow a left operand whose
+/// right edge has `operators`, from the outermost in, without any of them
+/// taking it over.
+fn fits_after(fixities: &FixityMap, parent: &Fixity, operators: &[Symbol]) -> bool {
+    operators
+        .iter()
+        .map_while(|&operator| lookup(fixities, operator))
+        .all(|child| fits_left(parent, child))
+}
+
+/// Like [`fits_after`], for the operators along the right edge of `left`.
+fn fits_after_expression(fixities: &FixityMap, parent: &Fixity, left: &Expression) -> bool {
+    let mut expression = left;
+    while let ExpressionK::BinaryOperator(_, operator, right) = &expression.kind {
+        let Some(child) = lookup(fixities, *operator) else {
+            break;
+        };
+        // Layout closes a block before the operator that follows it,
+        // whatever their binding powers, but the parser still rejects a
+        // chain of non-associative operators.
+        if is_closed_block(right) {
+            return associates(parent, child);
+        }
+        if !fits_left(parent, child) {
+            return false;
+        }
+        expression = right;
+    }
+    true
+}
+
+fn pattern_edge(pattern: &GreaterPattern) -> Vec<Symbol> {
+    let mut operators = vec![];
+    let mut pattern = pattern;
+    while let GreaterPatternK::BinaryOperator(_, operator, right) = &pattern.kind {
+        operators.push(*operator);
+        pattern = right;
+    }
+    operators
+}
+
+fn ty_edge(ty: &Ty) -> Vec<Symbol> {
+    let mut operators = vec![];
+    let mut ty = ty;
+    while let TyK::BinaryOperator(_, operator, right) = &ty.kind {
+        operators.push(*operator);
+        ty = right;
+    }
+    operators
+}
+
+fn associates(parent: &Fixity, child: &Fixity) -> bool {
+    let non_associative =
+        parent.associativity == Associativity::Infix || child.associativity == Associativity::Infix;
+    !non_associative || parent.binding_power != child.binding_power
+}
+
+/// A block that starts an expression, which the parser never continues
+/// into an application or an operator.
+fn is_block_headed(expression: &Expression) -> bool {
+    matches!(
+        expression.kind,
+        ExpressionK::CaseOf(..)
+            | ExpressionK::DoBlock(_)
+            | ExpressionK::IfThenElse(..)
+            | ExpressionK::Let(..)
+    )
+}
+
+/// An expression that ends with an `if` or a `let`, which would take over
+/// anything that follows it.
+fn ends_open(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionK::IfThenElse(..) | ExpressionK::Let(..) => true,
+        ExpressionK::Application(_, arguments) => arguments.last().is_some_and(ends_open),
+        ExpressionK::BinaryOperator(_, _, right) => ends_open(right),
+        _ => false,
+    }
+}
+
+/// An expression that ends with any block, which would take over the arrow
+/// of a guard that it ends.
+fn ends_in_block(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionK::Application(_, arguments) => arguments.last().is_some_and(ends_in_block),
+        ExpressionK::BinaryOperator(_, _, right) => ends_in_block(right),
+        _ => is_block_headed(expression),
+    }
+}
+
+/// A `do` or `case` block, which layout closes before what follows it.
+fn is_closed_block(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionK::DoBlock(statements) => !statements.is_empty(),
+        ExpressionK::CaseOf(..) => true,
+        _ => false,
+    }
+}
+
+fn wrap_expression(expression: &mut Expression) {
+    let placeholder = ExpressionK::Variable("".into());
+    let inner = std::mem::replace(&mut expression.kind, placeholder);
+    expression.kind = ExpressionK::Parenthesized(Box::new(Expression {
+        begin: expression.begin,
+        end: expression.end,
+        kind: inner,
+    }));
+}
+
+fn wrap_pattern(pattern: &mut GreaterPattern) {
+    let inner = std::mem::replace(&mut pattern.kind, GreaterPatternK::Null);
+    pattern.kind = GreaterPatternK::Parenthesized(Box::new(GreaterPattern {
+        begin: pattern.begin,
+        end: pattern.end,
+        kind: inner,
+    }));
+}
+
+fn wrap_ty(ty: &mut Ty) {
+    let inner = std::mem::replace(&mut ty.kind, TyK::Variable("".into()));
+    ty.kind = TyK::Parenthesized(Box::new(Ty {
+        begin: ty.begin,
+        end: ty.end,
+        kind: inner,
+    }));
+}
+
+impl VisitorMut for Parenthesize<'_> {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        match &mut expression.kind {
+            ExpressionK::Application(function, arguments) => {
+                let last = arguments.len().saturating_sub(1);
+                for (index, argument) in arguments.iter_mut().enumerate() {
+                    let wrap = match argument.kind {
+                        ExpressionK::Application(..) | ExpressionK::BinaryOperator(..) => true,
+                        // Only the first argument can be a block, and one that
+                        // ends open must also be the last.
+                        _ if is_block_headed(argument) => {
+                            index > 0 || index < last && ends_open(argument)
+                        }
+                        _ => false,
+                    };
+                    if wrap {
+                        wrap_expression(argument);
+                    }
+                }
+                // The parser only nests applications when a block argument
+                // follows them, and collects any other argument into the
+                // application before it.
+                let nests = arguments.first().is_some_and(is_block_headed);
+                if matches!(function.kind, ExpressionK::BinaryOperator(..))
+                    || matches!(function.kind, ExpressionK::Application(..)) && !nests
+                    || is_block_headed(function)
+                    || ends_open(function)
+                {
+                    wrap_expression(function);
+                }
+            }
+            ExpressionK::BinaryOperator(left, operator, right) => {
+                let parent = lookup(self.value_fixities, *operator);
+                let conflicts = parent.is_some_and(|parent| {
+                    !fits_after_expression(self.value_fixities, parent, left)
+                });
+                if conflicts || is_block_headed(left) || ends_open(left) {
+                    wrap_expression(left);
+                }
+                if let (ExpressionK::BinaryOperator(_, child, _), Some(parent)) =
+                    (&right.kind, parent)
+                {
+                    if lookup(self.value_fixities, *child)
+                        .is_some_and(|child| !fits_right(parent, child))
+                    {
+                        wrap_expression(right);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_case_arm_mut(&mut self, arm: &mut CaseArm) {
+        walk_case_arm_mut(self, arm);
+        if let Some(condition) = &mut arm.condition {
+            if ends_in_block(condition) {
+                wrap_expression(condition);
+            }
+        }
+    }
+
+    fn visit_do_statement_mut(&mut self, statement: &mut DoStatement) {
+        walk_do_statement_mut(self, statement);
+        // A statement that starts with `let` declares bindings instead.
+        if let DoStatementK::DiscardExpression(expression) = &mut statement.kind {
+            if let ExpressionK::Let(..) = expression.kind {
+                wrap_expression(expression);
+            }
+        }
+    }
+
+    fn visit_greater_pattern_mut(&mut self, pattern: &mut GreaterPattern) {
+        walk_greater_pattern_mut(self, pattern);
+        match &mut pattern.kind {
+            GreaterPatternK::Application(function, arguments) => {
+                if let GreaterPatternK::Application(..) | GreaterPatternK::BinaryOperator(..) =
+                    function.kind
+                {
+                    wrap_pattern(function);
+                }
+                for argument in arguments {
+                    if let GreaterPatternK::Application(..) | GreaterPatternK::BinaryOperator(..) =
+                        argument.kind
+                    {
+                        wrap_pattern(argument);
+                    }
+                }
+            }
+            GreaterPatternK::BinaryOperator(left, operator, right) => {
+                let Some(parent) = lookup(self.value_fixities, *operator) else {
+                    return;
+                };
+                if !fits_after(self.value_fixities, parent, &pattern_edge(left)) {
+                    wrap_pattern(left);
+                }
+                if let GreaterPatternK::BinaryOperator(_, child, _) = &right.kind {
+                    if lookup(self.value_fixities, *child).is_some_and(|c| !fits_right(parent, c)) {
+                        wrap_pattern(right);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_ty_mut(&mut self, ty: &mut Ty) {
+        walk_ty_mut(self, ty);
+        match &mut ty.kind {
+            TyK::Application(function, arguments) => {
+                if let TyK::Application(..) | TyK::BinaryOperator(..) = function.kind {
+                    wrap_ty(function);
+                }
+                for argument in arguments {
+                    if let TyK::Application(..) | TyK::BinaryOperator(..) = argument.kind {
+                        wrap_ty(argument);
+                    }
+                }
+            }
+            TyK::BinaryOperator(left, operator, right) => {
+                let Some(parent) = lookup(self.type_fixities, *operator) else {
+                    return;
+                };
+                if !fits_after(self.type_fixities, parent, &ty_edge(left)) {
+                    wrap_ty(left);
+                }
+                if let TyK::BinaryOperator(_, child, _) = &right.kind {
+                    if lookup(self.type_fixities, *child).is_some_and(|c| !fits_right(parent, c)) {
+                        wrap_ty(right);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,682 @@
+//! Builds the document for a module.
+//!
+//! Blocks are laid out the way `LayoutEngine` expects them: each `do`
+//! statement, `case` arm and `let` declaration starts a line at the same
+//! column, one indentation step past the line that opened the block. Any
+//! token that continues an expression after a block, other than `then`,
+//! `else`, `of` and `in`, goes on a new line between the column of that line
+//! and the column of the block, which is then indented by two steps.
+
+use lily_lexer::{indentation::Tabs, symbol::Symbol};
+use lily_parser::types::{
+    Associativity, CaseArm, Declaration, DeclarationK, DoStatement, DoStatementK, Domain,
+    Expression, ExpressionK, Fixity, GreaterPattern, GreaterPatternK, Import, ImportItem,
+    ImportItemK, ImportListK, ImportMembers, LesserPattern, LesserPatternK, Module, Ty, TyK,
+};
+
+use crate::{
+    comments::{self, Comment},
+    doc::{concat, group, hardline, line, nest, nil, softline, text, Doc},
+};
+
+/// A `do` or `case` block, which is always laid out vertically.
+fn is_block(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionK::DoBlock(statements) => !statements.is_empty(),
+        ExpressionK::CaseOf(..) => true,
+        _ => false,
+    }
+}
+
+/// Whether the last token of an expression is inside a block, such that the
+/// token after it has to close the block.
+fn ends_with_block(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionK::DoBlock(statements) => !statements.is_empty(),
+        ExpressionK::CaseOf(..) => true,
+        ExpressionK::Application(_, arguments) => arguments.last().is_some_and(ends_with_block),
+        ExpressionK::BinaryOperator(_, _, right) => ends_with_block(right),
+        ExpressionK::IfThenElse(_, _, expression) | ExpressionK::Let(_, expression) => {
+            ends_with_block(expression)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a run of applied expressions or operands ends with a block that
+/// can hang off the line that the run is on.
+fn is_hanging(parts: &[&Expression]) -> bool {
+    match parts {
+        [init @ .., last] => is_block(last) && !init.iter().copied().any(ends_with_block),
+        [] => false,
+    }
+}
+
+fn application_parts<'e>(expression: &'e Expression, parts: &mut Vec<&'e Expression>) {
+    match &expression.kind {
+        ExpressionK::Application(function, arguments) => {
+            parts.push(function);
+            parts.extend(arguments);
+        }
+        _ => parts.push(expression),
+    }
+}
+
+fn operator_parts<'e>(
+    expression: &'e Expression,
+    operands: &mut Vec<&'e Expression>,
+    operators: &mut Vec<Symbol>,
+) {
+    match &expression.kind {
+        ExpressionK::BinaryOperator(left, operator, right) => {
+            operator_parts(left, operands, operators);
+            operators.push(*operator);
+            operator_parts(right, operands, operators);
+        }
+        _ => operands.push(expression),
+    }
+}
+
+fn hangs(expression: &Expression) -> bool {
+    let mut parts = vec![];
+    match &expression.kind {
+        ExpressionK::Application(..) => application_parts(expression, &mut parts),
+        ExpressionK::BinaryOperator(..) => operator_parts(expression, &mut parts, &mut vec![]),
+        _ => return is_block(expression),
+    }
+    is_hanging(&parts)
+}
+
+enum Item<'a> {
+    Import(&'a Import),
+    Fixity(&'a Fixity),
+    Declaration(&'a Declaration),
+}
+
+impl Item<'_> {
+    fn begin(&self) -> usize {
+        match self {
+            Item::Import(import) => import.begin,
+            Item::Fixity(fixity) => fixity.begin,
+            Item::Declaration(declaration) => declaration.begin,
+        }
+    }
+
+    /// Whether two items are kept on consecutive lines rather than being
+    /// separated by a blank line.
+    fn is_adjacent(&self, next: &Item) -> bool {
+        match (self, next) {
+            (Item::Import(_), Item::Import(_)) | (Item::Fixity(_), Item::Fixity(_)) => true,
+            (Item::Declaration(signature), Item::Declaration(value)) => matches!(
+                (&signature.kind, &value.kind),
+                (
+                    DeclarationK::TypeDeclaration(signature, _),
+                    DeclarationK::ValueDeclaration(value, _, _),
+                ) if signature == value
+            ),
+            _ => false,
+        }
+    }
+}
+
+pub(crate) struct Printer<'a> {
+    source: &'a str,
+    indent: usize,
+    comments: Vec<Comment<'a>>,
+    next_comment: usize,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(source: &'a str, indent: usize, tabs: Tabs) -> Self {
+        Self {
+            source,
+            indent,
+            comments: comments::comments(source, tabs),
+            next_comment: 0,
+        }
+    }
+
+    fn take_comment(&mut self, offset: usize) -> Option<Comment<'a>> {
+        let comment = self.comments.get(self.next_comment)?;
+        if comment.begin < offset {
+            self.next_comment += 1;
+            Some(comment.clone())
+        } else {
+            None
+        }
+    }
+
+    /// A comment at the end of the line before an item, which is kept there.
+    fn trailing_comment(&mut self, offset: usize) -> Option<Doc> {
+        let comment = self.comments.get(self.next_comment)?;
+        if comment.trailing && comment.begin < offset {
+            let comment = concat([text(" "), text(comment.text)]);
+            self.next_comment += 1;
+            Some(comment)
+        } else {
+            None
+        }
+    }
+
+    /// The comments before an item at the start of a line, each on its own
+    /// line, keeping the blank lines between them.
+    fn leading_comments(&mut self, offset: usize) -> Doc {
+        let mut docs = vec![];
+        while let Some(comment) = self.take_comment(offset) {
+            docs.push(text(comment.text));
+            docs.push(hardline());
+            if comment.blank_after {
+                docs.push(hardline());
+            }
+        }
+        concat(docs)
+    }
+
+    /// The comments before a node in the middle of a line. A line comment
+    /// pushes the node to the next line, one indentation step further.
+    fn comments(&mut self, offset: usize) -> Doc {
+        let mut docs = vec![];
+        while let Some(comment) = self.take_comment(offset) {
+            docs.push(text(comment.text));
+            if comment.is_line() {
+                docs.push(nest(self.indent, hardline()));
+            } else {
+                docs.push(text(" "));
+            }
+        }
+        concat(docs)
+    }
+
+    pub(crate) fn module(&mut self, module: &Module) -> Doc {
+        let mut items: Vec<_> = (module.imports.iter().map(Item::Import))
+            .chain(module.fixities.iter().map(Item::Fixity))
+            .chain(module.declarations.iter().map(Item::Declaration))
+            .collect();
+        items.sort_by_key(Item::begin);
+
+        let mut docs = vec![];
+        let mut previous: Option<&Item> = None;
+        for item in &items {
+            if let Some(previous) = previous {
+                docs.extend(self.trailing_comment(item.begin()));
+                docs.push(hardline());
+                if !previous.is_adjacent(item) {
+                    docs.push(hardline());
+                }
+            }
+            docs.push(self.leading_comments(item.begin()));
+            docs.push(match item {
+                Item::Import(import) => self.import(import),
+                Item::Fixity(fixity) => self.fixity(fixity),
+                Item::Declaration(declaration) => self.declaration(declaration),
+            });
+            previous = Some(item);
+        }
+
+        docs.extend(self.trailing_comment(usize::MAX));
+        let trailing = self.comments[self.next_comment..].to_vec();
+        for (index, comment) in trailing.iter().enumerate() {
+            if index > 0 || previous.is_some() {
+                docs.push(hardline());
+                if comment.blank_before {
+                    docs.push(hardline());
+                }
+            }
+            docs.push(text(comment.text));
+        }
+        self.next_comment = self.comments.len();
+
+        if !docs.is_empty() {
+            docs.push(hardline());
+        }
+        concat(docs)
+    }
+
+    fn import(&mut self, import: &Import) -> Doc {
+        let mut header = format!("import {}", import.module_name);
+        if import.qualified {
+            header.push_str(" qualified");
+        }
+        if let Some(alias) = &import.alias {
+            header.push_str(" as ");
+            header.push_str(alias.as_str());
+        }
+        let Some(list) = &import.list else {
+            return text(header);
+        };
+
+        let items = match &list.kind {
+            ImportListK::Hiding(items) => {
+                header.push_str(" hiding");
+                items
+            }
+            ImportListK::Only(items) => items,
+        };
+        let mut docs = vec![softline()];
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                docs.push(text(","));
+                docs.push(line());
+            }
+            docs.push(text(import_item(item)));
+        }
+        concat([
+            text(header),
+            text(" "),
+            group(concat([
+                text("("),
+                nest(self.indent, concat(docs)),
+                text(")"),
+            ])),
+        ])
+    }
+
+    fn fixity(&mut self, fixity: &Fixity) -> Doc {
+        let associativity = match fixity.associativity {
+            Associativity::Infix => "infix",
+            Associativity::Infixl => "infixl",
+            Associativity::Infixr => "infixr",
+        };
+        let domain = match fixity.domain {
+            Domain::Type => " type",
+            Domain::Value => "",
+        };
+        text(format!(
+            "{} {}{} {} as {}",
+            associativity, fixity.binding_power, domain, fixity.identifier, fixity.operator
+        ))
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) -> Doc {
+        let comments = self.comments(declaration.begin);
+        let doc = match &declaration.kind {
+            DeclarationK::ValueDeclaration(name, patterns, expression) => {
+                let mut docs = vec![text(name.as_str())];
+                for pattern in patterns {
+                    docs.push(text(" "));
+                    docs.push(self.lesser_pattern(pattern));
+                }
+                docs.push(text(" ="));
+                docs.push(self.body(expression));
+                concat(docs)
+            }
+            DeclarationK::TypeDeclaration(name, ty) => {
+                concat([text(format!("{} : ", name)), self.ty(ty)])
+            }
+            DeclarationK::ForeignValue(name, ty) => {
+                concat([text(format!("foreign import {} : ", name)), self.ty(ty)])
+            }
+            DeclarationK::ForeignData(name, ty) => concat([
+                text(format!("foreign import data {} : ", name)),
+                self.ty(ty),
+            ]),
+            // A declaration that failed to parse is kept as written, along
+            // with the comments inside of it.
+            DeclarationK::Error => {
+                while self.take_comment(declaration.end).is_some() {}
+                text(self.source[declaration.begin..declaration.end].trim_end())
+            }
+        };
+        concat([comments, doc])
+    }
+
+    /// The expression after `=`, `<-` or `->`, which either hangs off the
+    /// same line or moves to the next one when it does not fit.
+    fn body(&mut self, expression: &Expression) -> Doc {
+        if hangs(expression) {
+            concat([text(" "), self.expression(expression, false)])
+        } else {
+            let expression = self.expression(expression, false);
+            group(nest(self.indent, concat([line(), expression])))
+        }
+    }
+
+    /// The statements or arms of a block. A block that is followed by more
+    /// tokens is indented by two steps, so that they fit in between.
+    fn block<T>(
+        &mut self,
+        items: &[T],
+        begin: fn(&T) -> usize,
+        print: fn(&mut Self, &T) -> Doc,
+        followed: bool,
+    ) -> Doc {
+        let mut docs = vec![];
+        for item in items {
+            docs.extend(self.trailing_comment(begin(item)));
+            docs.push(hardline());
+            docs.push(self.leading_comments(begin(item)));
+            docs.push(print(self, item));
+        }
+        let indent = if followed {
+            2 * self.indent
+        } else {
+            self.indent
+        };
+        nest(indent, concat(docs))
+    }
+
+    fn let_declarations(&mut self, declarations: &[Declaration]) -> Doc {
+        let mut docs = vec![];
+        for (index, declaration) in declarations.iter().enumerate() {
+            docs.push(match self.trailing_comment(declaration.begin) {
+                Some(comment) => concat([comment, hardline()]),
+                None if index == 0 => line(),
+                None => hardline(),
+            });
+            docs.push(self.leading_comments(declaration.begin));
+            docs.push(self.declaration(declaration));
+        }
+        nest(self.indent, concat(docs))
+    }
+
+    /// The separator before a token that follows `previous`, which has to
+    /// start a new line if `previous` ends with a block.
+    fn separator(previous: &Expression) -> Doc {
+        if ends_with_block(previous) {
+            hardline()
+        } else {
+            line()
+        }
+    }
+
+    /// Prints an expression; `followed` is set when more tokens come after it
+    /// that do not close its blocks by themselves.
+    fn expression(&mut self, expression: &Expression, followed: bool) -> Doc {
+        let comments = self.comments(expression.begin);
+        let doc = match &expression.kind {
+            ExpressionK::Variable(name) | ExpressionK::Constructor(name) => text(name.as_str()),
+            ExpressionK::Integer(literal) | ExpressionK::Float(literal) => text(literal.as_str()),
+            ExpressionK::Parenthesized(inner) => {
+                let ends = ends_with_block(inner);
+                let inner = self.expression(inner, ends);
+                let close = if ends {
+                    nest(self.indent, hardline())
+                } else {
+                    nil()
+                };
+                concat([text("("), inner, close, text(")")])
+            }
+            ExpressionK::Application(..) => {
+                let mut parts = vec![];
+                application_parts(expression, &mut parts);
+                self.application(&parts, followed)
+            }
+            ExpressionK::BinaryOperator(..) => {
+                let (mut operands, mut operators) = (vec![], vec![]);
+                operator_parts(expression, &mut operands, &mut operators);
+                self.operator_chain(&operands, &operators, followed)
+            }
+            ExpressionK::IfThenElse(condition, then_value, else_value) => {
+                let condition = self.expression(condition, false);
+                let then_value = self.expression(then_value, false);
+                let else_value = self.expression(else_value, followed);
+                group(concat([
+                    text("if "),
+                    condition,
+                    nest(
+                        self.indent,
+                        concat([
+                            line(),
+                            text("then "),
+                            then_value,
+                            line(),
+                            text("else "),
+                            else_value,
+                        ]),
+                    ),
+                ]))
+            }
+            ExpressionK::Let(declarations, body) => {
+                let declarations = self.let_declarations(declarations);
+                let before_in = match self.trailing_comment(body.begin) {
+                    Some(comment) => concat([comment, hardline()]),
+                    None => line(),
+                };
+                let body = self.expression(body, followed);
+                group(concat([
+                    text("let"),
+                    declarations,
+                    before_in,
+                    text("in"),
+                    nest(self.indent, concat([line(), body])),
+                ]))
+            }
+            ExpressionK::DoBlock(statements) if statements.is_empty() => text("do"),
+            ExpressionK::DoBlock(statements) => {
+                let statements = self.block(
+                    statements,
+                    |statement| statement.begin,
+                    Self::do_statement,
+                    followed,
+                );
+                concat([text("do"), statements])
+            }
+            ExpressionK::CaseOf(heads, arms) => {
+                let mut docs = vec![text("case ")];
+                for (index, head) in heads.iter().enumerate() {
+                    if index > 0 {
+                        if ends_with_block(&heads[index - 1]) {
+                            docs.push(nest(self.indent, hardline()));
+                        }
+                        docs.push(text(", "));
+                    }
+                    let followed = index + 1 < heads.len() && ends_with_block(head);
+                    docs.push(self.expression(head, followed));
+                }
+                docs.push(text(" of"));
+                docs.push(self.block(
+                    arms,
+                    |arm| arm.patterns.first().map_or(0, |pattern| pattern.begin),
+                    Self::case_arm,
+                    followed,
+                ));
+                concat(docs)
+            }
+        };
+        concat([comments, doc])
+    }
+
+    fn application(&mut self, parts: &[&Expression], followed: bool) -> Doc {
+        let last = parts.len() - 1;
+        let mut docs = vec![];
+        for (index, part) in parts.iter().enumerate() {
+            let followed = if index == last {
+                followed
+            } else {
+                ends_with_block(part)
+            };
+            docs.push(self.expression(part, followed));
+        }
+
+        if is_hanging(parts) {
+            let block = docs.pop().unwrap_or_else(nil);
+            let mut docs = docs.into_iter();
+            let function = docs.next().unwrap_or_else(nil);
+            let arguments = concat(docs.flat_map(|argument| [line(), argument]));
+            return concat([
+                group(concat([function, nest(self.indent, arguments)])),
+                text(" "),
+                block,
+            ]);
+        }
+
+        let mut docs = docs.into_iter();
+        let function = docs.next().unwrap_or_else(nil);
+        let arguments = concat(
+            parts
+                .iter()
+                .zip(docs)
+                .flat_map(|(previous, argument)| [Self::separator(previous), argument]),
+        );
+        group(concat([function, nest(self.indent, arguments)]))
+    }
+
+    fn operator_chain(
+        &mut self,
+        operands: &[&Expression],
+        operators: &[Symbol],
+        followed: bool,
+    ) -> Doc {
+        let last = operands.len() - 1;
+        let mut docs = vec![];
+        for (index, operand) in operands.iter().enumerate() {
+            let followed = if index == last {
+                followed
+            } else {
+                ends_with_block(operand)
+            };
+            docs.push(self.expression(operand, followed));
+        }
+
+        let hanging = is_hanging(operands);
+        let block = if hanging { docs.pop() } else { None };
+
+        let mut docs = docs.into_iter();
+        let first = docs.next().unwrap_or_else(nil);
+        let rest = concat(operands.iter().zip(operators).zip(docs).flat_map(
+            |((previous, operator), operand)| {
+                [
+                    Self::separator(previous),
+                    text(format!("{} ", operator)),
+                    operand,
+                ]
+            },
+        ));
+        let chain = group(concat([first, nest(self.indent, rest)]));
+
+        match block {
+            Some(block) => {
+                let operator = operators[last - 1];
+                concat([chain, text(format!(" {} ", operator)), block])
+            }
+            None => chain,
+        }
+    }
+
+    fn do_statement(&mut self, statement: &DoStatement) -> Doc {
+        match &statement.kind {
+            DoStatementK::BindExpression(pattern, expression) => {
+                let pattern = self.lesser_pattern(pattern);
+                concat([pattern, text(" <-"), self.body(expression)])
+            }
+            DoStatementK::DiscardExpression(expression) => self.expression(expression, false),
+            DoStatementK::LetStatement(declarations) => {
+                let comments = self.comments(statement.begin);
+                let declarations = self.let_declarations(declarations);
+                concat([comments, group(concat([text("let"), declarations]))])
+            }
+        }
+    }
+
+    fn case_arm(&mut self, arm: &CaseArm) -> Doc {
+        let mut docs = vec![];
+        for (index, pattern) in arm.patterns.iter().enumerate() {
+            if index > 0 {
+                docs.push(text(", "));
+            }
+            docs.push(self.greater_pattern(pattern));
+        }
+        if let Some(condition) = &arm.condition {
+            docs.push(text(" if "));
+            docs.push(self.expression(condition, false));
+        }
+        docs.push(text(" ->"));
+        docs.push(self.body(&arm.expression));
+        concat(docs)
+    }
+
+    fn lesser_pattern(&mut self, pattern: &LesserPattern) -> Doc {
+        let comments = self.comments(pattern.begin);
+        let doc = match &pattern.kind {
+            LesserPatternK::Null => text("_"),
+            LesserPatternK::Variable(name) => text(name.as_str()),
+        };
+        concat([comments, doc])
+    }
+
+    fn greater_pattern(&mut self, pattern: &GreaterPattern) -> Doc {
+        let comments = self.comments(pattern.begin);
+        let doc = match &pattern.kind {
+            GreaterPatternK::Application(function, arguments) => {
+                let mut docs = vec![self.greater_pattern(function)];
+                for argument in arguments {
+                    docs.push(text(" "));
+                    docs.push(self.greater_pattern(argument));
+                }
+                concat(docs)
+            }
+            GreaterPatternK::BinaryOperator(left, operator, right) => concat([
+                self.greater_pattern(left),
+                text(format!(" {} ", operator)),
+                self.greater_pattern(right),
+            ]),
+            GreaterPatternK::Constructor(name) | GreaterPatternK::Variable(name) => {
+                text(name.as_str())
+            }
+            GreaterPatternK::Integer(literal) => text(literal.as_str()),
+            GreaterPatternK::Null => text("_"),
+            GreaterPatternK::Parenthesized(inner) => {
+                concat([text("("), self.greater_pattern(inner), text(")")])
+            }
+        };
+        concat([comments, doc])
+    }
+
+    /// Prints a type, breaking before each operator when it does not fit.
+    fn ty(&mut self, ty: &Ty) -> Doc {
+        let mut operands = vec![];
+        let mut operators = vec![];
+        ty_operator_parts(ty, &mut operands, &mut operators);
+
+        let mut docs = operands.iter().map(|operand| self.ty_operand(operand));
+        let first = docs.next().unwrap_or_else(nil);
+        let rest: Vec<_> = operators
+            .iter()
+            .zip(docs)
+            .flat_map(|(operator, operand)| [line(), text(format!("{} ", operator)), operand])
+            .collect();
+        group(concat([first, nest(self.indent, concat(rest))]))
+    }
+
+    fn ty_operand(&mut self, ty: &Ty) -> Doc {
+        let comments = self.comments(ty.begin);
+        let doc = match &ty.kind {
+            TyK::Application(function, arguments) => {
+                let function = self.ty_operand(function);
+                let arguments: Vec<_> = arguments
+                    .iter()
+                    .flat_map(|argument| [line(), self.ty_operand(argument)])
+                    .collect();
+                group(concat([function, nest(self.indent, concat(arguments))]))
+            }
+            TyK::BinaryOperator(..) => self.ty(ty),
+            TyK::Constructor(name) | TyK::Variable(name) => text(name.as_str()),
+            TyK::Parenthesized(inner) => concat([text("("), self.ty(inner), text(")")]),
+        };
+        concat([comments, doc])
+    }
+}
+
+fn ty_operator_parts<'t>(ty: &'t Ty, operands: &mut Vec<&'t Ty>, operators: &mut Vec<Symbol>) {
+    match &ty.kind {
+        TyK::BinaryOperator(left, operator, right) => {
+            ty_operator_parts(left, operands, operators);
+            operators.push(*operator);
+            ty_operator_parts(right, operands, operators);
+        }
+        _ => operands.push(ty),
+    }
+}
+
+fn import_item(item: &ImportItem) -> String {
+    match &item.kind {
+        ImportItemK::Operator(operator) => format!("({})", operator),
+        ImportItemK::Type(name, None) => name.to_string(),
+        ImportItemK::Type(name, Some(ImportMembers::All)) => format!("{}(..)", name),
+        ImportItemK::Type(name, Some(ImportMembers::Listed(members))) => {
+            let members: Vec<_> = members.iter().map(|member| member.as_str()).collect();
+            format!("{}({})", name, members.join(", "))
+        }
+        ImportItemK::Value(name) => name.to_string(),
+    }
+}
@@ -0,0 +1,70 @@
+use lily_parser::{
+    types::{
+        Declaration, DoStatement, Expression, Fixity, GreaterPattern, Import, ImportItem,
+        ImportList, LesserPattern, Ty,
+    },
+    visit::{
+        walk_declaration_mut, walk_do_statement_mut, walk_expression_mut, walk_greater_pattern_mut,
+        walk_import_list_mut, walk_import_mut, walk_ty_mut, VisitorMut,
+    },
+};
+
+/// Resets every span, so that modules parsed from different sources can be
+/// compared.
+pub struct Erase;
+
+impl Erase {
+    fn span(begin: &mut usize, end: &mut usize) {
+        *begin = 0;
+        *end = 0;
+    }
+}
+
+impl VisitorMut for Erase {
+    fn visit_import_mut(&mut self, import: &mut Import) {
+        Self::span(&mut import.begin, &mut import.end);
+        walk_import_mut(self, import);
+    }
+
+    fn visit_import_list_mut(&mut self, list: &mut ImportList) {
+        Self::span(&mut list.begin, &mut list.end);
+        walk_import_list_mut(self, list);
+    }
+
+    fn visit_import_item_mut(&mut self, item: &mut ImportItem) {
+        Self::span(&mut item.begin, &mut item.end);
+    }
+
+    fn visit_fixity_mut(&mut self, fixity: &mut Fixity) {
+        Self::span(&mut fixity.begin, &mut fixity.end);
+    }
+
+    fn visit_declaration_mut(&mut self, declaration: &mut Declaration) {
+        Self::span(&mut declaration.begin, &mut declaration.end);
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        Self::span(&mut expression.begin, &mut expression.end);
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_do_statement_mut(&mut self, statement: &mut DoStatement) {
+        Self::span(&mut statement.begin, &mut statement.end);
+        walk_do_statement_mut(self, statement);
+    }
+
+    fn visit_lesser_pattern_mut(&mut self, pattern: &mut LesserPattern) {
+        Self::span(&mut pattern.begin, &mut pattern.end);
+    }
+
+    fn visit_greater_pattern_mut(&mut self, pattern: &mut GreaterPattern) {
+        Self::span(&mut pattern.begin, &mut pattern.end);
+        walk_greater_pattern_mut(self, pattern);
+    }
+
+    fn visit_ty_mut(&mut self, ty: &mut Ty) {
+        Self::span(&mut ty.begin, &mut ty.end);
+        walk_ty_mut(self, ty);
+    }
+}
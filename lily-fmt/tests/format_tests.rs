@@ -0,0 +1,240 @@
+mod common;
+
+use lily_fmt::{format, format_module, Config};
+use lily_lexer::indentation::Tabs;
+use lily_parser::{
+    parse_top_level,
+    types::{Expression, ExpressionK, Module},
+    visit::{walk_expression_mut, VisitorMut},
+};
+
+use self::common::Erase;
+
+const CORPUS: &[&str] = &[
+    "",
+    "\n\n  \n",
+    "-- only a comment",
+    "{- only a block comment -}\n",
+    r"
+infixl 1 add as +
+infixl 2 mul as *
+infixr 3 pow as ^
+
+example = a + b * (c + d) + e ^ f
+",
+    r"
+import Data.List (map, Maybe(..), (<>))
+import Data.Map qualified as Map hiding (insert)
+
+-- | Documented.
+example a b c = a b c -- trailing
+",
+    "
+example = do
+  let
+    u = 21
+    v = 21
+  w <- pure 21
+  attempt do
+    y <- pure 21
+",
+    "
+example = case a, b of
+  Cons a b, _ if hello -> do
+    world
+  _, Nil -> do
+    example
+",
+    "
+example = f
+  let
+    a = b
+    c = d
+  in
+    a c
+",
+    "
+infixr 9 type Function as ->
+
+identity : (a -> a) -> Maybe a b
+identity = {- inline -} x
+",
+    "
+infixr 9 type Function as ->
+
+foreign import data Effect : Type -> Type
+foreign import log : String -> Effect Unit
+",
+    "example = if a then b else c\r\nother = 1.5\r\n",
+    "
+infixl 4 add as +
+infixr 0 apply as $
+
+main = launch $ do
+  x <- (do
+      a
+      b
+    ) + c
+  f do
+      y
+    z
+  case do
+      p
+    , q of
+    _, _ -> if a then do
+        b
+      else do
+        c
+
+-- | Adds.
+
+-- Detached.
+add a b = a + b -- at the end of a line
+-- | Trailing.
+",
+    "
+example = someFunction argumentNumberOne argumentNumberTwo argumentNumberThree argumentNumberFour
+",
+    "
+example = let x = 1 in x
+other = let
+    -- | The first.
+    x = 1
+
+    -- A comment.
+    y : Int
+    y = 2
+  in x
+",
+];
+
+fn erased(source: &str) -> Module {
+    let (mut module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![], "{}", source);
+    Erase.visit_module_mut(&mut module);
+    module
+}
+
+fn comments(source: &str) -> Vec<&str> {
+    lily_lexer::lex_with_trivia(source, Tabs::default())
+        .into_iter()
+        .filter(|token| {
+            matches!(
+                token.kind,
+                lily_lexer::types::TokenK::Trivia(
+                    lily_lexer::types::TriviaK::BlockComment
+                        | lily_lexer::types::TriviaK::DocComment
+                        | lily_lexer::types::TriviaK::LineComment
+                )
+            )
+        })
+        .map(|token| source[token.begin..token.end].trim_end())
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    for width in [80, 20] {
+        let config = Config {
+            width,
+            ..Config::default()
+        };
+        for source in CORPUS {
+            let formatted = format(source, &config).unwrap();
+            assert_eq!(erased(source), erased(&formatted), "{}", formatted);
+            assert_eq!(comments(source), comments(&formatted), "{}", formatted);
+            assert_eq!(format(&formatted, &config).unwrap(), formatted);
+        }
+    }
+}
+
+#[test]
+fn canonical_layout() {
+    let source = "
+infixl 4 add as +
+infixr 0 apply as $
+example : Int
+example = launch $ do
+     x <- pure 1
+     -- | Doubles.
+     let
+        y = x + x
+     attempt
+        do
+          pure y
+other a = case a of
+        Just b if b -> b
+        _ -> if a then b else c
+";
+    insta::assert_snapshot!(format(source, &Config::default()).unwrap());
+}
+
+#[test]
+fn tab_indentation() {
+    let source = "example = do\n\ta\n\tb\n";
+    assert_eq!(format(source, &Config::default()).unwrap_err().len(), 2);
+
+    let config = Config {
+        tabs: Tabs::Expand(4),
+        ..Config::default()
+    };
+    assert_eq!(format(source, &config).unwrap(), "example = do\n  a\n  b\n");
+}
+
+#[test]
+fn line_width() {
+    let source = "
+infixl 4 add as +
+
+example = f (g alpha beta) gamma + h delta
+";
+    let narrow = Config {
+        width: 24,
+        indent: 4,
+        ..Config::default()
+    };
+    insta::assert_snapshot!(format(source, &narrow).unwrap());
+}
+
+#[test]
+fn parenthesizes_against_fixities() {
+    let source = "
+infixl 4 add as +
+infixl 5 mul as *
+
+example = a * (b + c)
+";
+    struct Strip;
+    impl VisitorMut for Strip {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            walk_expression_mut(self, expression);
+            if let ExpressionK::Parenthesized(inner) = &expression.kind {
+                *expression = (**inner).clone();
+            }
+        }
+    }
+
+    let (mut module, _) = parse_top_level(source);
+    Strip.visit_module_mut(&mut module);
+    assert_eq!(
+        format_module(source, &module, &Config::default()),
+        format(source, &Config::default()).unwrap()
+    );
+}
+
+#[test]
+fn keeps_error_declarations() {
+    let source = "
+first   =   1
+
+second = ) a -- unbalanced
+
+third   =   3
+";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        format_module(source, &module, &Config::default()),
+        "first = 1\n\nsecond = ) a -- unbalanced\n\nthird = 3\n"
+    );
+}
@@ -0,0 +1,375 @@
+//! Round trips generated modules through the printer and the parser.
+//!
+//! The generators follow the grammar, but not its precedence and layout
+//! rules: operators are nested regardless of their fixities, and blocks may
+//! appear anywhere an expression can. `parenthesize` turns them into the
+//! trees that the parser produces, which printing and parsing again must
+//! give back modulo spans.
+
+mod common;
+
+use lily_fmt::{format_module, parenthesize, Config};
+use lily_lexer::symbol::Symbol;
+use lily_parser::{
+    parse_top_level,
+    types::{
+        Associativity, CaseArm, Declaration, DeclarationK, DoStatement, DoStatementK, Domain,
+        Expression, ExpressionK, Fixity, GreaterPattern, GreaterPatternK, Import, ImportItem,
+        ImportItemK, ImportList, ImportListK, ImportMembers, LesserPattern, LesserPatternK, Module,
+        Ty, TyK,
+    },
+    validate::validate,
+    visit::VisitorMut,
+};
+use proptest::{prelude::*, sample::select};
+
+use self::common::Erase;
+
+const VALUE_OPERATORS: &[(&str, &str)] = &[
+    ("add", "+"),
+    ("mul", "*"),
+    ("append", "<>"),
+    ("eq", "=="),
+    ("apply", "$"),
+];
+const TYPE_OPERATORS: &[(&str, &str)] = &[("Function", "->"), ("Natural", "~>")];
+
+const VARIABLES: &[&str] = &["a", "b", "f", "go", "x1"];
+const QUALIFIED_VARIABLES: &[&str] = &["Map.insert", "Data.List.map"];
+const CONSTRUCTORS: &[&str] = &["Just", "Nothing", "Cons", "Data.Maybe.Just"];
+const PROPER_NAMES: &[&str] = &["Maybe", "Effect", "Map"];
+const MODULE_NAMES: &[&str] = &["Prelude", "Data.List", "Data.Map"];
+
+fn name(names: &'static [&'static str]) -> impl Strategy<Value = Symbol> {
+    select(names).prop_map(Symbol::intern)
+}
+
+fn variable() -> impl Strategy<Value = Symbol> {
+    prop_oneof![4 => name(VARIABLES), 1 => name(QUALIFIED_VARIABLES)]
+}
+
+fn value_operator() -> impl Strategy<Value = Symbol> {
+    (select(VALUE_OPERATORS), any::<bool>()).prop_map(|((_, operator), qualified)| {
+        if qualified {
+            Symbol::intern(&format!("Prelude.{}", operator))
+        } else {
+            operator.into()
+        }
+    })
+}
+
+fn type_operator() -> impl Strategy<Value = Symbol> {
+    select(TYPE_OPERATORS).prop_map(|(_, operator)| operator.into())
+}
+
+fn associativity() -> impl Strategy<Value = Associativity> {
+    prop_oneof![
+        Just(Associativity::Infix),
+        Just(Associativity::Infixl),
+        Just(Associativity::Infixr),
+    ]
+}
+
+fn fixities() -> impl Strategy<Value = Vec<Fixity>> {
+    let operators = VALUE_OPERATORS
+        .iter()
+        .map(|operator| (Domain::Value, operator))
+        .chain(
+            TYPE_OPERATORS
+                .iter()
+                .map(|operator| (Domain::Type, operator)),
+        );
+    operators
+        .map(|(domain, (identifier, operator))| {
+            (associativity(), 0..10u8).prop_map(move |(associativity, binding_power)| Fixity {
+                begin: 0,
+                end: 0,
+                associativity,
+                domain,
+                binding_power,
+                identifier: (*identifier).into(),
+                operator: (*operator).into(),
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+fn import_item() -> impl Strategy<Value = ImportItem> {
+    let members = prop_oneof![
+        Just(None),
+        Just(Some(ImportMembers::All)),
+        prop::collection::vec(name(CONSTRUCTORS.split_at(3).0), 0..3)
+            .prop_map(|names| Some(ImportMembers::Listed(names))),
+    ];
+    prop_oneof![
+        name(VARIABLES).prop_map(ImportItemK::Value),
+        (name(PROPER_NAMES), members).prop_map(|(name, members)| ImportItemK::Type(name, members)),
+        select(VALUE_OPERATORS).prop_map(|(_, operator)| ImportItemK::Operator(operator.into())),
+    ]
+    .prop_map(|kind| ImportItem {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+}
+
+fn import() -> impl Strategy<Value = Import> {
+    let list = prop::collection::vec(import_item(), 0..3);
+    let list = prop::option::of(
+        (any::<bool>(), list).prop_map(|(hiding, items)| ImportList {
+            begin: 0,
+            end: 0,
+            kind: if hiding {
+                ImportListK::Hiding(items)
+            } else {
+                ImportListK::Only(items)
+            },
+        }),
+    );
+    (
+        name(MODULE_NAMES),
+        any::<bool>(),
+        prop::option::of(name(PROPER_NAMES)),
+        list,
+    )
+        .prop_map(|(module_name, qualified, alias, list)| Import {
+            begin: 0,
+            end: 0,
+            module_name,
+            qualified,
+            alias,
+            list,
+        })
+}
+
+fn ty() -> impl Strategy<Value = Ty> {
+    let leaf = prop_oneof![
+        name(PROPER_NAMES).prop_map(TyK::Constructor),
+        name(VARIABLES).prop_map(TyK::Variable),
+    ];
+    leaf.prop_map(|kind| Ty {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+    .prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), prop::collection::vec(inner.clone(), 1..3))
+                .prop_map(|(function, arguments)| TyK::Application(Box::new(function), arguments)),
+            (inner.clone(), type_operator(), inner.clone()).prop_map(|(left, operator, right)| {
+                TyK::BinaryOperator(Box::new(left), operator, Box::new(right))
+            }),
+            inner.prop_map(|ty| TyK::Parenthesized(Box::new(ty))),
+        ]
+        .prop_map(|kind| Ty {
+            begin: 0,
+            end: 0,
+            kind,
+        })
+    })
+}
+
+fn lesser_pattern() -> impl Strategy<Value = LesserPattern> {
+    prop_oneof![
+        1 => Just(LesserPatternK::Null),
+        3 => name(VARIABLES).prop_map(LesserPatternK::Variable),
+    ]
+    .prop_map(|kind| LesserPattern {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+}
+
+fn greater_pattern() -> impl Strategy<Value = GreaterPattern> {
+    let leaf = prop_oneof![
+        name(CONSTRUCTORS).prop_map(GreaterPatternK::Constructor),
+        (0..100u8).prop_map(|value| GreaterPatternK::Integer(value.to_string().into())),
+        Just(GreaterPatternK::Null),
+        name(VARIABLES).prop_map(GreaterPatternK::Variable),
+    ];
+    leaf.prop_map(|kind| GreaterPattern {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+    .prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), prop::collection::vec(inner.clone(), 1..3)).prop_map(
+                |(function, arguments)| {
+                    GreaterPatternK::Application(Box::new(function), arguments)
+                }
+            ),
+            (inner.clone(), value_operator(), inner.clone()).prop_map(|(left, operator, right)| {
+                GreaterPatternK::BinaryOperator(Box::new(left), operator, Box::new(right))
+            }),
+            inner.prop_map(|pattern| GreaterPatternK::Parenthesized(Box::new(pattern))),
+        ]
+        .prop_map(|kind| GreaterPattern {
+            begin: 0,
+            end: 0,
+            kind,
+        })
+    })
+}
+
+fn declaration(kind: DeclarationK) -> Declaration {
+    Declaration {
+        begin: 0,
+        end: 0,
+        docs: None,
+        kind,
+    }
+}
+
+/// Declarations that can appear in a `let`, given a strategy for their
+/// expressions.
+fn let_declaration(
+    expression: impl Strategy<Value = Expression>,
+) -> impl Strategy<Value = Declaration> {
+    let value = (
+        name(VARIABLES),
+        prop::collection::vec(lesser_pattern(), 0..3),
+        expression,
+    );
+    prop_oneof![
+        3 => value.prop_map(|(name, patterns, expression)| {
+            DeclarationK::ValueDeclaration(name, patterns, expression)
+        }),
+        1 => (name(VARIABLES), ty()).prop_map(|(name, ty)| DeclarationK::TypeDeclaration(name, ty)),
+    ]
+    .prop_map(declaration)
+}
+
+fn do_statement(
+    expression: impl Strategy<Value = Expression> + Clone,
+) -> impl Strategy<Value = DoStatement> {
+    prop_oneof![
+        (lesser_pattern(), expression.clone())
+            .prop_map(|(pattern, expression)| DoStatementK::BindExpression(pattern, expression)),
+        expression.clone().prop_map(DoStatementK::DiscardExpression),
+        prop::collection::vec(let_declaration(expression), 1..3)
+            .prop_map(DoStatementK::LetStatement),
+    ]
+    .prop_map(|kind| DoStatement {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+}
+
+fn case_of(
+    expression: impl Strategy<Value = Expression> + Clone,
+) -> impl Strategy<Value = ExpressionK> {
+    (1..3usize).prop_flat_map(move |count| {
+        let arm = (
+            prop::collection::vec(greater_pattern(), count),
+            prop::option::weighted(0.3, expression.clone()),
+            expression.clone(),
+        )
+            .prop_map(|(patterns, condition, expression)| CaseArm {
+                patterns,
+                condition,
+                expression,
+            });
+        (
+            prop::collection::vec(expression.clone(), count),
+            prop::collection::vec(arm, 1..3),
+        )
+            .prop_map(|(expressions, arms)| ExpressionK::CaseOf(expressions, arms))
+    })
+}
+
+fn expression() -> impl Strategy<Value = Expression> {
+    let leaf = prop_oneof![
+        variable().prop_map(ExpressionK::Variable),
+        name(CONSTRUCTORS).prop_map(ExpressionK::Constructor),
+        (0..1000u16).prop_map(|value| ExpressionK::Integer(value.to_string().into())),
+        (0..100u8, 0..100u8)
+            .prop_map(|(whole, fraction)| ExpressionK::Float(format!("{whole}.{fraction}").into())),
+    ];
+    leaf.prop_map(|kind| Expression {
+        begin: 0,
+        end: 0,
+        kind,
+    })
+    .prop_recursive(4, 48, 4, |inner| {
+        prop_oneof![
+            4 => (inner.clone(), prop::collection::vec(inner.clone(), 1..4)).prop_map(
+                |(function, arguments)| ExpressionK::Application(Box::new(function), arguments)
+            ),
+            4 => (inner.clone(), value_operator(), inner.clone()).prop_map(
+                |(left, operator, right)| {
+                    ExpressionK::BinaryOperator(Box::new(left), operator, Box::new(right))
+                }
+            ),
+            1 => inner
+                .clone()
+                .prop_map(|expression| ExpressionK::Parenthesized(Box::new(expression))),
+            1 => (inner.clone(), inner.clone(), inner.clone()).prop_map(
+                |(condition, then, otherwise)| {
+                    ExpressionK::IfThenElse(Box::new(condition), Box::new(then), Box::new(otherwise))
+                }
+            ),
+            1 => (
+                prop::collection::vec(let_declaration(inner.clone()), 1..3),
+                inner.clone()
+            )
+                .prop_map(|(declarations, expression)| {
+                    ExpressionK::Let(declarations, Box::new(expression))
+                }),
+            1 => prop::collection::vec(do_statement(inner.clone()), 1..4)
+                .prop_map(ExpressionK::DoBlock),
+            1 => case_of(inner),
+        ]
+        .prop_map(|kind| Expression {
+            begin: 0,
+            end: 0,
+            kind,
+        })
+    })
+}
+
+fn top_level_declaration() -> impl Strategy<Value = Declaration> {
+    let foreign = prop_oneof![
+        (name(VARIABLES), ty()).prop_map(|(name, ty)| DeclarationK::ForeignValue(name, ty)),
+        (name(PROPER_NAMES), ty()).prop_map(|(name, ty)| DeclarationK::ForeignData(name, ty)),
+    ];
+    prop_oneof![
+        4 => let_declaration(expression()),
+        1 => foreign.prop_map(declaration),
+    ]
+}
+
+fn module() -> impl Strategy<Value = Module> {
+    (
+        prop::collection::vec(import(), 0..3),
+        fixities(),
+        prop::collection::vec(top_level_declaration(), 1..4),
+    )
+        .prop_map(|(imports, fixities, declarations)| Module {
+            imports,
+            fixities,
+            declarations,
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1024))]
+
+    #[test]
+    fn print_then_parse(mut module in module(), width in prop_oneof![Just(20), Just(80)]) {
+        parenthesize(&mut module);
+        let config = Config {
+            width,
+            ..Config::default()
+        };
+        let printed = format_module("", &module, &config);
+
+        let (mut parsed, errors) = parse_top_level(&printed);
+        prop_assert_eq!(errors, vec![], "{}", printed);
+        prop_assert_eq!(validate(&parsed, &printed), vec![], "{}", printed);
+        Erase.visit_module_mut(&mut parsed);
+        prop_assert_eq!(parsed, module, "{}", printed);
+    }
+}
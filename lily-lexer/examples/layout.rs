@@ -0,0 +1,60 @@
+//! Shows the layout tokens that are inserted into a source file.
+//!
+//! ```text
+//! cargo run -p lily-lexer --example layout -- [--trace] [--tabs <width>] Main.lily
+//! ```
+//!
+//! Begin, separator and end tokens are printed inline as `{`, `;` and `}`,
+//! followed by their depth. With `--trace`, the delimiter stack and the
+//! changes made to it are reported for every line instead. With `--tabs`,
+//! tabs are expanded to the given width.
+
+use anyhow::{bail, Context};
+use lily_lexer::{
+    indentation::Tabs,
+    trace::trace_layout,
+    types::{LayoutK, TokenK},
+    Lexer,
+};
+
+fn main() -> anyhow::Result<()> {
+    let mut trace = false;
+    let mut tabs = Tabs::default();
+    let mut path = None;
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--trace" => trace = true,
+            "--tabs" => {
+                let width = arguments.next().context("expected a tab width")?;
+                tabs = Tabs::Expand(width.parse().context("parsing the tab width")?);
+            }
+            _ if path.is_none() => path = Some(argument),
+            _ => bail!("unexpected argument `{}`", argument),
+        }
+    }
+    let Some(path) = path else {
+        bail!("usage: layout [--trace] [--tabs <width>] <file>");
+    };
+    let source = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path))?;
+
+    if trace {
+        print!("{}", trace_layout(&source, tabs));
+        return Ok(());
+    }
+
+    for token in Lexer::with_tabs(&source, tabs) {
+        match token.kind {
+            TokenK::Layout(LayoutK::Begin) => print!("{{{}", token.depth),
+            TokenK::Layout(LayoutK::End) => print!("}}{}", token.depth),
+            TokenK::Layout(LayoutK::Separator) => print!(";{}", token.depth),
+            _ => print!(
+                "{}{}",
+                &source[token.comment_begin..token.comment_end],
+                &source[token.begin..token.end]
+            ),
+        }
+    }
+    println!();
+    Ok(())
+}
@@ -2,7 +2,10 @@ use std::str::Chars;
 
 use unicode_categories::UnicodeCategories;
 
-use super::types::{DelimiterK, DigitK, IdentifierK, OperatorK, Token, TokenK, UnknownK};
+use super::types::{
+    is_identifier_part, DelimiterK, DigitK, IdentifierK, OperatorK, QualifiedK, Token, TokenK,
+    TriviaK, UnknownK,
+};
 
 #[derive(Debug, Clone)]
 pub struct Cursor<'a> {
@@ -22,6 +25,16 @@ impl<'a> Cursor<'a> {
         }
     }
 
+    /// Creates a cursor that starts at `offset`, reporting offsets relative
+    /// to the whole source.
+    pub fn new_at(source: &'a str, offset: usize) -> Self {
+        Self {
+            length: source.len(),
+            source,
+            chars: source[offset..].chars(),
+        }
+    }
+
     fn is_eof(&self) -> bool {
         self.chars.as_str().is_empty()
     }
@@ -51,7 +64,152 @@ impl<'a> Cursor<'a> {
     }
 }
 
+fn is_operator_part(c: char) -> bool {
+    !"(){}[]".contains(c) && (c.is_symbol() || c.is_punctuation())
+}
+
+fn identifier_kind(identifier: &str) -> IdentifierK {
+    match identifier {
+        "ado" => IdentifierK::Ado,
+        "as" => IdentifierK::As,
+        "case" => IdentifierK::Case,
+        "data" => IdentifierK::Data,
+        "do" => IdentifierK::Do,
+        "else" => IdentifierK::Else,
+        "foreign" => IdentifierK::Foreign,
+        "if" => IdentifierK::If,
+        "import" => IdentifierK::Import,
+        "in" => IdentifierK::In,
+        "infix" => IdentifierK::Infix,
+        "infixl" => IdentifierK::Infixl,
+        "infixr" => IdentifierK::Infixr,
+        "let" => IdentifierK::Let,
+        "of" => IdentifierK::Of,
+        "then" => IdentifierK::Then,
+        "type" => IdentifierK::Type,
+        _ => IdentifierK::Lower,
+    }
+}
+
 impl<'a> Cursor<'a> {
+    /// Continues an upper identifier into a qualified name. Segments are only
+    /// joined when the `.` is directly adjacent on both sides, such that
+    /// `Maybe.Just` is qualified while `Just . f` remains a composition.
+    fn take_qualified(&mut self) -> TokenK {
+        let mut kind = TokenK::Identifier(IdentifierK::Upper);
+        while self.peek_1() == '.' {
+            let mut lookahead = self.chars.clone();
+            lookahead.next();
+            let segment_begin = self.length - lookahead.as_str().len();
+            match lookahead.next().unwrap_or(EOF_CHAR) {
+                initial if initial.is_letter_uppercase() => {
+                    self.take();
+                    self.take_while(is_identifier_part);
+                    kind = TokenK::Qualified(QualifiedK::Upper);
+                }
+                initial if initial.is_letter_lowercase() => {
+                    let segment_length = lookahead
+                        .as_str()
+                        .find(|c: char| !is_identifier_part(c))
+                        .unwrap_or(lookahead.as_str().len());
+                    let segment_end = segment_begin + initial.len_utf8() + segment_length;
+                    if identifier_kind(&self.source[segment_begin..segment_end])
+                        != IdentifierK::Lower
+                    {
+                        break;
+                    }
+                    self.take();
+                    self.take_while(is_identifier_part);
+                    return TokenK::Qualified(QualifiedK::Lower);
+                }
+                initial if is_operator_part(initial) && !",_\\".contains(initial) => {
+                    self.take();
+                    self.take_while(is_operator_part);
+                    return TokenK::Qualified(QualifiedK::Operator);
+                }
+                _ => break,
+            }
+        }
+        kind
+    }
+
+    /// Consumes a block comment, including any nested block comments.
+    ///
+    /// Returns the offset of the innermost unmatched `{-` if the comment
+    /// is unterminated.
+    pub(crate) fn take_block_comment(&mut self) -> Result<(), usize> {
+        let mut openers = vec![];
+        loop {
+            match (self.peek_1(), self.peek_2()) {
+                ('{', '-') => {
+                    openers.push(self.consumed());
+                    self.take();
+                    self.take();
+                }
+                ('-', '}') => {
+                    self.take();
+                    self.take();
+                    openers.pop();
+                    if openers.is_empty() {
+                        break Ok(());
+                    }
+                }
+                _ if self.is_eof() => break Err(*openers.last().unwrap()),
+                _ => {
+                    self.take();
+                }
+            }
+        }
+    }
+
+    /// Takes a single piece of trivia, such as a newline, a run of other
+    /// whitespace, or a comment.
+    pub fn take_trivia(&mut self) -> Option<Token> {
+        let begin = self.consumed();
+        let kind = match (self.peek_1(), self.peek_2()) {
+            ('\n', _) => {
+                self.take();
+                TriviaK::Newline
+            }
+            ('\r', '\n') => {
+                self.take();
+                self.take();
+                TriviaK::Newline
+            }
+            ('-', '-') => {
+                self.take_while(|c| c != '\n');
+                let comment = self.source[begin + 2..self.consumed()].trim_start();
+                if comment.starts_with('|') {
+                    TriviaK::DocComment
+                } else {
+                    TriviaK::LineComment
+                }
+            }
+            ('{', '-') => {
+                self.take_block_comment().ok()?;
+                if self.source[begin..].starts_with("{-|") {
+                    TriviaK::DocComment
+                } else {
+                    TriviaK::BlockComment
+                }
+            }
+            (c, _) if c.is_whitespace() && !self.is_eof() => {
+                self.take();
+                self.take_while(|c| c.is_whitespace() && c != '\n' && c != '\r');
+                TriviaK::Whitespace
+            }
+            _ => return None,
+        };
+        Some(Token {
+            comment_begin: begin,
+            comment_end: begin,
+            begin,
+            end: self.consumed(),
+            kind: TokenK::Trivia(kind),
+            depth: 0,
+        })
+    }
+
     pub fn take_token(&mut self) -> Token {
         let comment_begin = self.consumed();
         loop {
@@ -59,17 +217,21 @@ impl<'a> Cursor<'a> {
                 ('-', '-') => {
                     self.take_while(|c| c != '\n');
                 }
-                ('{', '-') => loop {
-                    if self.is_eof() {
-                        break;
-                    } else if self.peek_1() == '-' && self.peek_2() == '}' {
-                        self.take();
-                        self.take();
-                        break;
-                    } else {
-                        self.take();
+                ('{', '-') => {
+                    // An unterminated comment becomes a single token from
+                    // its outermost opener, so that no source is dropped.
+                    let opener = self.consumed();
+                    if self.take_block_comment().is_err() {
+                        return Token {
+                            comment_begin,
+                            comment_end: opener,
+                            begin: opener,
+                            end: self.consumed(),
+                            kind: TokenK::Unknown(UnknownK::UnfinishedComment),
+                            depth: 0,
+                        };
                     }
-                },
+                }
                 (i, _) if i.is_whitespace() => {
                     self.take_while(|c| c.is_whitespace());
                 }
@@ -92,47 +254,20 @@ impl<'a> Cursor<'a> {
             '\\' => TokenK::Operator(OperatorK::Backslash),
             // Identifiers
             initial if initial.is_letter_lowercase() || initial == '_' && self.peek_1() == '_' => {
-                self.take_while(|c| c.is_letter() || c.is_number() || "'_".contains(c));
+                self.take_while(is_identifier_part);
                 let end = self.consumed();
-                TokenK::Identifier(match &self.source[begin..end] {
-                    "ado" => IdentifierK::Ado,
-                    "as" => IdentifierK::As,
-                    "case" => IdentifierK::Case,
-                    "do" => IdentifierK::Do,
-                    "else" => IdentifierK::Else,
-                    "if" => IdentifierK::If,
-                    "in" => IdentifierK::In,
-                    "infixl" => IdentifierK::Infixl,
-                    "infixr" => IdentifierK::Infixr,
-                    "let" => IdentifierK::Let,
-                    "of" => IdentifierK::Of,
-                    "then" => IdentifierK::Then,
-                    "type" => IdentifierK::Type,
-                    _ => IdentifierK::Lower,
-                })
+                TokenK::Identifier(identifier_kind(&self.source[begin..end]))
             }
             initial if initial.is_letter_uppercase() => {
-                self.take_while(|c| c.is_letter() || c.is_number() || "'_".contains(c));
-                TokenK::Identifier(IdentifierK::Upper)
+                self.take_while(is_identifier_part);
+                self.take_qualified()
             }
             // Compound Symbols
             '_' => TokenK::Operator(OperatorK::Underscore),
-            initial if initial.is_symbol() || initial.is_punctuation() => {
-                self.take_while(|c| !"(){}[]".contains(c) && (c.is_symbol() || c.is_punctuation()));
+            initial if is_operator_part(initial) => {
+                self.take_while(is_operator_part);
                 let end = self.consumed();
-                TokenK::Operator(match &self.source[begin..end] {
-                    "->" => OperatorK::ArrowRight,
-                    "<-" => OperatorK::ArrowLeft,
-                    "=" => OperatorK::Equal,
-                    ":" => OperatorK::Colon,
-                    "." => OperatorK::Period,
-                    "|" => OperatorK::Pipe,
-                    "?" => OperatorK::Question,
-                    "!" => OperatorK::Bang,
-                    "<" => OperatorK::LessThan,
-                    ">" => OperatorK::GreaterThan,
-                    _ => OperatorK::Source,
-                })
+                TokenK::Operator(OperatorK::from_source(&self.source[begin..end]))
             }
             // Digits
             initial if initial.is_ascii_digit() => {
@@ -177,9 +312,21 @@ impl<'a> Cursor<'a> {
 mod tests {
     use crate::types::UnknownK;
 
-    use super::{Cursor, DigitK, IdentifierK, OperatorK, Token, TokenK};
+    use super::{Cursor, DigitK, IdentifierK, OperatorK, QualifiedK, Token, TokenK, TriviaK};
     use pretty_assertions::assert_eq;
 
+    fn kinds(source: &str) -> Vec<TokenK> {
+        let mut cursor = Cursor::new(source);
+        let mut kinds = vec![];
+        loop {
+            let token = cursor.take_token();
+            if token.is_eof() {
+                break kinds;
+            }
+            kinds.push(token.kind);
+        }
+    }
+
     #[test]
     fn double_period_after_int() {
         let source = "1..2";
@@ -296,4 +443,146 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn qualified_identifiers() {
+        assert_eq!(
+            kinds("Data.Map.insert Data.Map.Map Data.Map.<> A..",),
+            vec![
+                TokenK::Qualified(QualifiedK::Lower),
+                TokenK::Qualified(QualifiedK::Upper),
+                TokenK::Qualified(QualifiedK::Operator),
+                TokenK::Qualified(QualifiedK::Operator),
+            ]
+        );
+    }
+
+    #[test]
+    fn qualified_split() {
+        let source = "Data.Map.insert Data.Map.Map A.B.. Map";
+        let mut cursor = Cursor::new(source);
+        let mut split = vec![];
+        loop {
+            let token = cursor.take_token();
+            if token.is_eof() {
+                break;
+            }
+            split.push(token.split_qualified(source));
+        }
+        assert_eq!(
+            split,
+            vec![
+                Some(("Data.Map", "insert")),
+                Some(("Data.Map", "Map")),
+                Some(("A.B", ".")),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn qualified_adjacency() {
+        assert_eq!(
+            kinds("Just . f"),
+            vec![
+                TokenK::Identifier(IdentifierK::Upper),
+                TokenK::Operator(OperatorK::Period),
+                TokenK::Identifier(IdentifierK::Lower),
+            ]
+        );
+        assert_eq!(
+            kinds("Just .f"),
+            vec![
+                TokenK::Identifier(IdentifierK::Upper),
+                TokenK::Operator(OperatorK::Period),
+                TokenK::Identifier(IdentifierK::Lower),
+            ]
+        );
+        assert_eq!(
+            kinds("record.field"),
+            vec![
+                TokenK::Identifier(IdentifierK::Lower),
+                TokenK::Operator(OperatorK::Period),
+                TokenK::Identifier(IdentifierK::Lower),
+            ]
+        );
+        assert_eq!(
+            kinds("Effect.do"),
+            vec![
+                TokenK::Identifier(IdentifierK::Upper),
+                TokenK::Operator(OperatorK::Period),
+                TokenK::Identifier(IdentifierK::Do),
+            ]
+        );
+    }
+
+    #[test]
+    fn unfinished_block_comment() {
+        let source = "1 {- hello";
+        let mut cursor = Cursor::new(source);
+        cursor.take_token();
+        assert_eq!(
+            cursor.take_token(),
+            Token {
+                comment_begin: 1,
+                comment_end: 2,
+                begin: 2,
+                end: 10,
+                kind: TokenK::Unknown(UnknownK::UnfinishedComment),
+                depth: 0,
+            }
+        );
+        assert!(cursor.take_token().is_eof());
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let source = "{- outer {- inner -} still comment -} 1";
+        let mut cursor = Cursor::new(source);
+        let token = cursor.take_token();
+        assert_eq!(token.kind, TokenK::Digit(DigitK::Int));
+        assert_eq!((token.comment_begin, token.comment_end), (0, 38));
+    }
+
+    #[test]
+    fn unfinished_nested_block_comment() {
+        let source = "{- outer {- inner -} {- unmatched";
+        let mut cursor = Cursor::new(source);
+        let token = cursor.take_token();
+        assert_eq!(token.kind, TokenK::Unknown(UnknownK::UnfinishedComment));
+        assert_eq!((token.begin, token.end), (0, 33));
+
+        let source = "{- outer {- inner -} still open";
+        let mut cursor = Cursor::new(source);
+        let token = cursor.take_token();
+        assert_eq!((token.begin, token.end), (0, 31));
+    }
+
+    #[test]
+    fn trivia() {
+        let source = "  -- | doc\r\n{- a {- b -} -}\t-- line\n{-| doc -}\r x";
+        let mut cursor = Cursor::new(source);
+        let mut trivia = vec![];
+        while let Some(token) = cursor.take_trivia() {
+            trivia.push((token.kind, &source[token.begin..token.end]));
+        }
+        assert_eq!(
+            trivia,
+            vec![
+                (TokenK::Trivia(TriviaK::Whitespace), "  "),
+                (TokenK::Trivia(TriviaK::DocComment), "-- | doc\r"),
+                (TokenK::Trivia(TriviaK::Newline), "\n"),
+                (TokenK::Trivia(TriviaK::BlockComment), "{- a {- b -} -}"),
+                (TokenK::Trivia(TriviaK::Whitespace), "\t"),
+                (TokenK::Trivia(TriviaK::LineComment), "-- line"),
+                (TokenK::Trivia(TriviaK::Newline), "\n"),
+                (TokenK::Trivia(TriviaK::DocComment), "{-| doc -}"),
+                (TokenK::Trivia(TriviaK::Whitespace), "\r "),
+            ]
+        );
+        assert_eq!(
+            cursor.take_token().kind,
+            TokenK::Identifier(IdentifierK::Lower)
+        );
+    }
 }
@@ -0,0 +1,318 @@
+use std::fmt::Write;
+
+use super::types::Position;
+
+/// Maps byte offsets to line and column positions.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// Returns the 1-based line and column of an offset, with columns
+    /// counted in characters.
+    pub fn position(&self, source: &str, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = source[line_start..offset].chars().count() + 1;
+        Position { line, column }
+    }
+
+    /// Returns the byte range of a 1-based line, excluding its line ending.
+    pub fn line_range(&self, source: &str, line: usize) -> (usize, usize) {
+        let begin = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(source.len(), |&next| next - 1);
+        let end = if source[begin..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        (begin, end)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn colour(&self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;36m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub begin: usize,
+    pub end: usize,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A report that points at one or more ranges of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_primary(mut self, begin: usize, end: usize, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            begin,
+            end,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, begin: usize, end: usize, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            begin,
+            end,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    fn primary_label(&self) -> Option<&Label> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .or_else(|| self.labels.first())
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const GUTTER: &str = "\x1b[1;34m";
+
+/// Renders diagnostics for a single source file, either as human-readable
+/// reports with source snippets or as JSON objects for CI annotations.
+pub struct Renderer<'a> {
+    file_name: &'a str,
+    source: &'a str,
+    index: LineIndex,
+    colour: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file_name: &'a str, source: &'a str) -> Self {
+        Self {
+            file_name,
+            source,
+            index: LineIndex::new(source),
+            colour: false,
+        }
+    }
+
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    fn paint(&self, colour: &str, text: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", colour, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut buffer = String::new();
+        let severity = diagnostic.severity;
+
+        let _ = writeln!(
+            buffer,
+            "{}: {}",
+            self.paint(severity.colour(), severity.as_str()),
+            diagnostic.message
+        );
+
+        let mut lines: Vec<usize> = diagnostic
+            .labels
+            .iter()
+            .map(|label| self.index.position(self.source, label.begin).line)
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let gutter_width = lines.last().map_or(1, |line| line.to_string().len());
+        let gutter = |text: &str| self.paint(GUTTER, &format!("{:>gutter_width$} |", text));
+
+        if let Some(label) = diagnostic.primary_label() {
+            let Position { line, column } = self.index.position(self.source, label.begin);
+            let _ = writeln!(
+                buffer,
+                "{:gutter_width$}{} {}:{}:{}",
+                "",
+                self.paint(GUTTER, "-->"),
+                self.file_name,
+                line,
+                column
+            );
+            let _ = writeln!(buffer, "{}", gutter(""));
+        }
+
+        for line in lines {
+            let (line_begin, line_end) = self.index.line_range(self.source, line);
+            let text = &self.source[line_begin..line_end];
+            let _ = writeln!(buffer, "{} {}", gutter(&line.to_string()), text);
+
+            for label in &diagnostic.labels {
+                if self.index.position(self.source, label.begin).line != line {
+                    continue;
+                }
+                let padding = self.source[line_begin..label.begin].chars().count();
+                let width = self.source[label.begin..label.end.min(line_end).max(label.begin)]
+                    .chars()
+                    .count()
+                    .max(1);
+                let (marker, colour) = if label.primary {
+                    ('^', severity.colour())
+                } else {
+                    ('-', GUTTER)
+                };
+                let underline = marker.to_string().repeat(width);
+                let underline = if label.message.is_empty() {
+                    underline
+                } else {
+                    format!("{} {}", underline, label.message)
+                };
+                let _ = writeln!(
+                    buffer,
+                    "{} {:padding$}{}",
+                    gutter(""),
+                    "",
+                    self.paint(colour, &underline)
+                );
+            }
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(
+                buffer,
+                "{:gutter_width$} {} note: {}",
+                "",
+                self.paint(GUTTER, "="),
+                note
+            );
+        }
+
+        buffer
+    }
+
+    pub fn to_json(&self, diagnostic: &Diagnostic) -> String {
+        let mut buffer = String::new();
+        let _ = write!(
+            buffer,
+            "{{\"file\":{},\"severity\":\"{}\",\"message\":{},\"labels\":[",
+            json_string(self.file_name),
+            diagnostic.severity.as_str(),
+            json_string(&diagnostic.message)
+        );
+        for (index, label) in diagnostic.labels.iter().enumerate() {
+            if index > 0 {
+                buffer.push(',');
+            }
+            let begin = self.index.position(self.source, label.begin);
+            let end = self.index.position(self.source, label.end);
+            let _ = write!(
+                buffer,
+                "{{\"begin\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{},\"primary\":{},\"message\":{}}}",
+                label.begin,
+                label.end,
+                begin.line,
+                begin.column,
+                end.line,
+                end.column,
+                label.primary,
+                json_string(&label.message)
+            );
+        }
+        buffer.push_str("],\"notes\":[");
+        for (index, note) in diagnostic.notes.iter().enumerate() {
+            if index > 0 {
+                buffer.push(',');
+            }
+            buffer.push_str(&json_string(note));
+        }
+        buffer.push_str("]}");
+        buffer
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut buffer = String::with_capacity(value.len() + 2);
+    buffer.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => buffer.push_str("\\\""),
+            '\\' => buffer.push_str("\\\\"),
+            '\n' => buffer.push_str("\\n"),
+            '\r' => buffer.push_str("\\r"),
+            '\t' => buffer.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(buffer, "\\u{:04x}", c as u32);
+            }
+            c => buffer.push(c),
+        }
+    }
+    buffer.push('"');
+    buffer
+}
@@ -0,0 +1,96 @@
+use thiserror::Error;
+
+use crate::{
+    cursor::Cursor,
+    diagnostics::Diagnostic,
+    types::{Token, TokenK, UnknownK},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LexErrorK {
+    #[error("Unterminated `{{-` opened at line {line}.")]
+    UnfinishedComment { line: usize },
+    #[error("Expected digits after the `.` in a float literal.")]
+    UnfinishedFloat,
+    #[error("Stray character {0:?}.")]
+    StrayCharacter(char),
+    #[error("Tab characters are not allowed in whitespace.")]
+    Tab,
+    /// Points at the indentation of the first statement of the block.
+    #[error("Indentation differs from the first statement of the same block.")]
+    InconsistentIndentation {
+        first_begin: usize,
+        first_end: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[error("{kind}")]
+pub struct LexError {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: LexErrorK,
+}
+
+impl LexError {
+    /// Describes an `Unknown` token, returning `None` for any other token.
+    pub fn from_token(source: &str, token: &Token) -> Option<Self> {
+        let mut begin = token.begin;
+        let kind = match token.kind {
+            TokenK::Unknown(UnknownK::UnfinishedComment) => {
+                // The token spans the whole comment, while the error points
+                // at the opener that was left unmatched.
+                begin = Cursor::new_at(source, token.begin)
+                    .take_block_comment()
+                    .err()?;
+                LexErrorK::UnfinishedComment {
+                    line: source[..begin].matches('\n').count() + 1,
+                }
+            }
+            TokenK::Unknown(UnknownK::UnfinishedFloat) => LexErrorK::UnfinishedFloat,
+            TokenK::Unknown(UnknownK::UnknownToken) => {
+                LexErrorK::StrayCharacter(source[token.begin..].chars().next()?)
+            }
+            _ => return None,
+        };
+        Some(LexError {
+            begin,
+            end: token.end,
+            kind,
+        })
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let LexError { begin, end, .. } = *self;
+        match self.kind {
+            LexErrorK::UnfinishedComment { line } => {
+                Diagnostic::error("unterminated block comment")
+                    .with_primary(begin, begin + 2, format!("comment opened at line {}", line))
+                    .with_note("block comments nest, so each `{-` needs its own `-}`")
+            }
+            LexErrorK::UnfinishedFloat => Diagnostic::error("unfinished float literal")
+                .with_primary(begin, end, "expected digits after the `.`"),
+            LexErrorK::StrayCharacter(character) => Diagnostic::error(format!(
+                "stray character {:?}",
+                character
+            ))
+            .with_primary(begin, end, "not valid in Lily source"),
+            LexErrorK::Tab => Diagnostic::error("tab character in whitespace")
+                .with_primary(begin, end, "layout cannot tell how wide this is")
+                .with_note("indent with spaces, or configure a tab width to expand tabs"),
+            LexErrorK::InconsistentIndentation {
+                first_begin,
+                first_end,
+            } => Diagnostic::error("inconsistent indentation")
+                .with_primary(begin, end, "indented differently from the block")
+                .with_secondary(
+                    first_begin,
+                    first_end,
+                    "the first statement is indented here",
+                )
+                .with_note("statements of a block should use the same tabs and spaces"),
+        }
+    }
+}
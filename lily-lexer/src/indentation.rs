@@ -0,0 +1,132 @@
+//! Checks the whitespace that layout depends on.
+//!
+//! Layout compares the columns of tokens, so a tab has to count as some
+//! number of columns. By default tabs are rejected, since the width that an
+//! editor shows them with is not known; they can also be expanded to a fixed
+//! tab width instead. Either way, the statements of a block should be
+//! indented with the same whitespace, or they would line up differently with
+//! another tab width.
+
+use crate::{
+    errors::{LexError, LexErrorK},
+    leading_trivia,
+    types::{LayoutK, Token, TokenK, TriviaK},
+};
+
+/// How tabs count towards the columns used by layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tabs {
+    /// Tabs are reported as errors, and count as a single column.
+    #[default]
+    Reject,
+    /// Tabs advance to the next multiple of the given width, which is at
+    /// least one.
+    Expand(usize),
+}
+
+impl Tabs {
+    /// The number of columns between tab stops.
+    pub fn width(self) -> usize {
+        match self {
+            Tabs::Reject => 1,
+            Tabs::Expand(width) => width.max(1),
+        }
+    }
+
+    /// The column after `text`, starting from the first column.
+    pub fn column(self, text: &str) -> usize {
+        let width = self.width();
+        let columns = text.chars().fold(0, |column, character| match character {
+            '\t' => (column / width + 1) * width,
+            _ => column + 1,
+        });
+        columns + 1
+    }
+}
+
+/// Reports tabs, unless they are expanded, and statements that are indented
+/// with different whitespace than the first statement of their block.
+///
+/// `tokens` must come from [`crate::lex`], or be a run of its top-level
+/// groups, such that their layout tokens are balanced.
+pub fn indentation_errors(source: &str, tokens: &[Token], tabs: Tabs) -> Vec<LexError> {
+    let mut errors = vec![];
+    if tabs == Tabs::Reject {
+        for token in tokens {
+            errors.extend(tab_errors(source, token));
+        }
+    }
+
+    // The indentation of the first statement of each open block that starts
+    // a line.
+    let mut blocks: Vec<Option<(usize, usize)>> = vec![None];
+    let mut statement_start = true;
+    for token in tokens {
+        match token.kind {
+            TokenK::Layout(LayoutK::Begin) => {
+                blocks.push(None);
+                statement_start = true;
+                continue;
+            }
+            TokenK::Layout(LayoutK::End) => {
+                blocks.pop();
+                statement_start = false;
+                continue;
+            }
+            TokenK::Layout(LayoutK::Separator) => {
+                statement_start = true;
+                continue;
+            }
+            _ => (),
+        }
+        if !std::mem::take(&mut statement_start) || token.is_eof() {
+            continue;
+        }
+
+        let line_start = source[..token.begin]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let indentation = &source[line_start..token.begin];
+        if !indentation.chars().all(char::is_whitespace) {
+            continue;
+        }
+        match blocks.last_mut() {
+            Some(Some((first_begin, first_end)))
+                if source[*first_begin..*first_end] != *indentation =>
+            {
+                errors.push(LexError {
+                    begin: line_start,
+                    end: token.begin,
+                    kind: LexErrorK::InconsistentIndentation {
+                        first_begin: *first_begin,
+                        first_end: *first_end,
+                    },
+                });
+            }
+            Some(first @ None) => *first = Some((line_start, token.begin)),
+            _ => (),
+        }
+    }
+
+    errors.sort_by_key(|error| error.begin);
+    errors
+}
+
+/// Reports each run of whitespace before `token` that contains tabs.
+fn tab_errors(source: &str, token: &Token) -> Vec<LexError> {
+    let mut errors = vec![];
+    for trivia in leading_trivia(source, token) {
+        if trivia.kind != TokenK::Trivia(TriviaK::Whitespace) {
+            continue;
+        }
+        let whitespace = &source[trivia.begin..trivia.end];
+        if let (Some(first), Some(last)) = (whitespace.find('\t'), whitespace.rfind('\t')) {
+            errors.push(LexError {
+                begin: trivia.begin + first,
+                end: trivia.begin + last + 1,
+                kind: LexErrorK::Tab,
+            });
+        }
+    }
+    errors
+}
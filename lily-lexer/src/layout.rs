@@ -1,7 +1,12 @@
-use super::types::{IdentifierK, LayoutK, OperatorK, Position, Token, TokenK};
+use super::{
+    trace::StackChange,
+    types::{IdentifierK, LayoutK, OperatorK, Position, Token, TokenK},
+};
 
+/// The constructs that layout keeps track of, along with masks that stop
+/// operators like `->` and `|` from closing the blocks around them.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum DelimiterK {
+pub enum DelimiterK {
     KwAdo,
     KwCase,
     KwDo,
@@ -29,13 +34,61 @@ impl DelimiterK {
 pub struct LayoutEngine {
     delimiters: Vec<(Position, DelimiterK)>,
     pub depth: usize,
+    /// Whether the last token pushed began a block or separated it, so that
+    /// the next token starts a statement.
+    statement_start: bool,
+    changes: Option<Vec<StackChange>>,
 }
 
 impl LayoutEngine {
     pub fn new(initial_position: Position) -> Self {
         let delimiters = vec![(initial_position, DelimiterK::MaskRoot)];
         let depth = 0;
-        Self { delimiters, depth }
+        Self {
+            delimiters,
+            depth,
+            statement_start: false,
+            changes: None,
+        }
+    }
+
+    /// Records every change to the delimiter stack, to be collected with
+    /// [`LayoutEngine::take_changes`] after each token.
+    pub fn traced(mut self) -> Self {
+        self.changes = Some(vec![]);
+        self
+    }
+
+    pub fn take_changes(&mut self) -> Vec<StackChange> {
+        self.changes
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn delimiters(&self) -> &[(Position, DelimiterK)] {
+        &self.delimiters
+    }
+
+    fn push_delimiter(&mut self, position: Position, delimiter: DelimiterK) {
+        if let Some(changes) = &mut self.changes {
+            changes.push(StackChange::Push(position, delimiter));
+        }
+        self.delimiters.push((position, delimiter));
+    }
+
+    fn pop_delimiter(&mut self) -> Option<(Position, DelimiterK)> {
+        let popped = self.delimiters.pop();
+        if let (Some(changes), Some((position, delimiter))) = (&mut self.changes, popped) {
+            changes.push(StackChange::Pop(position, delimiter));
+        }
+        popped
+    }
+
+    fn truncate_delimiters(&mut self, length: usize) {
+        while self.delimiters.len() > length {
+            self.pop_delimiter();
+        }
     }
 
     #[inline]
@@ -100,7 +153,7 @@ impl LayoutEngine {
             }
         }
 
-        self.delimiters.push((next_position, delimiter));
+        self.push_delimiter(next_position, delimiter);
         self.depth += 1;
         tokens.push(Token {
             comment_begin: current_token.end,
@@ -133,7 +186,7 @@ impl LayoutEngine {
                     depth: self.depth,
                 });
                 if let DelimiterK::KwOf = delimiter {
-                    self.delimiters.push((now_position, DelimiterK::MaskPat));
+                    self.push_delimiter(now_position, DelimiterK::MaskPat);
                 }
             }
         }
@@ -144,7 +197,7 @@ impl LayoutEngine {
         let (take_n, make_n) = self.determine_end(|position, delimiter| {
             delimiter.is_indented() && now_position.column < position.column
         });
-        self.delimiters.truncate(take_n);
+        self.truncate_delimiters(take_n);
         for _ in 0..make_n {
             self.push_end(tokens, current_token);
         }
@@ -170,7 +223,7 @@ impl LayoutEngine {
                     match &self.delimiters[..take_n] {
                         $($pattern $(if $guard)? => {
                             if $commit {
-                                self.delimiters.truncate(take_n);
+                                self.truncate_delimiters(take_n);
                                 for _ in 0..make_n {
                                     self.push_end(tokens, current_token);
                                 }
@@ -190,16 +243,16 @@ impl LayoutEngine {
             Identifier(Case) => {
                 self.add_end(tokens, current_token, now_position);
                 self.add_separator(tokens, current_token, now_position);
-                self.delimiters.push((now_position, KwCase));
+                self.push_delimiter(now_position, KwCase);
                 tokens.push(current_token.with_depth(self.depth));
             }
             Identifier(Of) => with_end!(
                 |_, delimiter| delimiter.is_indented(),
                 true ~ [.., (_, KwCase)] => {
-                    self.delimiters.pop();
+                    self.pop_delimiter();
                     tokens.push(current_token.with_depth(self.depth));
                     self.add_begin(tokens, current_token, next_position, KwOf);
-                    self.delimiters.push((next_position, MaskPat));
+                    self.push_delimiter(next_position, MaskPat);
                 },
                 true ~ _ => {
                     self.add_end(tokens, current_token, now_position);
@@ -210,7 +263,7 @@ impl LayoutEngine {
             Operator(Backslash) => {
                 self.add_end(tokens, current_token, now_position);
                 self.add_separator(tokens, current_token, now_position);
-                self.delimiters.push((now_position, MaskLam));
+                self.push_delimiter(now_position, MaskLam);
                 tokens.push(current_token.with_depth(self.depth));
             }
             Operator(ArrowRight) => with_end!(
@@ -225,10 +278,10 @@ impl LayoutEngine {
                 },
                 true ~ _ => {
                     if let Some((_, KwIf)) = self.delimiters.last() {
-                        self.delimiters.pop();
+                        self.pop_delimiter();
                     }
                     if let Some((_, MaskLam | MaskPat)) = self.delimiters.last() {
-                        self.delimiters.pop();
+                        self.pop_delimiter();
                     }
                     tokens.push(current_token.with_depth(self.depth));
                 },
@@ -248,14 +301,14 @@ impl LayoutEngine {
             Identifier(If) => {
                 self.add_end(tokens, current_token, now_position);
                 self.add_separator(tokens, current_token, now_position);
-                self.delimiters.push((now_position, KwIf));
+                self.push_delimiter(now_position, KwIf);
                 tokens.push(current_token.with_depth(self.depth));
             }
             Identifier(Then) => with_end!(
                 |_, delimiter| delimiter.is_indented(),
                 true ~ [.., (_, KwIf)] => {
-                    self.delimiters.pop();
-                    self.delimiters.push((now_position, KwThen));
+                    self.pop_delimiter();
+                    self.push_delimiter(now_position, KwThen);
                     tokens.push(current_token.with_depth(self.depth));
                 },
                 false ~ _ => {
@@ -267,7 +320,7 @@ impl LayoutEngine {
             Identifier(Else) => with_end!(
                 |_, delimiter| delimiter.is_indented(),
                 true ~ [.., (_, KwThen)] => {
-                    self.delimiters.pop();
+                    self.pop_delimiter();
                     tokens.push(current_token.with_depth(self.depth));
                 },
                 false ~ _ => {
@@ -279,13 +332,19 @@ impl LayoutEngine {
             Identifier(Let) => {
                 self.add_end(tokens, current_token, now_position);
                 self.add_separator(tokens, current_token, now_position);
+                // Only a `let` that starts a statement declares bindings for
+                // the rest of the block; one within a statement is an
+                // expression, even when no other delimiter encloses it.
+                let starts_statement = tokens
+                    .last()
+                    .map_or(self.statement_start, Self::is_statement_start);
                 tokens.push(current_token.with_depth(self.depth));
                 self.add_begin(
                     tokens,
                     current_token,
                     next_position,
                     match self.delimiters.last() {
-                        Some((_, KwAdo | KwDo)) => KwLetStmt,
+                        Some((_, KwAdo | KwDo)) if starts_statement => KwLetStmt,
                         _ => KwLetExpr,
                     },
                 );
@@ -298,7 +357,7 @@ impl LayoutEngine {
                     }
                 },
                 true ~ [.., (_, KwAdo | KwLetExpr)] => {
-                    self.delimiters.pop();
+                    self.pop_delimiter();
                     self.push_end(tokens, current_token);
                     tokens.push(current_token.with_depth(self.depth));
                 },
@@ -314,10 +373,20 @@ impl LayoutEngine {
                 tokens.push(current_token.with_depth(self.depth));
             }
         }
+
+        self.statement_start = tokens.last().is_some_and(Self::is_statement_start);
+    }
+
+    #[inline]
+    fn is_statement_start(token: &Token) -> bool {
+        matches!(
+            token.kind,
+            TokenK::Layout(LayoutK::Begin | LayoutK::Separator)
+        )
     }
 
     pub fn finalize_layout(&mut self, tokens: &mut Vec<Token>, eof_offset: usize) {
-        while let Some((_, delimiter)) = self.delimiters.pop() {
+        while let Some((_, delimiter)) = self.pop_delimiter() {
             if let DelimiterK::MaskRoot = delimiter {
                 tokens.push(Token {
                     comment_begin: eof_offset,
@@ -1,78 +1,294 @@
-use self::{cursor::Cursor, layout::LayoutEngine, types::Token};
+use self::{
+    cursor::Cursor,
+    errors::LexError,
+    indentation::{indentation_errors, Tabs},
+    layout::LayoutEngine,
+    types::{LayoutK, Position, Token, TokenK, UnknownK},
+};
 
 mod cursor;
+pub mod diagnostics;
+pub mod errors;
+pub mod indentation;
 mod layout;
+pub mod symbol;
+pub mod trace;
 pub mod types;
 
-pub fn lex(source: &str) -> Vec<Token> {
-    let tokens = {
+/// Lexes a source file, also reporting the tokens that could not be lexed
+/// and any whitespace that layout cannot rely on, given how `tabs` count.
+pub fn lex_with_diagnostics(source: &str, tabs: Tabs) -> (Vec<Token>, Vec<LexError>) {
+    let tokens: Vec<_> = Lexer::with_tabs(source, tabs).collect();
+    let mut errors: Vec<_> = tokens
+        .iter()
+        .filter_map(|token| LexError::from_token(source, token))
+        .chain(indentation_errors(source, &tokens, tabs))
+        .collect();
+    errors.sort_by_key(|error| error.begin);
+    (tokens, errors)
+}
+
+/// Lexes a source file like [`lex`], but also yields the whitespace and
+/// comments between tokens as separate `Trivia` tokens.
+///
+/// Trivia tokens are interleaved in source order, and the comment range of
+/// every other token is left empty. Layout tokens keep their position, such
+/// that the token stream remains sorted by offset, and are placed according
+/// to how `tabs` count.
+pub fn lex_with_trivia(source: &str, tabs: Tabs) -> Vec<Token> {
+    let mut output_tokens = vec![];
+    let mut pending_layout: Vec<Token> = vec![];
+    for token in Lexer::with_tabs(source, tabs) {
+        if let TokenK::Layout(_) = token.kind {
+            pending_layout.push(token);
+            continue;
+        }
+
+        let (before, after): (Vec<_>, Vec<_>) = pending_layout
+            .drain(..)
+            .partition(|layout| layout.begin <= token.comment_begin);
+        output_tokens.extend(before);
+
+        output_tokens
+            .extend(leading_trivia(source, &token).map(|trivia| trivia.with_depth(token.depth)));
+
+        output_tokens.extend(after);
+        output_tokens.push(Token {
+            comment_begin: token.begin,
+            comment_end: token.begin,
+            ..token
+        });
+    }
+    output_tokens
+}
+
+/// Splits the comment range of a token into `Trivia` tokens, in source order.
+pub fn leading_trivia<'a>(source: &'a str, token: &Token) -> impl Iterator<Item = Token> + 'a {
+    trivia(source, token.comment_begin, token.comment_end)
+}
+
+/// Lexes the `Trivia` tokens from `begin` that end by `end`, in source order.
+pub fn trivia(source: &str, begin: usize, end: usize) -> impl Iterator<Item = Token> + '_ {
+    let mut cursor = Cursor::new_at(source, begin);
+    std::iter::from_fn(move || cursor.take_trivia().filter(|trivia| trivia.end <= end))
+}
+
+/// Computes the positions of offsets that are visited in increasing order.
+struct Positions<'a> {
+    source: &'a str,
+    tabs: Tabs,
+    offset: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl<'a> Positions<'a> {
+    fn with_tabs(source: &'a str, tabs: Tabs) -> Self {
+        Self {
+            source,
+            tabs,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    fn at(&mut self, offset: usize) -> Position {
+        let skipped = &self.source[self.offset..offset];
+        if let Some(last_newline) = skipped.rfind('\n') {
+            self.line += skipped.matches('\n').count();
+            self.line_start = self.offset + last_newline + 1;
+        }
+        self.offset = offset;
+        let column = self.tabs.column(&self.source[self.line_start..offset]);
+        Position {
+            line: self.line,
+            column,
+        }
+    }
+}
+
+/// Lexes a source file lazily, inserting layout tokens along the way.
+///
+/// A token is laid out once the token after it has been lexed, so only one
+/// token of lookahead is kept, along with the layout tokens it produced.
+/// Collecting the iterator gives the same tokens as [`lex`].
+pub struct Lexer<'a> {
+    source: &'a str,
+    cursor: Cursor<'a>,
+    positions: Positions<'a>,
+    layout_engine: Option<LayoutEngine>,
+    lookahead: Option<Token>,
+    buffer: Vec<Token>,
+    index: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self::with_tabs(source, Tabs::default())
+    }
+
+    /// Lexes with tabs counting towards layout columns as given by `tabs`.
+    pub fn with_tabs(source: &'a str, tabs: Tabs) -> Self {
         let mut cursor = Cursor::new(source);
-        let mut tokens = vec![];
+        let lookahead = cursor.take_token();
+        let mut positions = Positions::with_tabs(source, tabs);
+
+        // A source file without any tokens gets no layout tokens either.
+        let layout_engine = if lookahead.is_eof() {
+            None
+        } else {
+            Some(LayoutEngine::new(positions.at(lookahead.begin)))
+        };
+
+        Self {
+            source,
+            cursor,
+            positions,
+            layout_engine,
+            lookahead: Some(lookahead),
+            buffer: vec![],
+            index: 0,
+        }
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
         loop {
-            let token = cursor.take_token();
-            tokens.push(token);
-            if token.is_eof() {
-                break tokens;
+            if let Some(&token) = self.buffer.get(self.index) {
+                self.index += 1;
+                return Some(token);
             }
-        }
-    };
+            self.buffer.clear();
+            self.index = 0;
 
-    if let [token] = &tokens[..] {
-        if token.is_eof() {
-            return tokens;
+            let token = self.lookahead.take()?;
+            let Some(layout_engine) = &mut self.layout_engine else {
+                return Some(token);
+            };
+
+            if token.is_eof() {
+                layout_engine.finalize_layout(&mut self.buffer, self.source.len());
+                self.buffer.push(token.with_depth(layout_engine.depth));
+            } else {
+                let next = self.cursor.take_token();
+                let now_position = self.positions.at(token.begin);
+                let next_position = self.positions.at(next.begin);
+                layout_engine.add_layout(&mut self.buffer, token, now_position, next_position);
+                self.lookahead = Some(next);
+            }
         }
     }
+}
 
-    let tokens = {
-        let input_tokens = tokens;
+pub fn lex(source: &str) -> Vec<Token> {
+    Lexer::new(source).collect()
+}
 
-        let get_position = |offset| {
-            let mut line = 1;
-            let mut column = 1;
+/// Re-lexes a run of complete top-level groups, for incremental parsing.
+///
+/// `begin` must be where the trivia before the first token of a top-level
+/// group starts, and `end` the offset of the first token of a later group,
+/// or `None` to lex through to the end of the file. The result matches the
+/// corresponding slice of `lex(source)`: it starts with the first token of
+/// the group at `begin`, and ends with the layout tokens that close the group
+/// before `end`, or with the end of file token.
+///
+/// Returns `None` when the tokens at either boundary no longer start a
+/// top-level group, for example because the region leaves a comment or a
+/// block open, in which case the whole file has to be lexed again. `tabs`
+/// must be the same as for the rest of the tokens.
+pub fn lex_groups(
+    source: &str,
+    begin: usize,
+    end: Option<usize>,
+    tabs: Tabs,
+) -> Option<Vec<Token>> {
+    let root = Cursor::new(source).take_token();
 
-            for (current, character) in source.chars().enumerate() {
-                if current == offset {
-                    break;
-                }
-                if character == '\n' {
-                    column = 1;
-                    line += 1
-                } else {
-                    column += 1;
+    let mut cursor = Cursor::new_at(source, begin);
+    let mut input_tokens = vec![];
+    let boundary = loop {
+        let token = cursor.take_token();
+        if let Some(end) = end {
+            if token.begin >= end {
+                // An unfinished comment runs to the end of the file, past
+                // any group that used to follow it.
+                let unfinished = token.kind == TokenK::Unknown(UnknownK::UnfinishedComment);
+                if token.begin != end || unfinished {
+                    return None;
                 }
+                break Some(token);
             }
+            if token.end > end || token.is_eof() {
+                return None;
+            }
+        }
+        input_tokens.push(token);
+        if token.is_eof() {
+            break None;
+        }
+    };
 
-            types::Position { line, column }
-        };
-
-        let initial_position = if let Some(token) = input_tokens.first() {
-            get_position(token.begin)
-        } else {
-            return input_tokens;
-        };
+    if root.is_eof() {
+        return Some(input_tokens);
+    }
 
-        let mut output_tokens = vec![];
-        let mut layout_engine = LayoutEngine::new(initial_position);
+    let mut positions = Positions::with_tabs(source, tabs);
+    let mut layout_engine = LayoutEngine::new(positions.at(root.begin));
+    let mut output_tokens = vec![];
 
-        for (index, &token) in input_tokens.iter().enumerate() {
-            let next_begin = match input_tokens.get(index + 1) {
-                Some(next) => next.begin,
+    let following = boundary.map(|_| cursor.take_token());
+    let mut tokens = input_tokens.iter().chain(boundary.iter()).peekable();
+    while let Some(&token) = tokens.next() {
+        let next_begin = match tokens.peek() {
+            Some(next) => next.begin,
+            None => match following {
+                Some(following) => following.begin,
                 None => {
                     layout_engine.finalize_layout(&mut output_tokens, source.len());
                     output_tokens.push(token.with_depth(layout_engine.depth));
                     break;
                 }
-            };
-            layout_engine.add_layout(
-                &mut output_tokens,
-                token,
-                get_position(token.begin),
-                get_position(next_begin),
-            )
-        }
+            },
+        };
+        let now_position = positions.at(token.begin);
+        let next_position = positions.at(next_begin);
+        layout_engine.add_layout(&mut output_tokens, token, now_position, next_position);
+    }
 
-        output_tokens
+    let separator = |token: Option<&Token>| {
+        matches!(
+            token,
+            Some(Token {
+                kind: TokenK::Layout(LayoutK::Separator),
+                depth: 0,
+                ..
+            })
+        )
     };
 
-    tokens
+    // The group at `begin` is closed off from the previous one by a separator,
+    // which is already part of the tokens before the region.
+    if begin > root.begin {
+        if !separator(output_tokens.first()) {
+            return None;
+        }
+        output_tokens.remove(0);
+    }
+
+    if let Some(boundary) = boundary {
+        let index = output_tokens
+            .iter()
+            .rposition(|token| token.begin == boundary.begin && token.kind == boundary.kind)?;
+        output_tokens.truncate(index);
+        if !separator(output_tokens.last()) {
+            return None;
+        }
+    }
+
+    Some(output_tokens)
 }
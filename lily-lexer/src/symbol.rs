@@ -0,0 +1,241 @@
+//! Interned strings for identifiers and operators.
+//!
+//! A [`Symbol`] is an index into a global interner, so comparing and hashing
+//! symbols never touches their text. Interned strings live for the rest of
+//! the program. Keywords and reserved operators are interned ahead of time,
+//! and are available as constants in [`kw`] and [`op`]. Words such as
+//! `hiding` and `qualified` are only special in imports, so they are
+//! ordinary identifiers here.
+//!
+//! Each thread remembers the symbols it has interned, so only text that is
+//! new to a thread takes the interner's lock. Reading the text of a symbol
+//! never does, so parsing and printing from many threads does not contend
+//! on the interner.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    sync::{OnceLock, RwLock},
+};
+
+use rustc_hash::FxHashMap;
+
+/// Symbols are ordered by when they were first interned, not by their text.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+macro_rules! preinterned {
+    ($($module:ident { $($name:ident => $text:literal,)* })*) => {
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[repr(u32)]
+        enum Preinterned {
+            $($($name,)*)*
+        }
+
+        const PREINTERNED: &[&str] = &[$($($text,)*)*];
+
+        $(
+            pub mod $module {
+                use super::{Preinterned, Symbol};
+
+                $(pub const $name: Symbol = Symbol(Preinterned::$name as u32);)*
+            }
+        )*
+    };
+}
+
+preinterned! {
+    kw {
+        ADO => "ado",
+        AS => "as",
+        CASE => "case",
+        DATA => "data",
+        DO => "do",
+        ELSE => "else",
+        FOREIGN => "foreign",
+        IF => "if",
+        IMPORT => "import",
+        IN => "in",
+        INFIX => "infix",
+        INFIXL => "infixl",
+        INFIXR => "infixr",
+        LET => "let",
+        OF => "of",
+        THEN => "then",
+        TYPE => "type",
+    }
+    op {
+        ARROW_LEFT => "<-",
+        ARROW_RIGHT => "->",
+        BACKSLASH => "\\",
+        BANG => "!",
+        COLON => ":",
+        COMMA => ",",
+        EQUAL => "=",
+        GREATER_THAN => ">",
+        LESS_THAN => "<",
+        PERIOD => ".",
+        PIPE => "|",
+        QUESTION => "?",
+        UNDERSCORE => "_",
+    }
+}
+
+/// The size of the first chunk of `Strings`; each later chunk is twice as
+/// large as the one before it.
+const FIRST_CHUNK: u64 = 64;
+
+const CHUNKS: usize = 26;
+
+/// The number of symbols that fit in every chunk, just short of `u32::MAX`.
+const CAPACITY: u64 = FIRST_CHUNK * ((1 << CHUNKS) - 1);
+
+/// The text of each symbol, by index.
+///
+/// Chunks are allocated when they are first needed and never move, so a
+/// string can be read while another one is appended. Only the interner
+/// appends, while it holds the write lock.
+struct Strings {
+    chunks: [OnceLock<Box<[OnceLock<&'static str>]>>; CHUNKS],
+}
+
+impl Strings {
+    const fn new() -> Self {
+        Self {
+            chunks: [const { OnceLock::new() }; CHUNKS],
+        }
+    }
+
+    /// The chunk that holds `index`, and the position within it.
+    fn locate(index: u32) -> (usize, usize) {
+        let shifted = u64::from(index) + FIRST_CHUNK;
+        let chunk = (shifted.ilog2() - FIRST_CHUNK.ilog2()) as usize;
+        (chunk, (shifted - (FIRST_CHUNK << chunk)) as usize)
+    }
+
+    fn get(&self, index: u32) -> Option<&'static str> {
+        let (chunk, position) = Strings::locate(index);
+        self.chunks[chunk].get()?[position].get().copied()
+    }
+
+    fn set(&self, index: u32, text: &'static str) {
+        let (chunk, position) = Strings::locate(index);
+        let slots = self.chunks[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| OnceLock::new()).collect());
+        slots[position]
+            .set(text)
+            .expect("symbols are only stored once");
+    }
+}
+
+static STRINGS: Strings = Strings::new();
+
+struct Interner {
+    symbols: FxHashMap<&'static str, Symbol>,
+}
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Interner {
+            symbols: FxHashMap::default(),
+        };
+        for text in PREINTERNED {
+            interner.insert(text);
+        }
+        interner
+    }
+
+    fn insert(&mut self, text: &'static str) -> Symbol {
+        let index = u32::try_from(self.symbols.len())
+            .ok()
+            .filter(|&index| u64::from(index) < CAPACITY)
+            .expect("too many symbols");
+        let symbol = Symbol(index);
+        STRINGS.set(index, text);
+        self.symbols.insert(text, symbol);
+        symbol
+    }
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| RwLock::new(Interner::new()))
+}
+
+thread_local! {
+    /// Symbols this thread has already interned.
+    static INTERNED: RefCell<FxHashMap<&'static str, Symbol>> = RefCell::default();
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Symbol {
+        if let Some(symbol) = INTERNED.with_borrow(|interned| interned.get(text).copied()) {
+            return symbol;
+        }
+        let symbol = Symbol::intern_shared(text);
+        INTERNED.with_borrow_mut(|interned| interned.insert(symbol.as_str(), symbol));
+        symbol
+    }
+
+    fn intern_shared(text: &str) -> Symbol {
+        if let Some(symbol) = Symbol::lookup(text) {
+            return symbol;
+        }
+        let mut interner = interner().write().unwrap();
+        // Another thread may have interned it while the lock was released.
+        if let Some(&symbol) = interner.symbols.get(text) {
+            return symbol;
+        }
+        interner.insert(Box::leak(text.into()))
+    }
+
+    /// Finds the symbol for `text` without interning it, for lookups in
+    /// tables that can only contain interned keys.
+    pub fn lookup(text: &str) -> Option<Symbol> {
+        interner().read().unwrap().symbols.get(text).copied()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        // Pre-interned symbols are only stored once the interner exists.
+        interner();
+        STRINGS
+            .get(self.0)
+            .expect("symbols are stored before they are handed out")
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Symbol::intern(text)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Indices are only meaningful within a process, so symbols are serialized
+// as their text and interned again when they are read back.
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <String as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&text))
+    }
+}
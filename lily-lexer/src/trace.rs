@@ -0,0 +1,189 @@
+//! Traces the decisions of the layout engine.
+//!
+//! The layout rules insert a `Separator` or an `End` based on the stack of
+//! delimiters that are open when a token is reached. [`trace_layout`] lexes a
+//! source file while recording that stack for every token, along with the
+//! delimiters that the token pushed or popped and the layout tokens that it
+//! produced. Displaying a [`LayoutTrace`] gives a report that is grouped by
+//! line:
+//!
+//! ```text
+//!    2 | example = do
+//!      |   stack MaskRoot@1:1
+//!      |   2:1 `example` => ;0 example
+//!      |   2:11 `do` => do {1, push KwDo@3:3
+//! ```
+
+use std::fmt;
+
+use crate::{
+    cursor::Cursor,
+    indentation::Tabs,
+    layout::LayoutEngine,
+    types::{LayoutK, Position, Token, TokenK},
+    Positions,
+};
+
+pub use crate::layout::DelimiterK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackChange {
+    Push(Position, DelimiterK),
+    Pop(Position, DelimiterK),
+}
+
+/// How layout was applied to a single token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutStep {
+    /// The token being laid out, which is the end of file token for the
+    /// step that closes the remaining blocks.
+    pub token: Token,
+    pub position: Position,
+    /// The delimiters that were open when the token was reached.
+    pub stack: Vec<(Position, DelimiterK)>,
+    /// Changes to the stack, in the order that they were made.
+    pub changes: Vec<StackChange>,
+    /// The token along with the layout tokens inserted around it, which is
+    /// what `lex` yields for it.
+    pub emitted: Vec<Token>,
+}
+
+impl LayoutStep {
+    fn is_eventful(&self) -> bool {
+        !self.changes.is_empty() || self.emitted.len() > 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutTrace<'a> {
+    source: &'a str,
+    pub steps: Vec<LayoutStep>,
+}
+
+/// Lexes a source file like [`crate::Lexer::with_tabs`], recording every
+/// step of layout.
+pub fn trace_layout(source: &str, tabs: Tabs) -> LayoutTrace<'_> {
+    let mut steps = vec![];
+    let mut cursor = Cursor::new(source);
+    let mut positions = Positions::with_tabs(source, tabs);
+    let mut token = cursor.take_token();
+
+    // A source file without any tokens gets no layout tokens either.
+    if token.is_eof() {
+        let position = positions.at(token.begin);
+        steps.push(LayoutStep {
+            token,
+            position,
+            stack: vec![],
+            changes: vec![],
+            emitted: vec![token],
+        });
+        return LayoutTrace { source, steps };
+    }
+
+    let mut layout_engine = LayoutEngine::new(positions.at(token.begin)).traced();
+    loop {
+        let position = positions.at(token.begin);
+        let stack = layout_engine.delimiters().to_vec();
+        let mut emitted = vec![];
+        let next = if token.is_eof() {
+            layout_engine.finalize_layout(&mut emitted, source.len());
+            emitted.push(token.with_depth(layout_engine.depth));
+            None
+        } else {
+            let next = cursor.take_token();
+            let next_position = positions.at(next.begin);
+            layout_engine.add_layout(&mut emitted, token, position, next_position);
+            Some(next)
+        };
+        steps.push(LayoutStep {
+            token,
+            position,
+            stack,
+            changes: layout_engine.take_changes(),
+            emitted,
+        });
+        match next {
+            Some(next) => token = next,
+            None => break,
+        }
+    }
+
+    LayoutTrace { source, steps }
+}
+
+impl LayoutTrace<'_> {
+    /// The tokens that `lex` yields for the same source file.
+    pub fn tokens(&self) -> Vec<Token> {
+        self.steps
+            .iter()
+            .flat_map(|step| step.emitted.iter().copied())
+            .collect()
+    }
+
+    fn text(&self, token: &Token) -> String {
+        match token.kind {
+            TokenK::Layout(LayoutK::Begin) => format!("{{{}", token.depth),
+            TokenK::Layout(LayoutK::End) => format!("}}{}", token.depth),
+            TokenK::Layout(LayoutK::Separator) => format!(";{}", token.depth),
+            _ if token.is_eof() => "<eof>".into(),
+            _ => self.source[token.begin..token.end].into(),
+        }
+    }
+}
+
+struct Delimiter((Position, DelimiterK));
+
+impl fmt::Display for Delimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (position, delimiter) = self.0;
+        write!(f, "{:?}@{}:{}", delimiter, position.line, position.column)
+    }
+}
+
+impl fmt::Display for LayoutTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<_> = self.source.lines().collect();
+        let gutter = lines.len().max(1).to_string().len();
+        let mut line = 0;
+        for step in &self.steps {
+            if step.position.line != line {
+                line = step.position.line;
+                let text = lines.get(line - 1).copied().unwrap_or_default();
+                let header = format!("{:>gutter$} | {}", line, text);
+                writeln!(f, "{}", header.trim_end())?;
+                write!(f, "{:gutter$} |   stack", "")?;
+                for delimiter in &step.stack {
+                    write!(f, " {}", Delimiter(*delimiter))?;
+                }
+                writeln!(f)?;
+            }
+            if !step.is_eventful() {
+                continue;
+            }
+
+            let position = step.position;
+            let token = self.text(&step.token);
+            write!(
+                f,
+                "{:gutter$} |   {}:{} `{}` =>",
+                "", position.line, position.column, token
+            )?;
+            for emitted in &step.emitted {
+                write!(f, " {}", self.text(emitted))?;
+            }
+            for change in &step.changes {
+                match change {
+                    StackChange::Push(position, delimiter) => {
+                        write!(f, ", push {}", Delimiter((*position, *delimiter)))?
+                    }
+                    StackChange::Pop(position, delimiter) => {
+                        write!(f, ", pop {}", Delimiter((*position, *delimiter)))?
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
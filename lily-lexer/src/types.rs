@@ -1,18 +1,33 @@
+use std::fmt;
+
+use unicode_categories::UnicodeCategories;
+
+use crate::symbol::{kw, op, Symbol};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum CommentK {
-    Block,
-    Line,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriviaK {
+    BlockComment,
+    DocComment,
+    LineComment,
+    Newline,
+    Whitespace,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IdentifierK {
     Ado,
     As,
     Case,
+    Data,
     Do,
     Else,
+    Foreign,
     If,
+    Import,
     In,
+    Infix,
     Infixl,
     Infixr,
     Let,
@@ -23,7 +38,35 @@ pub enum IdentifierK {
     Upper,
 }
 
+impl IdentifierK {
+    /// The pre-interned symbol of a keyword, or `None` for other identifiers.
+    pub fn symbol(self) -> Option<Symbol> {
+        let symbol = match self {
+            IdentifierK::Ado => kw::ADO,
+            IdentifierK::As => kw::AS,
+            IdentifierK::Case => kw::CASE,
+            IdentifierK::Data => kw::DATA,
+            IdentifierK::Do => kw::DO,
+            IdentifierK::Else => kw::ELSE,
+            IdentifierK::Foreign => kw::FOREIGN,
+            IdentifierK::If => kw::IF,
+            IdentifierK::Import => kw::IMPORT,
+            IdentifierK::In => kw::IN,
+            IdentifierK::Infix => kw::INFIX,
+            IdentifierK::Infixl => kw::INFIXL,
+            IdentifierK::Infixr => kw::INFIXR,
+            IdentifierK::Let => kw::LET,
+            IdentifierK::Of => kw::OF,
+            IdentifierK::Then => kw::THEN,
+            IdentifierK::Type => kw::TYPE,
+            IdentifierK::Lower | IdentifierK::Upper => return None,
+        };
+        Some(symbol)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DelimiterK {
     Round,
     Square,
@@ -31,6 +74,7 @@ pub enum DelimiterK {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperatorK {
     ArrowLeft,
     ArrowRight,
@@ -48,13 +92,65 @@ pub enum OperatorK {
     Underscore,
 }
 
+impl OperatorK {
+    /// Classifies a compound symbol, with anything that is not reserved by
+    /// the language being a `Source` operator.
+    pub fn from_source(operator: &str) -> Self {
+        match operator {
+            "->" => OperatorK::ArrowRight,
+            "<-" => OperatorK::ArrowLeft,
+            "=" => OperatorK::Equal,
+            ":" => OperatorK::Colon,
+            "." => OperatorK::Period,
+            "|" => OperatorK::Pipe,
+            "?" => OperatorK::Question,
+            "!" => OperatorK::Bang,
+            "<" => OperatorK::LessThan,
+            ">" => OperatorK::GreaterThan,
+            _ => OperatorK::Source,
+        }
+    }
+
+    /// The pre-interned symbol of a reserved operator, or `None` for
+    /// `Source` operators.
+    pub fn symbol(self) -> Option<Symbol> {
+        let symbol = match self {
+            OperatorK::ArrowLeft => op::ARROW_LEFT,
+            OperatorK::ArrowRight => op::ARROW_RIGHT,
+            OperatorK::Backslash => op::BACKSLASH,
+            OperatorK::Bang => op::BANG,
+            OperatorK::Colon => op::COLON,
+            OperatorK::Comma => op::COMMA,
+            OperatorK::Equal => op::EQUAL,
+            OperatorK::GreaterThan => op::GREATER_THAN,
+            OperatorK::LessThan => op::LESS_THAN,
+            OperatorK::Period => op::PERIOD,
+            OperatorK::Pipe => op::PIPE,
+            OperatorK::Question => op::QUESTION,
+            OperatorK::Underscore => op::UNDERSCORE,
+            OperatorK::Source => return None,
+        };
+        Some(symbol)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DigitK {
     Float,
     Int,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QualifiedK {
+    Lower,
+    Operator,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnknownK {
     UnfinishedComment,
     UnfinishedFloat,
@@ -63,6 +159,7 @@ pub enum UnknownK {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LayoutK {
     Begin,
     End,
@@ -70,6 +167,7 @@ pub enum LayoutK {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenK {
     CloseDelimiter(DelimiterK),
     Digit(DigitK),
@@ -77,10 +175,62 @@ pub enum TokenK {
     Layout(LayoutK),
     OpenDelimiter(DelimiterK),
     Operator(OperatorK),
+    Qualified(QualifiedK),
+    Trivia(TriviaK),
     Unknown(UnknownK),
 }
 
+impl fmt::Display for TokenK {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            TokenK::CloseDelimiter(DelimiterK::Round) => "`)`",
+            TokenK::CloseDelimiter(DelimiterK::Square) => "`]`",
+            TokenK::CloseDelimiter(DelimiterK::Brace) => "`}`",
+            TokenK::Digit(DigitK::Float) => "float",
+            TokenK::Digit(DigitK::Int) => "integer",
+            TokenK::Identifier(IdentifierK::Lower) => "identifier",
+            TokenK::Identifier(IdentifierK::Upper) => "proper name",
+            TokenK::Identifier(keyword) => {
+                return write!(f, "keyword `{}`", format!("{:?}", keyword).to_lowercase());
+            }
+            TokenK::Layout(LayoutK::Begin) => "start of block",
+            TokenK::Layout(LayoutK::End) => "end of block",
+            TokenK::Layout(LayoutK::Separator) => "end of line",
+            TokenK::OpenDelimiter(DelimiterK::Round) => "`(`",
+            TokenK::OpenDelimiter(DelimiterK::Square) => "`[`",
+            TokenK::OpenDelimiter(DelimiterK::Brace) => "`{`",
+            TokenK::Operator(OperatorK::ArrowLeft) => "`<-`",
+            TokenK::Operator(OperatorK::ArrowRight) => "`->`",
+            TokenK::Operator(OperatorK::Backslash) => "`\\`",
+            TokenK::Operator(OperatorK::Bang) => "`!`",
+            TokenK::Operator(OperatorK::Colon) => "`:`",
+            TokenK::Operator(OperatorK::Comma) => "`,`",
+            TokenK::Operator(OperatorK::Equal) => "`=`",
+            TokenK::Operator(OperatorK::GreaterThan) => "`>`",
+            TokenK::Operator(OperatorK::LessThan) => "`<`",
+            TokenK::Operator(OperatorK::Period) => "`.`",
+            TokenK::Operator(OperatorK::Pipe) => "`|`",
+            TokenK::Operator(OperatorK::Question) => "`?`",
+            TokenK::Operator(OperatorK::Source) => "operator",
+            TokenK::Operator(OperatorK::Underscore) => "`_`",
+            TokenK::Qualified(QualifiedK::Lower) => "qualified identifier",
+            TokenK::Qualified(QualifiedK::Operator) => "qualified operator",
+            TokenK::Qualified(QualifiedK::Upper) => "qualified proper name",
+            TokenK::Trivia(TriviaK::BlockComment | TriviaK::LineComment) => "comment",
+            TokenK::Trivia(TriviaK::DocComment) => "doc comment",
+            TokenK::Trivia(TriviaK::Newline) => "newline",
+            TokenK::Trivia(TriviaK::Whitespace) => "whitespace",
+            TokenK::Unknown(UnknownK::UnfinishedComment) => "unterminated comment",
+            TokenK::Unknown(UnknownK::UnfinishedFloat) => "unfinished float",
+            TokenK::Unknown(UnknownK::UnknownToken) => "unknown token",
+            TokenK::Unknown(UnknownK::EndOfFile) => "end of file",
+        };
+        f.write_str(description)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub comment_begin: usize,
     pub comment_end: usize,
@@ -95,6 +245,10 @@ impl Token {
         matches!(self.kind, TokenK::Unknown(UnknownK::EndOfFile))
     }
 
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenK::Trivia(_))
+    }
+
     pub fn is_separator_zero(&self) -> bool {
         self.depth == 0 && matches!(self.kind, TokenK::Layout(LayoutK::Separator))
     }
@@ -102,10 +256,14 @@ impl Token {
     pub fn is_infix_identifier(&self) -> bool {
         matches!(
             self.kind,
-            TokenK::Identifier(IdentifierK::Infixl | IdentifierK::Infixr)
+            TokenK::Identifier(IdentifierK::Infix | IdentifierK::Infixl | IdentifierK::Infixr)
         )
     }
 
+    pub fn is_import_identifier(&self) -> bool {
+        matches!(self.kind, TokenK::Identifier(IdentifierK::Import))
+    }
+
     pub fn is_greater_pattern_boundary(&self) -> bool {
         matches!(
             self.kind,
@@ -141,13 +299,40 @@ impl Token {
         )
     }
 
+    /// Splits a qualified token into its module prefix and its unqualified
+    /// name, e.g. `Data.Map.insert` into `Data.Map` and `insert`.
+    pub fn split_qualified<'s>(&self, source: &'s str) -> Option<(&'s str, &'s str)> {
+        if !matches!(self.kind, TokenK::Qualified(_)) {
+            return None;
+        }
+        let text = &source[self.begin..self.end];
+        let mut segment_begin = 0;
+        loop {
+            let segment_end = text[segment_begin..]
+                .find(|c: char| !is_identifier_part(c))
+                .map_or(text.len(), |length| segment_begin + length);
+            match text[segment_end..].chars().nth(1) {
+                Some(next) if next.is_letter_uppercase() => segment_begin = segment_end + 1,
+                Some(_) => break Some((&text[..segment_end], &text[segment_end + 1..])),
+                None => {
+                    break Some((&text[..segment_begin - 1], &text[segment_begin..]));
+                }
+            }
+        }
+    }
+
     pub fn with_depth(mut self, depth: usize) -> Self {
         self.depth = depth;
         self
     }
 }
 
+pub(crate) fn is_identifier_part(c: char) -> bool {
+    c.is_letter() || c.is_number() || "'_".contains(c)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -0,0 +1,192 @@
+use lily_lexer::{
+    diagnostics::{Diagnostic, LineIndex, Renderer},
+    errors::{LexError, LexErrorK},
+    indentation::{indentation_errors, Tabs},
+    lex, lex_with_diagnostics,
+    types::Position,
+};
+
+#[test]
+fn line_index_positions() {
+    let source = "ab\ncd\n\nλx";
+    let index = LineIndex::new(source);
+    assert_eq!(index.position(source, 0), Position { line: 1, column: 1 });
+    assert_eq!(index.position(source, 2), Position { line: 1, column: 3 });
+    assert_eq!(index.position(source, 3), Position { line: 2, column: 1 });
+    assert_eq!(index.position(source, 7), Position { line: 4, column: 1 });
+    assert_eq!(index.position(source, 9), Position { line: 4, column: 2 });
+    assert_eq!(index.line_range(source, 2), (3, 5));
+    assert_eq!(index.line_range(source, 3), (6, 6));
+}
+
+#[test]
+fn render_0() {
+    let source = "example : Int\nexample = a $$ b\n";
+    let diagnostic = Diagnostic::error("unknown operator `$$`")
+        .with_primary(28, 30, "not in scope")
+        .with_secondary(0, 7, "while checking this declaration")
+        .with_note("declare a fixity with `infixl 0 apply as $$`");
+    insta::assert_snapshot!(Renderer::new("Main.lily", source).render(&diagnostic));
+}
+
+#[test]
+fn render_1() {
+    let source = "a = 1.\nb = 2 字 3\n\nc = {- unterminated\n";
+    let (_, errors) = lex_with_diagnostics(source, Tabs::default());
+    let renderer = Renderer::new("Main.lily", source);
+    let rendered: Vec<_> = errors
+        .iter()
+        .map(|error| renderer.render(&error.to_diagnostic()))
+        .collect();
+    insta::assert_snapshot!(rendered.join("\n"));
+}
+
+#[test]
+fn render_colour() {
+    let source = "a = ?";
+    let diagnostic = Diagnostic::warning("suspicious").with_primary(4, 5, "here");
+    let rendered = Renderer::new("Main.lily", source)
+        .with_colour(true)
+        .render(&diagnostic);
+    assert!(rendered.starts_with("\x1b[1;33mwarning\x1b[0m: suspicious"));
+    assert!(rendered.contains("\x1b[1;33m^ here\x1b[0m"));
+}
+
+#[test]
+fn render_json() {
+    let source = "a = \"b\"\nc";
+    let diagnostic = Diagnostic::error("unexpected \"quote\"")
+        .with_primary(4, 7, "here")
+        .with_note("a note");
+    insta::assert_snapshot!(Renderer::new("src/Main.lily", source).to_json(&diagnostic));
+}
+
+#[test]
+fn lexical_errors() {
+    let source = "a = 1.\nb = 2 字 3\n\nc = {- unterminated\n";
+    let (_, errors) = lex_with_diagnostics(source, Tabs::default());
+    assert_eq!(
+        errors,
+        vec![
+            LexError {
+                begin: 4,
+                end: 6,
+                kind: LexErrorK::UnfinishedFloat,
+            },
+            LexError {
+                begin: 13,
+                end: 16,
+                kind: LexErrorK::StrayCharacter('字'),
+            },
+            LexError {
+                begin: 24,
+                end: 40,
+                kind: LexErrorK::UnfinishedComment { line: 4 },
+            },
+        ]
+    );
+    assert_eq!(errors[2].to_string(), "Unterminated `{-` opened at line 4.");
+}
+
+#[test]
+fn nested_comment_errors() {
+    let source = "a = {- outer\n  {- inner -}\n  {- unmatched\n-}\n";
+    let (_, errors) = lex_with_diagnostics(source, Tabs::default());
+    assert_eq!(
+        errors,
+        vec![LexError {
+            begin: 4,
+            end: 45,
+            kind: LexErrorK::UnfinishedComment { line: 1 },
+        }]
+    );
+
+    let source = "a = {- outer\n  {- unmatched\n";
+    let (_, errors) = lex_with_diagnostics(source, Tabs::default());
+    assert_eq!(
+        errors,
+        vec![LexError {
+            begin: 15,
+            end: 28,
+            kind: LexErrorK::UnfinishedComment { line: 2 },
+        }]
+    );
+}
+
+#[test]
+fn tab_errors() {
+    // Tabs within comments do not matter to layout.
+    let source = "main = do\n\t\ta\n\t\tb {- \t -}\tc\n";
+    let (_, errors) = lex_with_diagnostics(source, Tabs::default());
+    assert_eq!(
+        errors,
+        vec![
+            LexError {
+                begin: 10,
+                end: 12,
+                kind: LexErrorK::Tab,
+            },
+            LexError {
+                begin: 14,
+                end: 16,
+                kind: LexErrorK::Tab,
+            },
+            LexError {
+                begin: 25,
+                end: 26,
+                kind: LexErrorK::Tab,
+            },
+        ]
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "Tab characters are not allowed in whitespace."
+    );
+}
+
+#[test]
+fn inconsistent_indentation() {
+    let source = "main = do\n    a\n\tb\n    c d\n\t  e\n  \tf\n";
+    let tokens = lily_lexer::Lexer::with_tabs(source, Tabs::Expand(4)).collect::<Vec<_>>();
+    assert_eq!(
+        indentation_errors(source, &tokens, Tabs::Expand(4)),
+        vec![
+            LexError {
+                begin: 16,
+                end: 17,
+                kind: LexErrorK::InconsistentIndentation {
+                    first_begin: 10,
+                    first_end: 14,
+                },
+            },
+            LexError {
+                begin: 32,
+                end: 35,
+                kind: LexErrorK::InconsistentIndentation {
+                    first_begin: 10,
+                    first_end: 14,
+                },
+            },
+        ]
+    );
+
+    // Spaces only, with continuation lines that are indented further.
+    let source = "main = do\n  a\n    b\n  c\n";
+    assert_eq!(
+        indentation_errors(source, &lex(source), Tabs::Reject),
+        vec![]
+    );
+}
+
+#[test]
+fn render_2() {
+    let source = "main = do\n  a\n\tb\n";
+    let renderer = Renderer::new("Main.lily", source);
+    let rendered: Vec<_> = lex_with_diagnostics(source, Tabs::Reject)
+        .1
+        .into_iter()
+        .chain(lex_with_diagnostics(source, Tabs::Expand(2)).1)
+        .map(|error| renderer.render(&error.to_diagnostic()))
+        .collect();
+    insta::assert_snapshot!(rendered.join("\n"));
+}
@@ -1,10 +1,15 @@
 use lily_lexer::{
-    lex,
-    types::{LayoutK, TokenK},
+    indentation::Tabs,
+    lex, lex_groups,
+    types::{LayoutK, Token, TokenK},
+    Lexer,
 };
 
 fn lex_print(source: &str) -> String {
-    let tokens = lex(source);
+    print_tokens(source, lex(source))
+}
+
+fn print_tokens(source: &str, tokens: Vec<Token>) -> String {
     let mut buffer = String::new();
     for token in tokens {
         if let TokenK::Layout(layout) = token.kind {
@@ -168,3 +173,125 @@ adoLet = do
 
     insta::assert_snapshot!(lex_print(source));
 }
+
+#[test]
+fn layout_12() {
+    let source = r"doLetIn : Effect Unit
+doLetIn = do
+  logShow (let x = 1 in x)
+  pure $ let y = 2 in y
+  let
+    z = 3
+  logShow z";
+
+    insta::assert_snapshot!(lex_print(source));
+}
+
+#[test]
+fn layout_tabs() {
+    let source = "main = do\n        a\n\tb\n";
+    let expanded = |width| {
+        print_tokens(
+            source,
+            Lexer::with_tabs(source, Tabs::Expand(width)).collect(),
+        )
+    };
+    assert_eq!(expanded(8), "main = do{1\n        a;1\n\tb;1}1;0\n");
+    assert_eq!(expanded(4), "main = do{1\n        a;1}1\n\tb;0\n");
+    // Without expansion, a tab is a single column.
+    assert_eq!(lex_print(source), expanded(1));
+
+    assert_eq!(Tabs::Expand(4).column("  \t"), 5);
+    assert_eq!(Tabs::Expand(4).column("\t  \t"), 9);
+    assert_eq!(Tabs::Expand(0).column("\t\t"), 3);
+    assert_eq!(Tabs::Reject.column("\t字"), 3);
+}
+
+#[test]
+fn layout_groups() {
+    let source = "first = do
+  a
+  b
+
+-- | Second.
+second = case x of
+  y -> z
+
+(third = 1
+";
+    let tokens = lex(source);
+    let starts: Vec<_> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(index, token)| {
+            !matches!(token.kind, TokenK::Layout(_))
+                && !token.is_eof()
+                && (*index == 0
+                    || tokens[index - 1].kind == TokenK::Layout(LayoutK::Separator)
+                        && tokens[index - 1].depth == 0)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(starts.len(), 3);
+
+    let second = tokens[starts[1]];
+    let third = tokens[starts[2]];
+    assert_eq!(
+        lex_groups(
+            source,
+            second.comment_begin,
+            Some(third.begin),
+            Tabs::default()
+        )
+        .as_deref(),
+        Some(&tokens[starts[1]..starts[2]])
+    );
+    assert_eq!(
+        lex_groups(source, 0, Some(second.begin), Tabs::default()).as_deref(),
+        Some(&tokens[..starts[1]])
+    );
+    assert_eq!(
+        lex_groups(source, third.comment_begin, None, Tabs::default()).as_deref(),
+        Some(&tokens[starts[2]..])
+    );
+
+    // An `if` without a `then` keeps the next line in the same group.
+    let source = source.replace("(third = 1\n", "(third = if a\nfourth = 2\n");
+    let fourth = source.find("fourth").unwrap();
+    let tokens = lex(&source);
+    let index = tokens
+        .iter()
+        .position(|token| token.begin == fourth)
+        .unwrap();
+    assert_ne!(tokens[index - 1].kind, TokenK::Layout(LayoutK::Separator));
+    assert_eq!(
+        lex_groups(&source, third.comment_begin, Some(fourth), Tabs::default()),
+        None
+    );
+
+    // So does a comment that is left open.
+    let source = source.replace("if a", "{- a");
+    assert_eq!(
+        lex_groups(&source, third.comment_begin, Some(fourth), Tabs::default()),
+        None
+    );
+}
+
+#[test]
+fn layout_stream() {
+    let source = r"main = do
+  log message
+  case x of
+    Just y -> y
+    Nothing -> z
+other = 1";
+
+    let tokens = lex(source);
+    let mut lexer = Lexer::new(source);
+    let mut streamed: Vec<_> = lexer.by_ref().take(5).collect();
+    streamed.extend(lexer);
+    assert_eq!(streamed, tokens);
+
+    assert_eq!(Lexer::new("").collect::<Vec<_>>(), lex(""));
+    assert_eq!(Lexer::new("  -- only trivia").count(), 1);
+}
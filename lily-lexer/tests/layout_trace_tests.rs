@@ -0,0 +1,137 @@
+use lily_lexer::{
+    indentation::Tabs,
+    lex,
+    trace::{trace_layout, DelimiterK, StackChange},
+    types::Position,
+    Lexer,
+};
+
+const DO_BLOCK: &str = r"main = do
+  a <- pure 1
+  logShow a
+    # a
+  pure a
+next = 0";
+
+const CASE_OF: &str = r"fromMaybe d m = case m of
+  Just a | positive a -> a
+  _ ->
+    d";
+
+const IF_THEN_ELSE: &str = r"signum n =
+  if n then
+    1
+  else if m then 0 else -1";
+
+const LET_IN: &str = r"area r =
+  let
+    pi = 3
+    square = r * r
+  in
+    pi * square";
+
+const LET_STATEMENT: &str = r"main = do
+  let a = 1
+      b = let c = 2 in c
+  logShow (let d = a in d)
+  logShow b";
+
+const LAMBDA: &str = r"apply = \f x -> case x of
+  a -> f a";
+
+const ADO_IN: &str = r"sum = ado
+  x <- pure 1
+  let y = x
+  in x + y";
+
+/// Every token that `lex` yields is emitted by exactly one step.
+#[test]
+fn trace_matches_lex() {
+    for source in [
+        "",
+        DO_BLOCK,
+        CASE_OF,
+        IF_THEN_ELSE,
+        LET_IN,
+        LET_STATEMENT,
+        LAMBDA,
+        ADO_IN,
+    ] {
+        assert_eq!(trace_layout(source, Tabs::default()).tokens(), lex(source));
+    }
+
+    let source = "main = do\n\ta\n\tb\n";
+    assert_eq!(
+        trace_layout(source, Tabs::Expand(4)).tokens(),
+        Lexer::with_tabs(source, Tabs::Expand(4)).collect::<Vec<_>>()
+    );
+}
+
+/// Pushes and pops leave exactly the stack that the next step starts with.
+#[test]
+fn trace_changes_stack() {
+    let trace = trace_layout(CASE_OF, Tabs::default());
+    for window in trace.steps.windows(2) {
+        let mut stack = window[0].stack.clone();
+        for change in &window[0].changes {
+            match *change {
+                StackChange::Push(position, delimiter) => stack.push((position, delimiter)),
+                StackChange::Pop(position, delimiter) => {
+                    assert_eq!(stack.pop(), Some((position, delimiter)));
+                }
+            }
+        }
+        assert_eq!(stack, window[1].stack);
+    }
+
+    let last = trace.steps.last().unwrap();
+    let root = (Position { line: 1, column: 1 }, DelimiterK::MaskRoot);
+    assert_eq!(last.changes.last(), Some(&StackChange::Pop(root.0, root.1)));
+}
+
+/// Statements are separated at the column of the first one, and a token left
+/// of that column closes the block.
+#[test]
+fn trace_do_block() {
+    insta::assert_snapshot!(trace_layout(DO_BLOCK, Tabs::default()).to_string());
+}
+
+/// `of` opens a block of arms, and masks the patterns of each arm until its
+/// `->`, while a guard opens a block that the next arm closes.
+#[test]
+fn trace_case_of() {
+    insta::assert_snapshot!(trace_layout(CASE_OF, Tabs::default()).to_string());
+}
+
+/// `then` and `else` close the blocks opened since their `if`, but they do
+/// not open blocks of their own.
+#[test]
+fn trace_if_then_else() {
+    insta::assert_snapshot!(trace_layout(IF_THEN_ELSE, Tabs::default()).to_string());
+}
+
+/// `in` closes the block of the nearest `let`, whatever its column.
+#[test]
+fn trace_let_in() {
+    insta::assert_snapshot!(trace_layout(LET_IN, Tabs::default()).to_string());
+}
+
+/// A `let` that starts a statement declares bindings for the rest of the
+/// block, while one within a statement is an expression that `in` closes.
+#[test]
+fn trace_let_statement() {
+    insta::assert_snapshot!(trace_layout(LET_STATEMENT, Tabs::default()).to_string());
+}
+
+/// The arguments of a lambda are masked until its `->`.
+#[test]
+fn trace_lambda() {
+    insta::assert_snapshot!(trace_layout(LAMBDA, Tabs::default()).to_string());
+}
+
+/// The `in` of an `ado` block closes the block, along with any `let`
+/// statement before it.
+#[test]
+fn trace_ado_in() {
+    insta::assert_snapshot!(trace_layout(ADO_IN, Tabs::default()).to_string());
+}
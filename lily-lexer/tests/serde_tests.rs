@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+use lily_lexer::{indentation::Tabs, lex_with_diagnostics, lex_with_trivia, types::Token};
+
+#[test]
+fn tokens_round_trip() {
+    let source = "
+example = do
+  x <- pure 1.5 -- comment
+  y 字 {- unterminated
+";
+    let (tokens, errors) = lex_with_diagnostics(source, Tabs::default());
+    assert!(!errors.is_empty());
+
+    let json = serde_json::to_string(&(&tokens, &errors)).unwrap();
+    assert_eq!(
+        serde_json::from_str::<(_, _)>(&json).ok(),
+        Some((tokens, errors))
+    );
+
+    let trivia = lex_with_trivia(source, Tabs::default());
+    let json = serde_json::to_string(&trivia).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<Token>>(&json).unwrap(), trivia);
+}
+
+#[test]
+fn symbols_as_text() {
+    let symbol = lily_lexer::symbol::Symbol::intern("serialized");
+    let json = serde_json::to_string(&symbol).unwrap();
+    assert_eq!(json, "\"serialized\"");
+    assert_eq!(
+        serde_json::from_str::<lily_lexer::symbol::Symbol>(&json).ok(),
+        Some(symbol)
+    );
+}
@@ -0,0 +1,60 @@
+use lily_lexer::{
+    lex,
+    symbol::{kw, op, Symbol},
+    types::TokenK,
+};
+
+#[test]
+fn interning() {
+    let first = Symbol::intern("interning");
+    assert_eq!(Symbol::intern("interning"), first);
+    assert_ne!(Symbol::intern("interned"), first);
+    assert_eq!(first.as_str(), "interning");
+    assert_eq!(first.to_string(), "interning");
+    assert_eq!(format!("{:?}", first), "\"interning\"");
+
+    assert_eq!(Symbol::lookup("never interned anywhere"), None);
+    assert_eq!(Symbol::lookup("interning"), Some(first));
+}
+
+#[test]
+fn preinterned() {
+    assert_eq!(Symbol::intern("if"), kw::IF);
+    assert_eq!(kw::IMPORT.as_str(), "import");
+    assert_eq!(Symbol::intern("->"), op::ARROW_RIGHT);
+    assert_eq!(op::UNDERSCORE.as_str(), "_");
+
+    let source = "example = case x of\n  _ -> if a then b else c";
+    for token in lex(source) {
+        let symbol = match token.kind {
+            TokenK::Identifier(identifier) => identifier.symbol(),
+            TokenK::Operator(operator) => operator.symbol(),
+            _ => continue,
+        };
+        if let Some(symbol) = symbol {
+            assert_eq!(symbol.as_str(), &source[token.begin..token.end]);
+        }
+    }
+}
+
+#[test]
+fn concurrent_interning() {
+    let names: Vec<String> = (0..512)
+        .map(|index| format!("concurrent{}", index))
+        .collect();
+    let symbols: Vec<Vec<Symbol>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| names.iter().map(|name| Symbol::intern(name)).collect()))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    for other in &symbols[1..] {
+        assert_eq!(other, &symbols[0]);
+    }
+    for (name, symbol) in names.iter().zip(&symbols[0]) {
+        assert_eq!(symbol.as_str(), name);
+    }
+}
@@ -0,0 +1,65 @@
+use lily_lexer::{
+    indentation::Tabs,
+    lex_with_trivia,
+    types::{LayoutK, TokenK, TriviaK},
+    Lexer,
+};
+
+#[test]
+fn trivia_round_trip() {
+    for (source, tabs) in [
+        (
+            "-- | Doc\r\nexample = do\n  {- a {- b -} -} x\n\n  y -- trailing\n{- unterminated",
+            Tabs::Reject,
+        ),
+        ("{- a {- b -} {- c", Tabs::Reject),
+        ("example = 1 {- a {- b -} {- c -}", Tabs::Reject),
+        ("example = do\n\ta -- tab\n  \tb\n", Tabs::Expand(4)),
+    ] {
+        let tokens = lex_with_trivia(source, tabs);
+
+        let mut text = String::new();
+        for token in &tokens {
+            assert_eq!(token.comment_begin, token.comment_end);
+            text.push_str(&source[token.begin..token.end]);
+        }
+        assert_eq!(text, source);
+
+        let offsets: Vec<_> = tokens.iter().map(|token| token.begin).collect();
+        let mut sorted = offsets.clone();
+        sorted.sort_unstable();
+        assert_eq!(offsets, sorted);
+
+        let significant: Vec<_> = tokens
+            .iter()
+            .filter(|token| !token.is_trivia())
+            .map(|token| (token.begin, token.end, token.kind, token.depth))
+            .collect();
+        let expected: Vec<_> = Lexer::with_tabs(source, tabs)
+            .map(|token| (token.begin, token.end, token.kind, token.depth))
+            .collect();
+        assert_eq!(significant, expected);
+    }
+}
+
+#[test]
+fn trivia_kinds() {
+    let source = "a = 1 -- one\n{-| two -}\nb = 2\n";
+    let kinds: Vec<_> = lex_with_trivia(source, Tabs::default())
+        .iter()
+        .map(|token| match token.kind {
+            TokenK::Trivia(TriviaK::BlockComment) => "block".to_string(),
+            TokenK::Trivia(TriviaK::DocComment) => "doc".to_string(),
+            TokenK::Trivia(TriviaK::LineComment) => "line".to_string(),
+            TokenK::Trivia(TriviaK::Newline) => "nl".to_string(),
+            TokenK::Trivia(TriviaK::Whitespace) => "ws".to_string(),
+            TokenK::Layout(LayoutK::Separator) => ";".to_string(),
+            TokenK::Layout(_) => "layout".to_string(),
+            _ => source[token.begin..token.end].to_string(),
+        })
+        .collect();
+    assert_eq!(
+        kinds.join(" "),
+        "a ws = ws 1 ws line nl doc nl ; b ws = ws 2 nl ; "
+    );
+}
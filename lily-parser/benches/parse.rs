@@ -0,0 +1,107 @@
+//! Parses generated modules with thousands of declarations.
+//!
+//! With the `rayon` feature, every module is parsed both on a single thread
+//! and on the default thread pool, which shows the speed-up of the parallel
+//! phases:
+//!
+//! ```text
+//! cargo bench -p lily-parser --features rayon
+//! ```
+//!
+//! `parse_top_level` lexes the whole source on one thread, since layout
+//! depends on everything before it. Splitting the tokens into top-level
+//! groups is a single pass, after which the lexical and indentation errors
+//! of each group are collected in parallel. Imports and fixities are parsed
+//! in order, then declarations in parallel, and the module is assembled on
+//! one thread. The `lex` group measures the sequential lexing on its own,
+//! which bounds the speed-up that more threads can give.
+//!
+//! On a single core, 4000 declarations (about 880 KiB) took 134 ms to parse
+//! sequentially and 127 ms on the thread pool, of which lexing took 83 ms;
+//! 1000 declarations took 39 ms and 40 ms, with 21 ms of lexing.
+
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lily_lexer::lex;
+use lily_parser::parse_top_level;
+
+fn module(declarations: usize) -> String {
+    let mut source = String::from(
+        "import Data.Maybe (Maybe(..))\n\ninfixl 4 add as +\ninfixl 5 mul as *\ninfixr 9 type Function as ->\n\n",
+    );
+    for index in 0..declarations {
+        write!(
+            source,
+            "\
+-- | Declaration number {index}.
+example{index} : Maybe Int -> Int -> Int
+example{index} x y = case x of
+  Just z if positive z -> z * y + {index}
+  Nothing -> do
+    w <- pure (y + 1)
+    let
+      v = if w then w * w else y
+    pure v
+
+"
+        )
+        .unwrap();
+    }
+    source
+}
+
+fn parse(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("parse_top_level");
+    group.sample_size(20);
+    for declarations in [1000, 4000] {
+        let source = module(declarations);
+        assert!(parse_top_level(&source).1.is_empty());
+        group.throughput(Throughput::Bytes(source.len() as u64));
+
+        #[cfg(feature = "rayon")]
+        {
+            let sequential = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap();
+            group.bench_with_input(
+                BenchmarkId::new("sequential", declarations),
+                &source,
+                |bencher, source| sequential.install(|| bencher.iter(|| parse_top_level(source))),
+            );
+            group.bench_with_input(
+                BenchmarkId::new("parallel", declarations),
+                &source,
+                |bencher, source| bencher.iter(|| parse_top_level(source)),
+            );
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        group.bench_with_input(
+            BenchmarkId::new("sequential", declarations),
+            &source,
+            |bencher, source| bencher.iter(|| parse_top_level(source)),
+        );
+    }
+    group.finish();
+}
+
+/// Lexing is the part of `parse_top_level` that is never parallel.
+fn lexing(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("lex");
+    group.sample_size(20);
+    for declarations in [1000, 4000] {
+        let source = module(declarations);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(declarations),
+            &source,
+            |bencher, source| bencher.iter(|| lex(source)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, lexing);
+criterion_main!(benches);
@@ -0,0 +1,570 @@
+//! An arena-allocated representation of the AST.
+//!
+//! Nodes are stored in one [`Arena`] per kind and refer to their children
+//! through typed [`Id`]s, so later phases can attach information to nodes
+//! with an [`ArenaMap`] instead of keying on their offsets. A [`Module`] is
+//! lowered from the tree built by the parser with [`lower`], which allocates
+//! children before their parents, and interns every name and operator.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use lily_lexer::symbol::Symbol;
+use smol_str::SmolStr;
+
+use crate::types::{self, Fixity, Import, LesserPattern};
+
+/// An index into an [`Arena`] of `T`.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Id<T> {
+    index: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Id<T> {
+    fn new(index: usize) -> Self {
+        let index = u32::try_from(index).expect("arena is too large");
+        Id {
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// These are implemented by hand, as deriving them would require `T` to
+// implement them as well.
+
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> PartialOrd for Id<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Id<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for Id<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Id({})", self.index)
+    }
+}
+
+pub type ExpressionId = Id<Expression>;
+pub type GreaterPatternId = Id<GreaterPattern>;
+pub type TyId = Id<Ty>;
+pub type DeclarationId = Id<Declaration>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arena<T> {
+    nodes: Vec<T>,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena { nodes: vec![] }
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc(&mut self, node: T) -> Id<T> {
+        let id = Id::new(self.nodes.len());
+        self.nodes.push(node);
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterates over the nodes in allocation order.
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (Id::new(index), node))
+    }
+}
+
+impl<T> Index<Id<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: Id<T>) -> &T {
+        &self.nodes[id.index()]
+    }
+}
+
+impl<T> IndexMut<Id<T>> for Arena<T> {
+    fn index_mut(&mut self, id: Id<T>) -> &mut T {
+        &mut self.nodes[id.index()]
+    }
+}
+
+/// A side table that associates values with the nodes of an [`Arena`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaMap<T, V> {
+    values: Vec<Option<V>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, V> Default for ArenaMap<T, V> {
+    fn default() -> Self {
+        ArenaMap {
+            values: vec![],
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, V> ArenaMap<T, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value that was previously associated with `id`, if any.
+    pub fn insert(&mut self, id: Id<T>, value: V) -> Option<V> {
+        if self.values.len() <= id.index() {
+            self.values.resize_with(id.index() + 1, || None);
+        }
+        self.values[id.index()].replace(value)
+    }
+
+    pub fn remove(&mut self, id: Id<T>) -> Option<V> {
+        self.values.get_mut(id.index())?.take()
+    }
+
+    pub fn get(&self, id: Id<T>) -> Option<&V> {
+        self.values.get(id.index())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut V> {
+        self.values.get_mut(id.index())?.as_mut()
+    }
+
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &V)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((Id::new(index), value.as_ref()?)))
+    }
+}
+
+impl<T, V> Index<Id<T>> for ArenaMap<T, V> {
+    type Output = V;
+
+    fn index(&self, id: Id<T>) -> &V {
+        self.get(id).expect("no value for this id")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GreaterPattern {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: GreaterPatternK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GreaterPatternK {
+    Application(GreaterPatternId, Vec<GreaterPatternId>),
+    BinaryOperator(GreaterPatternId, Symbol, GreaterPatternId),
+    Constructor(Symbol),
+    Integer(SmolStr),
+    Null,
+    Parenthesized(GreaterPatternId),
+    Variable(Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expression {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: ExpressionK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExpressionK {
+    Application(ExpressionId, Vec<ExpressionId>),
+    BinaryOperator(ExpressionId, Symbol, ExpressionId),
+    CaseOf(Vec<ExpressionId>, Vec<CaseArm>),
+    Constructor(Symbol),
+    DoBlock(Vec<DoStatement>),
+    Float(SmolStr),
+    IfThenElse(ExpressionId, ExpressionId, ExpressionId),
+    Integer(SmolStr),
+    Let(Vec<DeclarationId>, ExpressionId),
+    Parenthesized(ExpressionId),
+    Variable(Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoStatement {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: DoStatementK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DoStatementK {
+    BindExpression(LesserPattern, ExpressionId),
+    DiscardExpression(ExpressionId),
+    LetStatement(Vec<DeclarationId>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CaseArm {
+    pub patterns: Vec<GreaterPatternId>,
+    pub condition: Option<ExpressionId>,
+    pub expression: ExpressionId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ty {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: TyK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TyK {
+    Application(TyId, Vec<TyId>),
+    BinaryOperator(TyId, Symbol, TyId),
+    Constructor(Symbol),
+    Parenthesized(TyId),
+    Variable(Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Declaration {
+    pub begin: usize,
+    pub end: usize,
+    pub docs: Option<SmolStr>,
+    pub kind: DeclarationK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeclarationK {
+    ValueDeclaration(Symbol, Vec<LesserPattern>, ExpressionId),
+    TypeDeclaration(Symbol, TyId),
+    ForeignValue(Symbol, TyId),
+    ForeignData(Symbol, TyId),
+    Error,
+}
+
+/// The arenas that own every node of a [`Module`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arenas {
+    pub expressions: Arena<Expression>,
+    pub greater_patterns: Arena<GreaterPattern>,
+    pub types: Arena<Ty>,
+    pub declarations: Arena<Declaration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Module {
+    pub imports: Vec<Import>,
+    pub fixities: Vec<Fixity>,
+    /// The top-level declarations, in source order.
+    pub declarations: Vec<DeclarationId>,
+    pub arenas: Arenas,
+}
+
+macro_rules! index_arenas {
+    ($($node:ty => $field:ident),* $(,)?) => {
+        $(
+            impl Index<Id<$node>> for Arenas {
+                type Output = $node;
+
+                fn index(&self, id: Id<$node>) -> &$node {
+                    &self.$field[id]
+                }
+            }
+
+            impl IndexMut<Id<$node>> for Arenas {
+                fn index_mut(&mut self, id: Id<$node>) -> &mut $node {
+                    &mut self.$field[id]
+                }
+            }
+
+            impl Index<Id<$node>> for Module {
+                type Output = $node;
+
+                fn index(&self, id: Id<$node>) -> &$node {
+                    &self.arenas[id]
+                }
+            }
+
+            impl IndexMut<Id<$node>> for Module {
+                fn index_mut(&mut self, id: Id<$node>) -> &mut $node {
+                    &mut self.arenas[id]
+                }
+            }
+        )*
+    };
+}
+
+index_arenas! {
+    Expression => expressions,
+    GreaterPattern => greater_patterns,
+    Ty => types,
+    Declaration => declarations,
+}
+
+/// Lowers a module into arenas.
+///
+/// Children are allocated before their parents, and siblings in source
+/// order, so every node has a greater [`Id`] than the nodes below it.
+pub fn lower(module: &types::Module) -> Module {
+    let mut arenas = Arenas::default();
+    let declarations = arenas.lower_declarations(&module.declarations);
+    Module {
+        imports: module.imports.clone(),
+        fixities: module.fixities.clone(),
+        declarations,
+        arenas,
+    }
+}
+
+impl Arenas {
+    fn lower_declarations(&mut self, declarations: &[types::Declaration]) -> Vec<DeclarationId> {
+        declarations
+            .iter()
+            .map(|declaration| self.lower_declaration(declaration))
+            .collect()
+    }
+
+    fn lower_declaration(&mut self, declaration: &types::Declaration) -> DeclarationId {
+        let kind = match &declaration.kind {
+            types::DeclarationK::ValueDeclaration(name, patterns, expression) => {
+                let expression = self.lower_expression(expression);
+                DeclarationK::ValueDeclaration(*name, patterns.clone(), expression)
+            }
+            types::DeclarationK::TypeDeclaration(name, ty) => {
+                DeclarationK::TypeDeclaration(*name, self.lower_ty(ty))
+            }
+            types::DeclarationK::ForeignValue(name, ty) => {
+                DeclarationK::ForeignValue(*name, self.lower_ty(ty))
+            }
+            types::DeclarationK::ForeignData(name, ty) => {
+                DeclarationK::ForeignData(*name, self.lower_ty(ty))
+            }
+            types::DeclarationK::Error => DeclarationK::Error,
+        };
+        self.declarations.alloc(Declaration {
+            begin: declaration.begin,
+            end: declaration.end,
+            docs: declaration.docs.clone(),
+            kind,
+        })
+    }
+
+    fn lower_expressions(&mut self, expressions: &[types::Expression]) -> Vec<ExpressionId> {
+        expressions
+            .iter()
+            .map(|expression| self.lower_expression(expression))
+            .collect()
+    }
+
+    fn lower_expression(&mut self, expression: &types::Expression) -> ExpressionId {
+        let kind = match &expression.kind {
+            types::ExpressionK::Application(function, arguments) => {
+                let function = self.lower_expression(function);
+                ExpressionK::Application(function, self.lower_expressions(arguments))
+            }
+            types::ExpressionK::BinaryOperator(left, operator, right) => {
+                let left = self.lower_expression(left);
+                let right = self.lower_expression(right);
+                ExpressionK::BinaryOperator(left, *operator, right)
+            }
+            types::ExpressionK::CaseOf(expressions, arms) => {
+                let expressions = self.lower_expressions(expressions);
+                let arms = arms.iter().map(|arm| self.lower_case_arm(arm)).collect();
+                ExpressionK::CaseOf(expressions, arms)
+            }
+            types::ExpressionK::Constructor(name) => ExpressionK::Constructor(*name),
+            types::ExpressionK::DoBlock(statements) => ExpressionK::DoBlock(
+                statements
+                    .iter()
+                    .map(|statement| self.lower_do_statement(statement))
+                    .collect(),
+            ),
+            types::ExpressionK::Float(value) => ExpressionK::Float(value.clone()),
+            types::ExpressionK::IfThenElse(condition, then, otherwise) => {
+                let condition = self.lower_expression(condition);
+                let then = self.lower_expression(then);
+                let otherwise = self.lower_expression(otherwise);
+                ExpressionK::IfThenElse(condition, then, otherwise)
+            }
+            types::ExpressionK::Integer(value) => ExpressionK::Integer(value.clone()),
+            types::ExpressionK::Let(declarations, expression) => {
+                let declarations = self.lower_declarations(declarations);
+                ExpressionK::Let(declarations, self.lower_expression(expression))
+            }
+            types::ExpressionK::Parenthesized(expression) => {
+                ExpressionK::Parenthesized(self.lower_expression(expression))
+            }
+            types::ExpressionK::Variable(name) => ExpressionK::Variable(*name),
+        };
+        self.expressions.alloc(Expression {
+            begin: expression.begin,
+            end: expression.end,
+            kind,
+        })
+    }
+
+    fn lower_do_statement(&mut self, statement: &types::DoStatement) -> DoStatement {
+        let kind = match &statement.kind {
+            types::DoStatementK::BindExpression(pattern, expression) => {
+                DoStatementK::BindExpression(pattern.clone(), self.lower_expression(expression))
+            }
+            types::DoStatementK::DiscardExpression(expression) => {
+                DoStatementK::DiscardExpression(self.lower_expression(expression))
+            }
+            types::DoStatementK::LetStatement(declarations) => {
+                DoStatementK::LetStatement(self.lower_declarations(declarations))
+            }
+        };
+        DoStatement {
+            begin: statement.begin,
+            end: statement.end,
+            kind,
+        }
+    }
+
+    fn lower_case_arm(&mut self, arm: &types::CaseArm) -> CaseArm {
+        let patterns = self.lower_greater_patterns(&arm.patterns);
+        let condition = arm
+            .condition
+            .as_ref()
+            .map(|condition| self.lower_expression(condition));
+        let expression = self.lower_expression(&arm.expression);
+        CaseArm {
+            patterns,
+            condition,
+            expression,
+        }
+    }
+
+    fn lower_greater_patterns(
+        &mut self,
+        patterns: &[types::GreaterPattern],
+    ) -> Vec<GreaterPatternId> {
+        patterns
+            .iter()
+            .map(|pattern| self.lower_greater_pattern(pattern))
+            .collect()
+    }
+
+    fn lower_greater_pattern(&mut self, pattern: &types::GreaterPattern) -> GreaterPatternId {
+        let kind = match &pattern.kind {
+            types::GreaterPatternK::Application(function, arguments) => {
+                let function = self.lower_greater_pattern(function);
+                GreaterPatternK::Application(function, self.lower_greater_patterns(arguments))
+            }
+            types::GreaterPatternK::BinaryOperator(left, operator, right) => {
+                let left = self.lower_greater_pattern(left);
+                let right = self.lower_greater_pattern(right);
+                GreaterPatternK::BinaryOperator(left, *operator, right)
+            }
+            types::GreaterPatternK::Constructor(name) => GreaterPatternK::Constructor(*name),
+            types::GreaterPatternK::Integer(value) => GreaterPatternK::Integer(value.clone()),
+            types::GreaterPatternK::Null => GreaterPatternK::Null,
+            types::GreaterPatternK::Parenthesized(pattern) => {
+                GreaterPatternK::Parenthesized(self.lower_greater_pattern(pattern))
+            }
+            types::GreaterPatternK::Variable(name) => GreaterPatternK::Variable(*name),
+        };
+        self.greater_patterns.alloc(GreaterPattern {
+            begin: pattern.begin,
+            end: pattern.end,
+            kind,
+        })
+    }
+
+    fn lower_ty(&mut self, ty: &types::Ty) -> TyId {
+        let kind = match &ty.kind {
+            types::TyK::Application(function, arguments) => {
+                let function = self.lower_ty(function);
+                let arguments = arguments.iter().map(|argument| self.lower_ty(argument));
+                TyK::Application(function, arguments.collect())
+            }
+            types::TyK::BinaryOperator(left, operator, right) => {
+                let left = self.lower_ty(left);
+                let right = self.lower_ty(right);
+                TyK::BinaryOperator(left, *operator, right)
+            }
+            types::TyK::Constructor(name) => TyK::Constructor(*name),
+            types::TyK::Parenthesized(ty) => TyK::Parenthesized(self.lower_ty(ty)),
+            types::TyK::Variable(name) => TyK::Variable(*name),
+        };
+        self.types.alloc(Ty {
+            begin: ty.begin,
+            end: ty.end,
+            kind,
+        })
+    }
+}
@@ -1,5 +1,6 @@
 mod declaration;
 mod expression;
 mod fixity;
+mod import;
 mod patterns;
 mod ty;
@@ -1,19 +1,26 @@
-use anyhow::bail;
-use lily_lexer::types::{IdentifierK, LayoutK, OperatorK, Token, TokenK};
-use smol_str::SmolStr;
+use lily_lexer::{
+    symbol::Symbol,
+    types::{IdentifierK, LayoutK, OperatorK, Token, TokenK},
+};
 
 use crate::{
     cursor::Cursor,
-    errors::ParseError,
+    errors::{ParseError, ParseResult},
     expect_token,
+    syntax::SyntaxKind,
     types::{Declaration, DeclarationK, Ty},
 };
 
 impl<'a> Cursor<'a> {
-    fn declaration_lower(&mut self) -> anyhow::Result<Declaration> {
-        let (declaration_begin, identifier) = {
-            let Token { begin, end, .. } = self.take()?;
-            (begin, SmolStr::new(&self.source[begin..end]))
+    fn declaration_lower(&mut self) -> ParseResult<Declaration> {
+        let marker = self.start();
+        let (declaration_begin, docs, identifier) = {
+            let token @ Token { begin, end, .. } = self.take()?;
+            (
+                begin,
+                self.docs(&token),
+                Symbol::intern(&self.source[begin..end]),
+            )
         };
 
         if let TokenK::Operator(OperatorK::Colon) = self.peek()?.kind {
@@ -23,9 +30,11 @@ impl<'a> Cursor<'a> {
                 ..
             } = self.ty()?;
             expect_token!(self, TokenK::Layout(LayoutK::Separator));
+            self.finish(marker, SyntaxKind::TypeDeclaration);
             return Ok(Declaration {
                 begin: declaration_begin,
                 end: declaration_end,
+                docs,
                 kind: DeclarationK::TypeDeclaration(identifier, ty),
             });
         }
@@ -38,24 +47,71 @@ impl<'a> Cursor<'a> {
                 (expression.end, expression)
             };
             expect_token!(self, TokenK::Layout(LayoutK::Separator));
+            self.finish(marker, SyntaxKind::ValueDeclaration);
             return Ok(Declaration {
                 begin: declaration_begin,
                 end: declaration_end,
+                docs,
                 kind: DeclarationK::ValueDeclaration(identifier, lesser_patterns, expression),
             });
         }
 
-        bail!(ParseError::UnexpectedToken(self.peek()?.kind));
+        self.unexpected(&[TokenK::Operator(OperatorK::Equal)])
+    }
+
+    fn declaration_foreign(&mut self) -> ParseResult<Declaration> {
+        let marker = self.start();
+        let token @ Token {
+            begin: declaration_begin,
+            ..
+        } = expect_token!(self, TokenK::Identifier(IdentifierK::Foreign));
+        let docs = self.docs(&token);
+        expect_token!(self, TokenK::Identifier(IdentifierK::Import));
+
+        let is_data = if let TokenK::Identifier(IdentifierK::Data) = self.peek()?.kind {
+            self.take()?;
+            true
+        } else {
+            false
+        };
+
+        let identifier = {
+            let Token { begin, end, .. } = if is_data {
+                expect_token!(self, TokenK::Identifier(IdentifierK::Upper))
+            } else {
+                expect_token!(self, TokenK::Identifier(IdentifierK::Lower))
+            };
+            Symbol::intern(&self.source[begin..end])
+        };
+
+        expect_token!(self, TokenK::Operator(OperatorK::Colon));
+        let ty @ Ty {
+            end: declaration_end,
+            ..
+        } = self.ty()?;
+        expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::ForeignDeclaration);
+
+        Ok(Declaration {
+            begin: declaration_begin,
+            end: declaration_end,
+            docs,
+            kind: if is_data {
+                DeclarationK::ForeignData(identifier, ty)
+            } else {
+                DeclarationK::ForeignValue(identifier, ty)
+            },
+        })
     }
 
-    pub fn declaration_let(&mut self) -> anyhow::Result<Declaration> {
+    pub fn declaration_let(&mut self) -> ParseResult<Declaration> {
         if let TokenK::Identifier(IdentifierK::Lower) = self.peek()?.kind {
             return self.declaration_lower();
         }
-        bail!(ParseError::UnexpectedToken(self.peek()?.kind))
+        self.unexpected(&[TokenK::Identifier(IdentifierK::Lower)])
     }
 
-    pub fn declaration_let_block(&mut self) -> anyhow::Result<Vec<Declaration>> {
+    pub fn declaration_let_block(&mut self) -> ParseResult<Vec<Declaration>> {
         let mut declarations = vec![self.declaration_let()?];
         loop {
             if let TokenK::Layout(LayoutK::End) = self.peek()?.kind {
@@ -66,10 +122,16 @@ impl<'a> Cursor<'a> {
         Ok(declarations)
     }
 
-    pub fn declaration(&mut self) -> anyhow::Result<Declaration> {
+    pub fn declaration(&mut self) -> ParseResult<Declaration> {
         if let TokenK::Identifier(IdentifierK::Lower) = self.peek()?.kind {
             return self.declaration_lower();
         }
-        bail!(ParseError::UnexpectedToken(self.peek()?.kind));
+        if let TokenK::Identifier(IdentifierK::Foreign) = self.peek()?.kind {
+            return self.declaration_foreign();
+        }
+        self.unexpected(&[
+            TokenK::Identifier(IdentifierK::Lower),
+            TokenK::Identifier(IdentifierK::Foreign),
+        ])
     }
 }
@@ -1,65 +1,87 @@
-use anyhow::{bail, Context};
-use lily_lexer::types::{DelimiterK, DigitK, IdentifierK, LayoutK, OperatorK, Token, TokenK};
-use smol_str::SmolStr;
+use lily_lexer::{
+    symbol::Symbol,
+    types::{DelimiterK, DigitK, IdentifierK, LayoutK, OperatorK, QualifiedK, Token, TokenK},
+};
 
 use crate::{
     cursor::{expect_token, Cursor},
-    errors::ParseError,
-    types::{CaseArm, DoStatement, DoStatementK, Expression, ExpressionK, LesserPattern},
+    errors::{ParseError, ParseResult},
+    syntax::SyntaxKind,
+    types::{CaseArm, DoStatement, DoStatementK, Expression, ExpressionK, Fixity, LesserPattern},
 };
 
+/// Tokens that begin an atom.
+const ATOM_STARTS: &[TokenK] = &[
+    TokenK::Digit(DigitK::Int),
+    TokenK::Digit(DigitK::Float),
+    TokenK::Identifier(IdentifierK::Lower),
+    TokenK::Qualified(QualifiedK::Lower),
+    TokenK::Identifier(IdentifierK::Upper),
+    TokenK::Qualified(QualifiedK::Upper),
+    TokenK::OpenDelimiter(DelimiterK::Round),
+];
+
+/// Keywords that begin a block expression, which can also be an argument.
+const BLOCK_STARTS: &[TokenK] = &[
+    TokenK::Identifier(IdentifierK::If),
+    TokenK::Identifier(IdentifierK::Do),
+    TokenK::Identifier(IdentifierK::Case),
+    TokenK::Identifier(IdentifierK::Let),
+];
+
 impl<'a> Cursor<'a> {
-    fn expression_atom(&mut self) -> anyhow::Result<Expression> {
+    fn expression_atom(&mut self) -> ParseResult<Expression> {
+        if !ATOM_STARTS.contains(&self.peek()?.kind) {
+            return self.unexpected(&[ATOM_STARTS, BLOCK_STARTS].concat());
+        }
+
+        let marker = self.start();
         let Token {
             begin, end, kind, ..
         } = self.take()?;
+        let text = &self.source[begin..end];
 
-        if let TokenK::Digit(DigitK::Int) = kind {
-            return Ok(Expression {
-                begin,
-                end,
-                kind: ExpressionK::Integer(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Digit(DigitK::Float) = kind {
-            return Ok(Expression {
-                begin,
-                end,
-                kind: ExpressionK::Float(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Identifier(IdentifierK::Lower) = kind {
-            return Ok(Expression {
-                begin,
-                end,
-                kind: ExpressionK::Variable(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Identifier(IdentifierK::Upper) = kind {
-            return Ok(Expression {
-                begin,
-                end,
-                kind: ExpressionK::Constructor(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::OpenDelimiter(DelimiterK::Round) = kind {
-            let expression = self.expression_core(0)?;
-            let Token { end, .. } = expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
-            return Ok(Expression {
-                begin,
-                end,
-                kind: ExpressionK::Parenthesized(Box::new(expression)),
-            });
-        }
+        let (kind, syntax_kind) = match kind {
+            TokenK::Digit(DigitK::Int) => (
+                ExpressionK::Integer(text.into()),
+                SyntaxKind::IntegerExpression,
+            ),
+            TokenK::Digit(DigitK::Float) => {
+                (ExpressionK::Float(text.into()), SyntaxKind::FloatExpression)
+            }
+            TokenK::Identifier(IdentifierK::Lower) | TokenK::Qualified(QualifiedK::Lower) => (
+                ExpressionK::Variable(Symbol::intern(text)),
+                SyntaxKind::VariableExpression,
+            ),
+            TokenK::Identifier(IdentifierK::Upper) | TokenK::Qualified(QualifiedK::Upper) => (
+                ExpressionK::Constructor(Symbol::intern(text)),
+                SyntaxKind::ConstructorExpression,
+            ),
+            TokenK::OpenDelimiter(DelimiterK::Round) => {
+                let expression = self.expression_core(0, None)?;
+                let Token { end, .. } =
+                    expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
+                self.finish(marker, SyntaxKind::ParenthesizedExpression);
+                return Ok(Expression {
+                    begin,
+                    end,
+                    kind: ExpressionK::Parenthesized(Box::new(expression)),
+                });
+            }
+            kind => {
+                return Err(ParseError::InternalError(format!(
+                    "Unhandled atom '{:?}'",
+                    kind
+                )))
+            }
+        };
+        self.finish(marker, syntax_kind);
 
-        bail!(ParseError::UnexpectedToken(kind));
+        Ok(Expression { begin, end, kind })
     }
 
-    fn expression_if(&mut self) -> anyhow::Result<Expression> {
+    fn expression_if(&mut self) -> ParseResult<Expression> {
+        let marker = self.start();
         let Token { begin, .. } = expect_token!(self, TokenK::Identifier(IdentifierK::If));
         let condition = self.expression()?;
 
@@ -68,6 +90,7 @@ impl<'a> Cursor<'a> {
 
         expect_token!(self, TokenK::Identifier(IdentifierK::Else));
         let else_value @ Expression { end, .. } = self.expression()?;
+        self.finish(marker, SyntaxKind::IfExpression);
 
         Ok(Expression {
             begin,
@@ -80,7 +103,8 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_do(&mut self) -> anyhow::Result<Expression> {
+    fn expression_do(&mut self) -> ParseResult<Expression> {
+        let marker = self.start();
         let Token {
             begin: do_begin,
             end: do_end,
@@ -88,6 +112,7 @@ impl<'a> Cursor<'a> {
         } = expect_token!(self, TokenK::Identifier(IdentifierK::Do));
 
         if let TokenK::Layout(LayoutK::Separator) = self.peek()?.kind {
+            self.finish(marker, SyntaxKind::DoExpression);
             return Ok(Expression {
                 begin: do_begin,
                 end: do_end,
@@ -100,12 +125,11 @@ impl<'a> Cursor<'a> {
         let statements = self.expression_do_statements()?;
         let do_end = statements
             .last()
-            .context(ParseError::InternalError(
-                "Cannot determine last do statement".into(),
-            ))?
+            .ok_or_else(|| ParseError::InternalError("Cannot determine last do statement".into()))?
             .end;
 
         expect_token!(self, TokenK::Layout(LayoutK::End));
+        self.finish(marker, SyntaxKind::DoExpression);
 
         Ok(Expression {
             begin: do_begin,
@@ -114,8 +138,9 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_do_statement(&mut self) -> anyhow::Result<DoStatement> {
+    fn expression_do_statement(&mut self) -> ParseResult<DoStatement> {
         if let TokenK::Identifier(IdentifierK::Let) = self.peek()?.kind {
+            let marker = self.start();
             let Token {
                 begin: let_begin, ..
             } = expect_token!(self, TokenK::Identifier(IdentifierK::Let));
@@ -125,13 +150,14 @@ impl<'a> Cursor<'a> {
             let declarations = self.declaration_let_block()?;
             let let_end = declarations
                 .last()
-                .context(ParseError::InternalError(
-                    "Cannot determine last declaration".into(),
-                ))?
+                .ok_or_else(|| {
+                    ParseError::InternalError("Cannot determine last declaration".into())
+                })?
                 .end;
 
             expect_token!(self, TokenK::Layout(LayoutK::End));
             expect_token!(self, TokenK::Layout(LayoutK::Separator));
+            self.finish(marker, SyntaxKind::LetStatement);
 
             return Ok(DoStatement {
                 begin: let_begin,
@@ -140,22 +166,29 @@ impl<'a> Cursor<'a> {
             });
         }
 
-        if let do_statement @ Ok(_) = self.attempt(Self::expression_do_statement_discard) {
-            return do_statement;
-        }
+        let discard_error = match self.attempt(Self::expression_do_statement_discard) {
+            do_statement @ Ok(_) => return do_statement,
+            Err(error) => error,
+        };
 
-        if let do_statement @ Ok(_) = self.attempt(Self::expression_do_statement_bind) {
-            return do_statement;
-        }
+        let bind_error = match self.attempt(Self::expression_do_statement_bind) {
+            do_statement @ Ok(_) => return do_statement,
+            Err(error) => error,
+        };
 
-        bail!(ParseError::UnexpectedToken(self.peek()?.kind));
+        // Report the error of whichever statement form got further.
+        Err(std::cmp::max_by_key(discard_error, bind_error, |error| {
+            error.span()
+        }))
     }
 
-    fn expression_do_statement_bind(&mut self) -> anyhow::Result<DoStatement> {
+    fn expression_do_statement_bind(&mut self) -> ParseResult<DoStatement> {
+        let marker = self.start();
         let lesser_pattern @ LesserPattern { begin, .. } = self.lesser_pattern()?;
         expect_token!(self, TokenK::Operator(OperatorK::ArrowLeft));
         let expression @ Expression { end, .. } = self.expression()?;
         expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::BindStatement);
         Ok(DoStatement {
             begin,
             end,
@@ -163,9 +196,11 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_do_statement_discard(&mut self) -> anyhow::Result<DoStatement> {
+    fn expression_do_statement_discard(&mut self) -> ParseResult<DoStatement> {
+        let marker = self.start();
         let expression @ Expression { begin, end, .. } = self.expression()?;
         expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::DiscardStatement);
         Ok(DoStatement {
             begin,
             end,
@@ -173,7 +208,7 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_do_statements(&mut self) -> anyhow::Result<Vec<DoStatement>> {
+    fn expression_do_statements(&mut self) -> ParseResult<Vec<DoStatement>> {
         let mut statements: Vec<DoStatement> = vec![];
         loop {
             if let TokenK::Layout(LayoutK::End) = self.peek()?.kind {
@@ -184,7 +219,8 @@ impl<'a> Cursor<'a> {
         Ok(statements)
     }
 
-    fn expression_case(&mut self) -> anyhow::Result<Expression> {
+    fn expression_case(&mut self) -> ParseResult<Expression> {
+        let marker = self.start();
         let Token {
             begin: case_begin, ..
         } = expect_token!(self, TokenK::Identifier(IdentifierK::Case));
@@ -196,12 +232,11 @@ impl<'a> Cursor<'a> {
         let arms = self.expression_case_arms()?;
 
         expect_token!(self, TokenK::Layout(LayoutK::End));
+        self.finish(marker, SyntaxKind::CaseExpression);
 
         let case_end = arms
             .last()
-            .context(ParseError::InternalError(
-                "Cannot determine last match arm".into(),
-            ))?
+            .ok_or_else(|| ParseError::InternalError("Cannot determine last match arm".into()))?
             .expression
             .end;
 
@@ -212,7 +247,7 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_case_expressions(&mut self) -> anyhow::Result<Vec<Expression>> {
+    fn expression_case_expressions(&mut self) -> ParseResult<Vec<Expression>> {
         let mut expressions = vec![self.expression()?];
         loop {
             if let TokenK::Operator(OperatorK::Comma) = self.peek()?.kind {
@@ -228,7 +263,8 @@ impl<'a> Cursor<'a> {
         Ok(expressions)
     }
 
-    fn expression_case_arm(&mut self) -> anyhow::Result<CaseArm> {
+    fn expression_case_arm(&mut self) -> ParseResult<CaseArm> {
+        let marker = self.start();
         let patterns = self.greater_patterns()?;
         let condition = if let TokenK::Identifier(IdentifierK::If) = self.peek()?.kind {
             self.take()?;
@@ -239,6 +275,7 @@ impl<'a> Cursor<'a> {
         expect_token!(self, TokenK::Operator(OperatorK::ArrowRight));
         let expression = self.expression()?;
         expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::CaseArm);
         Ok(CaseArm {
             patterns,
             condition,
@@ -246,7 +283,7 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_case_arms(&mut self) -> anyhow::Result<Vec<CaseArm>> {
+    fn expression_case_arms(&mut self) -> ParseResult<Vec<CaseArm>> {
         let mut arms = vec![self.expression_case_arm()?];
         loop {
             if let TokenK::Layout(LayoutK::End) = self.peek()?.kind {
@@ -257,7 +294,8 @@ impl<'a> Cursor<'a> {
         Ok(arms)
     }
 
-    fn expression_let(&mut self) -> anyhow::Result<Expression> {
+    fn expression_let(&mut self) -> ParseResult<Expression> {
+        let marker = self.start();
         let Token {
             begin: let_begin, ..
         } = expect_token!(self, TokenK::Identifier(IdentifierK::Let));
@@ -265,18 +303,13 @@ impl<'a> Cursor<'a> {
         expect_token!(self, TokenK::Layout(LayoutK::Begin));
 
         let declarations = self.declaration_let_block()?;
-        let let_end = declarations
-            .last()
-            .context(ParseError::InternalError(
-                "Cannot determine last declaration".into(),
-            ))?
-            .end;
 
         expect_token!(self, TokenK::Layout(LayoutK::End));
 
         expect_token!(self, TokenK::Identifier(IdentifierK::In));
 
-        let expression = self.expression()?;
+        let expression @ Expression { end: let_end, .. } = self.expression()?;
+        self.finish(marker, SyntaxKind::LetExpression);
 
         Ok(Expression {
             begin: let_begin,
@@ -285,7 +318,13 @@ impl<'a> Cursor<'a> {
         })
     }
 
-    fn expression_core(&mut self, minimum_power: u8) -> anyhow::Result<Expression> {
+    fn expression_core(
+        &mut self,
+        minimum_power: u8,
+        parent: Option<(Token, &'a Fixity)>,
+    ) -> ParseResult<Expression> {
+        let mut previous = parent;
+
         if let TokenK::Identifier(IdentifierK::If) = self.peek()?.kind {
             return self.expression_if();
         }
@@ -299,6 +338,10 @@ impl<'a> Cursor<'a> {
             return self.expression_let();
         }
 
+        // The node of the accumulator, and whether it is an application
+        // that later arguments still go into.
+        let mut marker = self.checkpoint();
+        let mut is_application = false;
         let mut accumulator = self.expression_atom()?;
 
         loop {
@@ -307,16 +350,23 @@ impl<'a> Cursor<'a> {
             }
 
             if self.peek()?.is_block_argument() {
+                if std::mem::take(&mut is_application) {
+                    self.finish(marker, SyntaxKind::ApplicationExpression);
+                }
+                marker = self.precede(marker);
                 let argument = match self.peek()?.kind {
                     TokenK::Identifier(IdentifierK::If) => self.expression_if()?,
                     TokenK::Identifier(IdentifierK::Do) => self.expression_do()?,
                     TokenK::Identifier(IdentifierK::Case) => self.expression_case()?,
                     TokenK::Identifier(IdentifierK::Let) => self.expression_let()?,
-                    kind => bail!(ParseError::InternalError(format!(
-                        "Unhandled block argument '{:?}'",
-                        kind
-                    ))),
+                    kind => {
+                        return Err(ParseError::InternalError(format!(
+                            "Unhandled block argument '{:?}'",
+                            kind
+                        )))
+                    }
                 };
+                is_application = true;
                 accumulator = Expression {
                     begin: accumulator.begin,
                     end: argument.end,
@@ -325,25 +375,32 @@ impl<'a> Cursor<'a> {
                 continue;
             }
 
-            if let Token {
+            if let token @ Token {
                 begin,
                 end,
-                kind: TokenK::Operator(OperatorK::Source),
+                kind: TokenK::Operator(OperatorK::Source) | TokenK::Qualified(QualifiedK::Operator),
                 ..
-            } = self.peek()?
+            } = *self.peek()?
             {
-                let source_range = *begin..*end;
-                let operator = SmolStr::new(&self.source[source_range]);
+                let operator = Symbol::intern(&self.source[begin..end]);
 
-                let (left_power, right_power) = self.get_fixity(&operator)?;
+                let fixity = self.get_fixity(&token, operator)?;
+                let (left_power, right_power) = fixity.as_pair();
 
                 if left_power < minimum_power {
                     break;
                 } else {
+                    self.check_associativity(previous, (token, fixity))?;
+                    if std::mem::take(&mut is_application) {
+                        self.finish(marker, SyntaxKind::ApplicationExpression);
+                    }
+                    marker = self.precede(marker);
                     self.take()?;
+                    previous = Some((token, fixity));
                 }
 
-                let argument = self.expression_core(right_power)?;
+                let argument = self.expression_core(right_power, previous)?;
+                self.finish(marker, SyntaxKind::BinaryOperatorExpression);
                 accumulator = Expression {
                     begin: accumulator.begin,
                     end: argument.end,
@@ -356,6 +413,10 @@ impl<'a> Cursor<'a> {
                 continue;
             }
 
+            if !is_application {
+                marker = self.precede(marker);
+                is_application = true;
+            }
             let argument = self.expression_atom()?;
             match &mut accumulator.kind {
                 ExpressionK::Application(_, arguments) => {
@@ -372,10 +433,13 @@ impl<'a> Cursor<'a> {
             }
         }
 
+        if is_application {
+            self.finish(marker, SyntaxKind::ApplicationExpression);
+        }
         Ok(accumulator)
     }
 
-    pub fn expression(&mut self) -> anyhow::Result<Expression> {
-        self.expression_core(0)
+    pub fn expression(&mut self) -> ParseResult<Expression> {
+        self.expression_core(0, None)
     }
 }
@@ -1,71 +1,83 @@
-use anyhow::Context;
-use lily_lexer::types::{DigitK, IdentifierK, LayoutK, Token, TokenK};
-use smol_str::SmolStr;
+use lily_lexer::{
+    symbol::Symbol,
+    types::{DigitK, IdentifierK, LayoutK, OperatorK, Token, TokenK},
+};
 
 use crate::{
     cursor::{expect_token, Cursor},
-    errors::ParseError,
+    errors::{ParseError, ParseResult},
+    syntax::SyntaxKind,
     types::{Associativity, Domain, Fixity},
 };
 
 impl<'a> Cursor<'a> {
-    pub fn fixity(&mut self) -> anyhow::Result<(SmolStr, Fixity)> {
+    pub fn fixity(&mut self) -> ParseResult<Fixity> {
+        let marker = self.start();
         let Token {
             begin: fixity_begin,
             kind,
             ..
         } = expect_token!(
             self,
-            TokenK::Identifier(IdentifierK::Infixl | IdentifierK::Infixr)
+            TokenK::Identifier(IdentifierK::Infix),
+            TokenK::Identifier(IdentifierK::Infixl),
+            TokenK::Identifier(IdentifierK::Infixr)
         );
         let associativity = match kind {
+            TokenK::Identifier(IdentifierK::Infix) => Associativity::Infix,
             TokenK::Identifier(IdentifierK::Infixl) => Associativity::Infixl,
             TokenK::Identifier(IdentifierK::Infixr) => Associativity::Infixr,
             _ => unreachable!(),
         };
 
-        let Token { begin, end, .. } = expect_token!(self, TokenK::Digit(DigitK::Int));
+        let token @ Token { begin, end, .. } = expect_token!(self, TokenK::Digit(DigitK::Int));
         let binding_power = self.source[begin..end]
             .parse()
-            .context(ParseError::InternalError(
-                "Malformed digit token.".to_string(),
-            ))?;
+            .ok()
+            .filter(|binding_power| *binding_power <= Fixity::MAX_BINDING_POWER)
+            .ok_or(ParseError::InvalidBindingPower { token })?;
 
         let (domain, identifier) = if let TokenK::Identifier(IdentifierK::Type) = self.peek()?.kind
         {
             self.take()?;
             let Token { begin, end, .. } =
                 expect_token!(self, TokenK::Identifier(IdentifierK::Upper));
-            let identifier = SmolStr::new(&self.source[begin..end]);
+            let identifier = Symbol::intern(&self.source[begin..end]);
             (Domain::Type, identifier)
         } else {
             let Token { begin, end, .. } =
                 expect_token!(self, TokenK::Identifier(IdentifierK::Lower));
-            let identifier = SmolStr::new(&self.source[begin..end]);
+            let identifier = Symbol::intern(&self.source[begin..end]);
             (Domain::Value, identifier)
         };
 
         expect_token!(self, TokenK::Identifier(IdentifierK::As));
 
-        let Token {
+        let token @ Token {
             begin,
             end: fixity_end,
+            kind,
             ..
-        } = expect_token!(self, TokenK::Operator(_));
-        let operator = SmolStr::new(&self.source[begin..fixity_end]);
+        } = self.take()?;
+        if !matches!(kind, TokenK::Operator(_)) {
+            return Err(ParseError::UnexpectedToken {
+                token,
+                expected: vec![TokenK::Operator(OperatorK::Source)],
+            });
+        }
+        let operator = Symbol::intern(&self.source[begin..fixity_end]);
 
         expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::Fixity);
 
-        Ok((
+        Ok(Fixity {
+            begin: fixity_begin,
+            end: fixity_end,
+            associativity,
+            domain,
+            binding_power,
+            identifier,
             operator,
-            Fixity {
-                begin: fixity_begin,
-                end: fixity_end,
-                associativity,
-                domain,
-                binding_power,
-                identifier,
-            },
-        ))
+        })
     }
 }
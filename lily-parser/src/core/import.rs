@@ -0,0 +1,214 @@
+use lily_lexer::{
+    symbol::Symbol,
+    types::{DelimiterK, IdentifierK, LayoutK, OperatorK, QualifiedK, Token, TokenK},
+};
+
+use crate::{
+    cursor::{expect_token, Cursor},
+    errors::{ParseError, ParseResult},
+    syntax::SyntaxKind,
+    types::{Import, ImportItem, ImportItemK, ImportList, ImportListK, ImportMembers},
+};
+
+impl<'a> Cursor<'a> {
+    /// Whether the next token is a lower identifier that reads as `keyword`,
+    /// for the words that are only reserved within an import.
+    fn peek_contextual(&mut self, keyword: &str) -> ParseResult<bool> {
+        let Token {
+            begin, end, kind, ..
+        } = *self.peek()?;
+        Ok(kind == TokenK::Identifier(IdentifierK::Lower) && &self.source[begin..end] == keyword)
+    }
+
+    fn import_module_name(&mut self) -> ParseResult<(usize, usize)> {
+        let Token { begin, end, .. } = expect_token!(
+            self,
+            TokenK::Identifier(IdentifierK::Upper),
+            TokenK::Qualified(QualifiedK::Upper)
+        );
+        Ok((begin, end))
+    }
+
+    fn import_members(&mut self) -> ParseResult<(usize, ImportMembers)> {
+        expect_token!(self, TokenK::OpenDelimiter(DelimiterK::Round));
+
+        if let Token {
+            begin,
+            end,
+            kind: TokenK::Operator(OperatorK::Source),
+            ..
+        } = *self.peek()?
+        {
+            if &self.source[begin..end] == ".." {
+                self.take()?;
+                let Token { end, .. } =
+                    expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
+                return Ok((end, ImportMembers::All));
+            }
+        }
+
+        let mut members = vec![];
+        loop {
+            if let TokenK::CloseDelimiter(DelimiterK::Round) = self.peek()?.kind {
+                let Token { end, .. } = self.take()?;
+                break Ok((end, ImportMembers::Listed(members)));
+            }
+            if !members.is_empty() {
+                expect_token!(self, TokenK::Operator(OperatorK::Comma));
+            }
+            let Token { begin, end, .. } =
+                expect_token!(self, TokenK::Identifier(IdentifierK::Upper));
+            members.push(Symbol::intern(&self.source[begin..end]));
+        }
+    }
+
+    fn import_item(&mut self) -> ParseResult<ImportItem> {
+        let marker = self.start();
+        let item = self.import_item_kind()?;
+        self.finish(marker, SyntaxKind::ImportItem);
+        Ok(item)
+    }
+
+    fn import_item_kind(&mut self) -> ParseResult<ImportItem> {
+        let token @ Token {
+            begin, end, kind, ..
+        } = self.take()?;
+
+        if let TokenK::Identifier(IdentifierK::Lower) = kind {
+            return Ok(ImportItem {
+                begin,
+                end,
+                kind: ImportItemK::Value(Symbol::intern(&self.source[begin..end])),
+            });
+        }
+
+        if let TokenK::Identifier(IdentifierK::Upper) = kind {
+            let identifier = Symbol::intern(&self.source[begin..end]);
+            if let TokenK::OpenDelimiter(DelimiterK::Round) = self.peek()?.kind {
+                let (end, members) = self.import_members()?;
+                return Ok(ImportItem {
+                    begin,
+                    end,
+                    kind: ImportItemK::Type(identifier, Some(members)),
+                });
+            }
+            return Ok(ImportItem {
+                begin,
+                end,
+                kind: ImportItemK::Type(identifier, None),
+            });
+        }
+
+        if let TokenK::OpenDelimiter(DelimiterK::Round) = kind {
+            let operator = self.take()?;
+            if !matches!(operator.kind, TokenK::Operator(_)) {
+                return Err(ParseError::UnexpectedToken {
+                    token: operator,
+                    expected: vec![TokenK::Operator(OperatorK::Source)],
+                });
+            }
+            let Token { end, .. } = expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
+            return Ok(ImportItem {
+                begin,
+                end,
+                kind: ImportItemK::Operator(Symbol::intern(
+                    &self.source[operator.begin..operator.end],
+                )),
+            });
+        }
+
+        Err(ParseError::UnexpectedToken {
+            token,
+            expected: vec![
+                TokenK::Identifier(IdentifierK::Lower),
+                TokenK::Identifier(IdentifierK::Upper),
+                TokenK::OpenDelimiter(DelimiterK::Round),
+            ],
+        })
+    }
+
+    fn import_list(&mut self) -> ParseResult<ImportList> {
+        let marker = self.start();
+        let Token { begin, .. } = *self.peek()?;
+
+        let hiding = if self.peek_contextual("hiding")? {
+            self.take()?;
+            true
+        } else {
+            false
+        };
+
+        expect_token!(self, TokenK::OpenDelimiter(DelimiterK::Round));
+
+        let mut items = vec![];
+        let end = loop {
+            if let TokenK::CloseDelimiter(DelimiterK::Round) = self.peek()?.kind {
+                break self.take()?.end;
+            }
+            if !items.is_empty() {
+                expect_token!(self, TokenK::Operator(OperatorK::Comma));
+            }
+            items.push(self.import_item()?);
+        };
+        self.finish(marker, SyntaxKind::ImportList);
+
+        Ok(ImportList {
+            begin,
+            end,
+            kind: if hiding {
+                ImportListK::Hiding(items)
+            } else {
+                ImportListK::Only(items)
+            },
+        })
+    }
+
+    pub fn import(&mut self) -> ParseResult<Import> {
+        let marker = self.start();
+        let Token {
+            begin: import_begin,
+            ..
+        } = expect_token!(self, TokenK::Identifier(IdentifierK::Import));
+
+        let (begin, mut import_end) = self.import_module_name()?;
+        let module_name = Symbol::intern(&self.source[begin..import_end]);
+
+        let qualified = if self.peek_contextual("qualified")? {
+            import_end = self.take()?.end;
+            true
+        } else {
+            false
+        };
+
+        let alias = if let TokenK::Identifier(IdentifierK::As) = self.peek()?.kind {
+            self.take()?;
+            let (begin, end) = self.import_module_name()?;
+            import_end = end;
+            Some(Symbol::intern(&self.source[begin..end]))
+        } else {
+            None
+        };
+
+        let list = if self.peek_contextual("hiding")?
+            || self.peek()?.kind == TokenK::OpenDelimiter(DelimiterK::Round)
+        {
+            let list = self.import_list()?;
+            import_end = list.end;
+            Some(list)
+        } else {
+            None
+        };
+
+        expect_token!(self, TokenK::Layout(LayoutK::Separator));
+        self.finish(marker, SyntaxKind::Import);
+
+        Ok(Import {
+            begin: import_begin,
+            end: import_end,
+            module_name,
+            qualified,
+            alias,
+            list,
+        })
+    }
+}
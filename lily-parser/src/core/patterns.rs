@@ -1,17 +1,21 @@
-use anyhow::bail;
-use lily_lexer::types::{DelimiterK, DigitK, IdentifierK, OperatorK, Token, TokenK};
-use smol_str::SmolStr;
+use lily_lexer::{
+    symbol::Symbol,
+    types::{DelimiterK, DigitK, IdentifierK, OperatorK, QualifiedK, Token, TokenK},
+};
 
 use crate::{
     cursor::{expect_token, Cursor},
-    errors::ParseError,
-    types::{GreaterPattern, GreaterPatternK, LesserPattern, LesserPatternK},
+    errors::{ParseError, ParseResult},
+    syntax::SyntaxKind,
+    types::{Fixity, GreaterPattern, GreaterPatternK, LesserPattern, LesserPatternK},
 };
 
 impl<'a> Cursor<'a> {
-    pub fn lesser_pattern(&mut self) -> anyhow::Result<LesserPattern> {
+    pub fn lesser_pattern(&mut self) -> ParseResult<LesserPattern> {
         if let TokenK::Operator(OperatorK::Underscore) = self.peek()?.kind {
+            let marker = self.start();
             let Token { begin, end, .. } = self.take()?;
+            self.finish(marker, SyntaxKind::NullPattern);
             return Ok(LesserPattern {
                 begin,
                 end,
@@ -20,18 +24,23 @@ impl<'a> Cursor<'a> {
         }
 
         if let TokenK::Identifier(IdentifierK::Lower) = self.peek()?.kind {
+            let marker = self.start();
             let Token { begin, end, .. } = self.take()?;
+            self.finish(marker, SyntaxKind::VariablePattern);
             return Ok(LesserPattern {
                 begin,
                 end,
-                kind: LesserPatternK::Variable(SmolStr::new(&self.source[begin..end])),
+                kind: LesserPatternK::Variable(Symbol::intern(&self.source[begin..end])),
             });
         }
 
-        bail!(ParseError::UnexpectedToken(self.peek()?.kind));
+        self.unexpected(&[
+            TokenK::Operator(OperatorK::Underscore),
+            TokenK::Identifier(IdentifierK::Lower),
+        ])
     }
 
-    pub fn lesser_patterns(&mut self) -> anyhow::Result<Vec<LesserPattern>> {
+    pub fn lesser_patterns(&mut self) -> ParseResult<Vec<LesserPattern>> {
         let mut lesser_patterns = vec![];
         loop {
             if let TokenK::Operator(OperatorK::Equal) = self.peek()?.kind {
@@ -43,63 +52,79 @@ impl<'a> Cursor<'a> {
                 continue;
             }
 
-            bail!(ParseError::UnexpectedToken(self.peek()?.kind));
+            break self.unexpected(&[
+                TokenK::Operator(OperatorK::Equal),
+                TokenK::Operator(OperatorK::Underscore),
+                TokenK::Identifier(IdentifierK::Lower),
+            ]);
         }
     }
 }
 
 impl<'a> Cursor<'a> {
-    fn greater_pattern_atom(&mut self) -> anyhow::Result<GreaterPattern> {
-        let Token {
+    fn greater_pattern_atom(&mut self) -> ParseResult<GreaterPattern> {
+        let marker = self.start();
+        let token @ Token {
             begin, end, kind, ..
         } = self.take()?;
+        let text = &self.source[begin..end];
+
+        let (kind, syntax_kind) = match kind {
+            TokenK::Digit(DigitK::Int) => (
+                GreaterPatternK::Integer(text.into()),
+                SyntaxKind::IntegerPattern,
+            ),
+            TokenK::Identifier(IdentifierK::Lower) => (
+                GreaterPatternK::Variable(Symbol::intern(text)),
+                SyntaxKind::VariablePattern,
+            ),
+            TokenK::Identifier(IdentifierK::Upper) | TokenK::Qualified(QualifiedK::Upper) => (
+                GreaterPatternK::Constructor(Symbol::intern(text)),
+                SyntaxKind::ConstructorPattern,
+            ),
+            TokenK::Operator(OperatorK::Underscore) => {
+                (GreaterPatternK::Null, SyntaxKind::NullPattern)
+            }
+            TokenK::OpenDelimiter(DelimiterK::Round) => {
+                let greater_pattern = self.greater_pattern_core(0, None)?;
+                let Token { end, .. } =
+                    expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
+                self.finish(marker, SyntaxKind::ParenthesizedPattern);
+                return Ok(GreaterPattern {
+                    begin,
+                    end,
+                    kind: GreaterPatternK::Parenthesized(Box::new(greater_pattern)),
+                });
+            }
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    token,
+                    expected: vec![
+                        TokenK::Digit(DigitK::Int),
+                        TokenK::Identifier(IdentifierK::Lower),
+                        TokenK::Identifier(IdentifierK::Upper),
+                        TokenK::Operator(OperatorK::Underscore),
+                        TokenK::OpenDelimiter(DelimiterK::Round),
+                    ],
+                })
+            }
+        };
+        self.finish(marker, syntax_kind);
 
-        if let TokenK::Digit(DigitK::Int) = kind {
-            return Ok(GreaterPattern {
-                begin,
-                end,
-                kind: GreaterPatternK::Integer(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Identifier(IdentifierK::Lower) = kind {
-            return Ok(GreaterPattern {
-                begin,
-                end,
-                kind: GreaterPatternK::Variable(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Identifier(IdentifierK::Upper) = kind {
-            return Ok(GreaterPattern {
-                begin,
-                end,
-                kind: GreaterPatternK::Constructor(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Operator(OperatorK::Underscore) = kind {
-            return Ok(GreaterPattern {
-                begin,
-                end,
-                kind: GreaterPatternK::Null,
-            });
-        }
-
-        if let TokenK::OpenDelimiter(DelimiterK::Round) = kind {
-            let greater_pattern = self.greater_pattern_core(0)?;
-            let Token { end, .. } = expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
-            return Ok(GreaterPattern {
-                begin,
-                end,
-                kind: GreaterPatternK::Parenthesized(Box::new(greater_pattern)),
-            });
-        }
-
-        bail!(ParseError::UnexpectedToken(kind));
+        Ok(GreaterPattern { begin, end, kind })
     }
 
-    fn greater_pattern_core(&mut self, minimum_power: u8) -> anyhow::Result<GreaterPattern> {
+    fn greater_pattern_core(
+        &mut self,
+        minimum_power: u8,
+        parent: Option<(Token, &'a Fixity)>,
+    ) -> ParseResult<GreaterPattern> {
+        let mut previous = parent;
+
+        // The node of the accumulator, and whether it is an application
+        // that later arguments still go into.
+        let mut marker = self.checkpoint();
+        let mut is_application = false;
         let mut accumulator = self.greater_pattern_atom()?;
 
         loop {
@@ -107,25 +132,32 @@ impl<'a> Cursor<'a> {
                 break;
             }
 
-            if let Token {
+            if let token @ Token {
                 begin,
                 end,
-                kind: TokenK::Operator(OperatorK::Source),
+                kind: TokenK::Operator(OperatorK::Source) | TokenK::Qualified(QualifiedK::Operator),
                 ..
-            } = self.peek()?
+            } = *self.peek()?
             {
-                let source_range = *begin..*end;
-                let operator = SmolStr::new(&self.source[source_range]);
+                let operator = Symbol::intern(&self.source[begin..end]);
 
-                let (left_power, right_power) = self.get_fixity(&operator)?;
+                let fixity = self.get_fixity(&token, operator)?;
+                let (left_power, right_power) = fixity.as_pair();
 
                 if left_power < minimum_power {
                     break;
                 } else {
+                    self.check_associativity(previous, (token, fixity))?;
+                    if std::mem::take(&mut is_application) {
+                        self.finish(marker, SyntaxKind::ApplicationPattern);
+                    }
+                    marker = self.precede(marker);
                     self.take()?;
+                    previous = Some((token, fixity));
                 }
 
-                let argument = self.greater_pattern_core(right_power)?;
+                let argument = self.greater_pattern_core(right_power, previous)?;
+                self.finish(marker, SyntaxKind::BinaryOperatorPattern);
                 accumulator = GreaterPattern {
                     begin: accumulator.begin,
                     end: argument.end,
@@ -138,6 +170,10 @@ impl<'a> Cursor<'a> {
                 continue;
             }
 
+            if !is_application {
+                marker = self.precede(marker);
+                is_application = true;
+            }
             let argument = self.greater_pattern_atom()?;
             match &mut accumulator.kind {
                 GreaterPatternK::Application(_, arguments) => {
@@ -154,10 +190,13 @@ impl<'a> Cursor<'a> {
             }
         }
 
+        if is_application {
+            self.finish(marker, SyntaxKind::ApplicationPattern);
+        }
         Ok(accumulator)
     }
 
-    pub fn greater_patterns(&mut self) -> anyhow::Result<Vec<GreaterPattern>> {
+    pub fn greater_patterns(&mut self) -> ParseResult<Vec<GreaterPattern>> {
         let mut greater_patterns = vec![];
 
         loop {
@@ -172,7 +211,7 @@ impl<'a> Cursor<'a> {
                 break;
             }
 
-            greater_patterns.push(self.greater_pattern_core(0)?);
+            greater_patterns.push(self.greater_pattern_core(0, None)?);
         }
 
         Ok(greater_patterns)
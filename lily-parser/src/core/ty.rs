@@ -1,50 +1,69 @@
-use anyhow::bail;
-use lily_lexer::types::{DelimiterK, IdentifierK, OperatorK, Token, TokenK};
-use smol_str::SmolStr;
+use lily_lexer::{
+    symbol::Symbol,
+    types::{DelimiterK, IdentifierK, OperatorK, QualifiedK, Token, TokenK},
+};
 
 use crate::{
     cursor::Cursor,
-    errors::ParseError,
+    errors::{ParseError, ParseResult},
     expect_token,
-    types::{Ty, TyK},
+    syntax::SyntaxKind,
+    types::{Fixity, Ty, TyK},
 };
 
 impl<'a> Cursor<'a> {
-    fn ty_atom(&mut self) -> anyhow::Result<Ty> {
+    fn ty_atom(&mut self) -> ParseResult<Ty> {
+        let marker = self.start();
         let token @ Token {
             begin, end, kind, ..
         } = self.take()?;
+        let text = Symbol::intern(&self.source[begin..end]);
 
-        if let TokenK::Identifier(IdentifierK::Upper) = kind {
-            return Ok(Ty {
-                begin,
-                end,
-                kind: TyK::Constructor(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::Identifier(IdentifierK::Lower) = kind {
-            return Ok(Ty {
-                begin,
-                end,
-                kind: TyK::Variable(SmolStr::new(&self.source[begin..end])),
-            });
-        }
-
-        if let TokenK::OpenDelimiter(DelimiterK::Round) = kind {
-            let ty = self.ty_core(0)?;
-            let Token { end, .. } = expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
-            return Ok(Ty {
-                begin,
-                end,
-                kind: TyK::Parenthesized(Box::new(ty)),
-            });
-        }
+        let (kind, syntax_kind) = match kind {
+            TokenK::Identifier(IdentifierK::Upper) | TokenK::Qualified(QualifiedK::Upper) => {
+                (TyK::Constructor(text), SyntaxKind::ConstructorType)
+            }
+            TokenK::Identifier(IdentifierK::Lower) => {
+                (TyK::Variable(text), SyntaxKind::VariableType)
+            }
+            TokenK::OpenDelimiter(DelimiterK::Round) => {
+                let ty = self.ty_core(0, None)?;
+                let Token { end, .. } =
+                    expect_token!(self, TokenK::CloseDelimiter(DelimiterK::Round));
+                self.finish(marker, SyntaxKind::ParenthesizedType);
+                return Ok(Ty {
+                    begin,
+                    end,
+                    kind: TyK::Parenthesized(Box::new(ty)),
+                });
+            }
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    token,
+                    expected: vec![
+                        TokenK::Identifier(IdentifierK::Upper),
+                        TokenK::Identifier(IdentifierK::Lower),
+                        TokenK::OpenDelimiter(DelimiterK::Round),
+                    ],
+                })
+            }
+        };
+        self.finish(marker, syntax_kind);
 
-        bail!(ParseError::UnexpectedToken(token.kind));
+        Ok(Ty { begin, end, kind })
     }
 
-    fn ty_core(&mut self, minimum_power: u8) -> anyhow::Result<Ty> {
+    fn ty_core(
+        &mut self,
+        minimum_power: u8,
+        parent: Option<(Token, &'a Fixity)>,
+    ) -> ParseResult<Ty> {
+        let mut previous = parent;
+
+        // The node of the accumulator, and whether it is an application
+        // that later arguments still go into.
+        let mut marker = self.checkpoint();
+        let mut is_application = false;
         let mut accumulator = self.ty_atom()?;
 
         loop {
@@ -52,25 +71,34 @@ impl<'a> Cursor<'a> {
                 break;
             }
 
-            if let Token {
+            if let token @ Token {
                 begin,
                 end,
-                kind: TokenK::Operator(OperatorK::ArrowRight | OperatorK::Source),
+                kind:
+                    TokenK::Operator(OperatorK::ArrowRight | OperatorK::Source)
+                    | TokenK::Qualified(QualifiedK::Operator),
                 ..
-            } = self.peek()?
+            } = *self.peek()?
             {
-                let source_range = *begin..*end;
-                let operator = SmolStr::new(&self.source[source_range]);
+                let operator = Symbol::intern(&self.source[begin..end]);
 
-                let (left_power, right_power) = self.get_type_fixity(&operator)?;
+                let fixity = self.get_type_fixity(&token, operator)?;
+                let (left_power, right_power) = fixity.as_pair();
 
                 if left_power < minimum_power {
                     break;
                 } else {
+                    self.check_associativity(previous, (token, fixity))?;
+                    if std::mem::take(&mut is_application) {
+                        self.finish(marker, SyntaxKind::ApplicationType);
+                    }
+                    marker = self.precede(marker);
                     self.take()?;
+                    previous = Some((token, fixity));
                 }
 
-                let argument = self.ty_core(right_power)?;
+                let argument = self.ty_core(right_power, previous)?;
+                self.finish(marker, SyntaxKind::BinaryOperatorType);
                 accumulator = Ty {
                     begin: accumulator.begin,
                     end: argument.end,
@@ -79,6 +107,10 @@ impl<'a> Cursor<'a> {
                 continue;
             }
 
+            if !is_application {
+                marker = self.precede(marker);
+                is_application = true;
+            }
             let argument = self.ty_atom()?;
             match &mut accumulator.kind {
                 TyK::Application(_, arguments) => {
@@ -88,17 +120,20 @@ impl<'a> Cursor<'a> {
                 _ => {
                     accumulator = Ty {
                         begin: accumulator.begin,
-                        end: accumulator.end,
+                        end: argument.end,
                         kind: TyK::Application(Box::new(accumulator), vec![argument]),
                     }
                 }
             }
         }
 
+        if is_application {
+            self.finish(marker, SyntaxKind::ApplicationType);
+        }
         Ok(accumulator)
     }
 
-    pub fn ty(&mut self) -> anyhow::Result<Ty> {
-        self.ty_core(0)
+    pub fn ty(&mut self) -> ParseResult<Ty> {
+        self.ty_core(0, None)
     }
 }
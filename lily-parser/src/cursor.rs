@@ -1,15 +1,62 @@
-use anyhow::{bail, Context};
-use lily_lexer::types::Token;
+use crate::{
+    docs::doc_comment,
+    errors::{OperatorChain, ParseError, ParseResult},
+    syntax::{Event, SyntaxKind},
+    types::{Associativity, Fixity, FixityMap},
+};
+use lily_lexer::{
+    symbol::Symbol,
+    types::{Token, TokenK},
+};
 use smol_str::SmolStr;
 
-use crate::{errors::ParseError, types::FixityMap};
+/// The tokens that a cursor reads from.
+enum Tokens<'a> {
+    Slice(&'a [Token]),
+    /// Tokens are pulled from the stream as the parser looks ahead, and kept
+    /// around so that `attempt` can backtrack over them.
+    Stream {
+        buffer: Vec<Token>,
+        stream: Box<dyn Iterator<Item = Token> + 'a>,
+    },
+}
+
+impl<'a> Tokens<'a> {
+    fn get(&mut self, index: usize) -> Option<&Token> {
+        match self {
+            Tokens::Slice(tokens) => tokens.get(index),
+            Tokens::Stream { buffer, stream } => {
+                while buffer.len() <= index {
+                    buffer.push(stream.next()?);
+                }
+                buffer.get(index)
+            }
+        }
+    }
+
+    fn last(&self) -> Option<&Token> {
+        match self {
+            Tokens::Slice(tokens) => tokens.last(),
+            Tokens::Stream { buffer, .. } => buffer.last(),
+        }
+    }
+}
+
+/// The position of a `Start` event, which refers to a syntax node.
+#[derive(Debug, Clone, Copy)]
+pub struct Marker(usize);
 
 pub struct Cursor<'a> {
     pub source: &'a str,
-    tokens: &'a [Token],
+    tokens: Tokens<'a>,
     index: usize,
     value_fixities: Option<&'a FixityMap>,
     type_fixities: Option<&'a FixityMap>,
+    /// Events for the syntax tree, which are only recorded on request.
+    events: Option<Vec<Event>>,
+    /// The nodes that were given a parent by `precede`, in order, such that
+    /// `attempt` can undo it.
+    preceded: Vec<usize>,
 }
 
 impl<'a> Cursor<'a> {
@@ -21,75 +68,236 @@ impl<'a> Cursor<'a> {
     ) -> Self {
         Self {
             source,
-            tokens,
+            tokens: Tokens::Slice(tokens),
             index: 0,
             value_fixities,
             type_fixities,
+            events: None,
+            preceded: vec![],
         }
     }
 
-    pub fn peek(&mut self) -> anyhow::Result<&Token> {
-        if self.is_eof() {
-            bail!(ParseError::UnexpectedEndOfFile);
+    /// Creates a cursor that reads tokens from an iterator as it needs them,
+    /// such as a `Lexer`, rather than from a slice lexed up front.
+    pub fn from_stream(
+        source: &'a str,
+        tokens: impl Iterator<Item = Token> + 'a,
+        value_fixities: Option<&'a FixityMap>,
+        type_fixities: Option<&'a FixityMap>,
+    ) -> Self {
+        Self {
+            source,
+            tokens: Tokens::Stream {
+                buffer: vec![],
+                stream: Box::new(tokens),
+            },
+            index: 0,
+            value_fixities,
+            type_fixities,
+            events: None,
+            preceded: vec![],
+        }
+    }
+
+    /// Records the events of the syntax tree while parsing.
+    pub fn with_events(mut self) -> Self {
+        self.events = Some(vec![]);
+        self
+    }
+
+    /// The events recorded so far, leaving none behind.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// The marker of the node that the next call to `start` creates, for
+    /// parsers that may have to `precede` a node that they did not start.
+    pub fn checkpoint(&self) -> Marker {
+        Marker(self.events.as_ref().map_or(0, Vec::len))
+    }
+
+    /// Starts a syntax node before the next token.
+    pub fn start(&mut self) -> Marker {
+        let marker = self.checkpoint();
+        if let Some(events) = &mut self.events {
+            events.push(Event::Start {
+                kind: None,
+                forward_parent: None,
+            });
+        }
+        marker
+    }
+
+    /// Finishes the node at `marker` after the last token that was taken.
+    pub fn finish(&mut self, marker: Marker, kind: SyntaxKind) {
+        if let Some(events) = &mut self.events {
+            if let Event::Start { kind: slot, .. } = &mut events[marker.0] {
+                *slot = Some(kind);
+            }
+            events.push(Event::Finish);
+        }
+    }
+
+    /// Starts a node that encloses the finished node at `marker`, along with
+    /// whatever is parsed until it is finished.
+    pub fn precede(&mut self, marker: Marker) -> Marker {
+        let parent = self.start();
+        if let Some(events) = &mut self.events {
+            if let Event::Start { forward_parent, .. } = &mut events[marker.0] {
+                *forward_parent = Some(parent.0 - marker.0);
+            }
+            self.preceded.push(marker.0);
         }
-        Ok(&self.tokens[self.index])
+        parent
+    }
+
+    fn end_of_file(&self) -> ParseError {
+        let offset = self.tokens.last().map_or(0, |token| token.end);
+        ParseError::UnexpectedEndOfFile { offset }
     }
 
-    pub fn take(&mut self) -> anyhow::Result<Token> {
+    pub fn peek(&mut self) -> ParseResult<&Token> {
         if self.is_eof() {
-            bail!(ParseError::UnexpectedEndOfFile);
+            return Err(self.end_of_file());
         }
-        let token = self.tokens[self.index];
+        Ok(self
+            .tokens
+            .get(self.index)
+            .expect("the cursor is not at the end"))
+    }
+
+    pub fn take(&mut self) -> ParseResult<Token> {
+        let token = *self.peek()?;
         self.index += 1;
+        if let Some(events) = &mut self.events {
+            events.push(Event::Token(token));
+        }
         Ok(token)
     }
 
     pub fn is_eof(&mut self) -> bool {
-        self.index == self.tokens.len()
+        self.tokens.get(self.index).is_none()
+    }
+
+    /// Fails on the next token, without consuming it.
+    pub fn unexpected<T>(&mut self, expected: &[TokenK]) -> ParseResult<T> {
+        let token = *self.peek()?;
+        Err(ParseError::UnexpectedToken {
+            token,
+            expected: expected.to_vec(),
+        })
     }
 
     pub fn attempt<T>(
         &mut self,
-        callback: impl FnOnce(&mut Self) -> anyhow::Result<T>,
-    ) -> anyhow::Result<T> {
+        callback: impl FnOnce(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<T> {
         let index = self.index;
+        let Marker(events) = self.checkpoint();
+        let preceded = self.preceded.len();
         match callback(self) {
             Ok(ok) => Ok(ok),
             Err(err) => {
                 self.index = index;
+                if let Some(recorded) = &mut self.events {
+                    recorded.truncate(events);
+                    for node in self.preceded.drain(preceded..) {
+                        if let Some(Event::Start { forward_parent, .. }) = recorded.get_mut(node) {
+                            *forward_parent = None;
+                        }
+                    }
+                }
                 Err(err)
             }
         }
     }
 
-    pub fn get_fixity(&self, operator: &SmolStr) -> anyhow::Result<(u8, u8)> {
-        Ok(self
-            .value_fixities
-            .context(ParseError::UnknownBindingPower(operator.clone()))?
-            .get(operator)
-            .context(ParseError::UnknownBindingPower(operator.clone()))?
-            .as_pair())
+    /// The doc comment attached to a token, if any.
+    pub fn docs(&self, token: &Token) -> Option<SmolStr> {
+        doc_comment(&self.source[token.comment_begin..token.comment_end])
     }
 
-    pub fn get_type_fixity(&self, operator: &SmolStr) -> anyhow::Result<(u8, u8)> {
-        Ok(self
-            .type_fixities
-            .context(ParseError::UnknownBindingPower(operator.clone()))?
-            .get(operator)
-            .context(ParseError::UnknownBindingPower(operator.clone()))?
-            .as_pair())
+    /// Runs a parser that is expected to consume every remaining token.
+    pub fn complete<T>(
+        &mut self,
+        callback: impl FnOnce(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<T> {
+        let value = callback(self)?;
+        if !self.is_eof() {
+            return self.unexpected(&[]);
+        }
+        Ok(value)
+    }
+
+    /// Qualified operators share the fixity of their unqualified name, which
+    /// is the only case that interns anything beyond the operator itself.
+    fn fixity_key(&self, operator: &Token, symbol: Symbol) -> Symbol {
+        match operator.split_qualified(self.source) {
+            Some((_, name)) => Symbol::intern(name),
+            None => symbol,
+        }
+    }
+
+    fn lookup_fixity(
+        &self,
+        fixities: Option<&'a FixityMap>,
+        operator: &Token,
+        symbol: Symbol,
+    ) -> ParseResult<&'a Fixity> {
+        let key = self.fixity_key(operator, symbol);
+        fixities
+            .and_then(|fixities| fixities.get(&key))
+            .ok_or_else(|| ParseError::UnknownBindingPower {
+                operator: key.as_str().into(),
+                token: *operator,
+            })
+    }
+
+    /// Finds the fixity of an operator token, given its interned text.
+    pub fn get_fixity(&self, operator: &Token, symbol: Symbol) -> ParseResult<&'a Fixity> {
+        self.lookup_fixity(self.value_fixities, operator, symbol)
+    }
+
+    pub fn get_type_fixity(&self, operator: &Token, symbol: Symbol) -> ParseResult<&'a Fixity> {
+        self.lookup_fixity(self.type_fixities, operator, symbol)
+    }
+
+    /// Rejects operator chains such as `a == b == c`, where an operator
+    /// follows a non-associative operator of the same binding power.
+    pub fn check_associativity(
+        &self,
+        previous: Option<(Token, &Fixity)>,
+        current: (Token, &Fixity),
+    ) -> ParseResult<()> {
+        if let Some((previous_token, previous_fixity)) = previous {
+            let (current_token, current_fixity) = current;
+            let is_non_associative = previous_fixity.associativity == Associativity::Infix
+                || current_fixity.associativity == Associativity::Infix;
+            if is_non_associative && previous_fixity.binding_power == current_fixity.binding_power {
+                return Err(ParseError::NonAssociativeChain(Box::new(OperatorChain {
+                    first_operator: self.source[previous_token.begin..previous_token.end].into(),
+                    first: previous_token,
+                    second_operator: self.source[current_token.begin..current_token.end].into(),
+                    second: current_token,
+                })));
+            }
+        }
+        Ok(())
     }
 }
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! expect_token {
-    ($self:ident, $kind:pat) => {{
+    ($self:ident, $($kind:expr),+ $(,)?) => {{
         let token = $self.take()?;
-        if matches!(token.kind, $kind) {
+        if [$($kind),+].contains(&token.kind) {
             token
         } else {
-            anyhow::bail!(ParseError::UnexpectedToken(token.kind));
+            return Err(ParseError::UnexpectedToken {
+                token,
+                expected: vec![$($kind),+],
+            });
         }
     }};
 }
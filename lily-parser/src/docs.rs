@@ -0,0 +1,81 @@
+use smol_str::SmolStr;
+
+/// Extracts the doc comment from the trivia that precedes a token.
+///
+/// A doc comment is either a `{-| -}` block or a run of line comments that
+/// starts with `-- |`. Only the comment closest to the token is considered,
+/// so an ordinary comment in between detaches the documentation.
+pub(crate) fn doc_comment(trivia: &str) -> Option<SmolStr> {
+    let mut docs: Option<Vec<&str>> = None;
+    let mut in_line_docs = false;
+    let mut rest = trivia;
+
+    loop {
+        let trimmed = rest.trim_start();
+        if rest[..rest.len() - trimmed.len()].matches('\n').count() > 1 {
+            in_line_docs = false;
+        }
+        rest = trimmed;
+
+        if let Some(comment) = rest.strip_prefix("--") {
+            let line_end = comment.find('\n').unwrap_or(comment.len());
+            let line = &comment[..line_end];
+            rest = &comment[line_end..];
+            if let Some(line) = line.trim_start().strip_prefix('|') {
+                docs = Some(vec![line]);
+                in_line_docs = true;
+            } else if in_line_docs {
+                if let Some(docs) = &mut docs {
+                    docs.push(line);
+                }
+            } else {
+                docs = None;
+            }
+        } else if rest.starts_with("{-") {
+            let length = block_comment_length(rest);
+            let comment = &rest[2..length];
+            rest = &rest[length..];
+            docs = comment
+                .strip_prefix('|')
+                .map(|comment| vec![comment.strip_suffix("-}").unwrap_or(comment)]);
+            in_line_docs = false;
+        } else {
+            break;
+        }
+    }
+
+    let docs = docs?;
+    let docs: Vec<_> = docs
+        .iter()
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect();
+    let docs = docs.join("\n");
+    let docs = docs.trim();
+    if docs.is_empty() {
+        None
+    } else {
+        Some(SmolStr::new(docs))
+    }
+}
+
+/// The length of the (possibly nested) block comment at the start of `source`.
+fn block_comment_length(source: &str) -> usize {
+    let mut depth = 0;
+    let mut offset = 0;
+    while offset < source.len() {
+        let rest = &source[offset..];
+        if rest.starts_with("{-") {
+            depth += 1;
+            offset += 2;
+        } else if rest.starts_with("-}") {
+            depth -= 1;
+            offset += 2;
+            if depth == 0 {
+                break;
+            }
+        } else {
+            offset += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    offset
+}
@@ -1,15 +1,280 @@
-use lily_lexer::types::TokenK;
+use lily_lexer::{
+    diagnostics::Diagnostic,
+    errors::LexError,
+    types::{Token, TokenK},
+};
 use smol_str::SmolStr;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+use crate::types::Fixity;
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// Two operators that cannot appear next to each other without parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperatorChain {
+    pub first_operator: SmolStr,
+    pub first: Token,
+    pub second_operator: SmolStr,
+    pub second: Token,
+}
+
+fn list_expected(expected: &[TokenK]) -> String {
+    match expected {
+        [] => String::new(),
+        [kind] => kind.to_string(),
+        [kinds @ .., last] => {
+            let kinds: Vec<_> = kinds.iter().map(TokenK::to_string).collect();
+            format!("{} or {}", kinds.join(", "), last)
+        }
+    }
+}
+
+fn describe_expected(expected: &[TokenK]) -> String {
+    if expected.is_empty() {
+        String::new()
+    } else {
+        format!(", expected {}", list_expected(expected))
+    }
+}
+
+/// The limit on binding powers, shared by the parser and fixity validation.
+fn binding_power_range() -> String {
+    format!("must be between 0 and {}", Fixity::MAX_BINDING_POWER)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParseError {
-    #[error("Unexpected end of file.")]
-    UnexpectedEndOfFile,
-    #[error("Unexpected token {0:?}.")]
-    UnexpectedToken(TokenK),
-    #[error("Unknown binding power for operator {0:?}.")]
-    UnknownBindingPower(SmolStr),
+    #[error("Unexpected end of file at offset {offset}.")]
+    UnexpectedEndOfFile { offset: usize },
+    #[error(
+        "Unexpected {} at {}..{}{}.",
+        token.kind,
+        token.begin,
+        token.end,
+        describe_expected(expected)
+    )]
+    UnexpectedToken { token: Token, expected: Vec<TokenK> },
+    #[error(
+        "Binding power at {}..{} {}.",
+        token.begin,
+        token.end,
+        binding_power_range()
+    )]
+    InvalidBindingPower { token: Token },
+    #[error(
+        "Import declaration at {}..{} must come before all other declarations.",
+        token.begin,
+        token.end
+    )]
+    MisplacedImport { token: Token },
+    #[error(
+        "Unknown binding power for operator {operator:?} at {}..{}.",
+        token.begin,
+        token.end
+    )]
+    UnknownBindingPower { operator: SmolStr, token: Token },
+    #[error(
+        "Cannot chain {:?} at {}..{} with {:?} at {}..{}; non-associative operators of equal precedence must be parenthesized.",
+        .0.first_operator,
+        .0.first.begin,
+        .0.first.end,
+        .0.second_operator,
+        .0.second.begin,
+        .0.second.end
+    )]
+    NonAssociativeChain(Box<OperatorChain>),
+    #[error(transparent)]
+    Lexical(#[from] LexError),
     #[error("Internal error: {0}. This incident should be reported!")]
     InternalError(String),
 }
+
+impl ParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ParseError::UnexpectedEndOfFile { offset } => {
+                Diagnostic::error("unexpected end of input").with_primary(*offset, *offset, "")
+            }
+            ParseError::UnexpectedToken { token, expected } => {
+                let label = if expected.is_empty() {
+                    String::new()
+                } else {
+                    format!("expected {}", list_expected(expected))
+                };
+                Diagnostic::error(format!("unexpected {}", token.kind)).with_primary(
+                    token.begin,
+                    token.end,
+                    label,
+                )
+            }
+            ParseError::InvalidBindingPower { token } => Diagnostic::error("invalid binding power")
+                .with_primary(token.begin, token.end, binding_power_range()),
+            ParseError::MisplacedImport { token } => Diagnostic::error("misplaced import")
+                .with_primary(token.begin, token.end, "")
+                .with_note("imports must come before all other declarations"),
+            ParseError::UnknownBindingPower { operator, token } => Diagnostic::error(format!(
+                "unknown binding power for `{}`",
+                operator
+            ))
+            .with_primary(token.begin, token.end, "no fixity declaration in scope"),
+            ParseError::NonAssociativeChain(chain) => Diagnostic::error(format!(
+                "cannot chain `{}` with `{}`",
+                chain.first_operator, chain.second_operator
+            ))
+            .with_secondary(chain.first.begin, chain.first.end, "")
+            .with_primary(chain.second.begin, chain.second.end, "")
+            .with_note("non-associative operators of equal precedence must be parenthesized"),
+            ParseError::Lexical(error) => error.to_diagnostic(),
+            ParseError::InternalError(_) => Diagnostic::error(self.to_string()),
+        }
+    }
+
+    /// The source range that the error points at, if any.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::UnexpectedEndOfFile { offset } => Some((*offset, *offset)),
+            ParseError::UnexpectedToken { token, .. }
+            | ParseError::InvalidBindingPower { token }
+            | ParseError::MisplacedImport { token }
+            | ParseError::UnknownBindingPower { token, .. } => Some((token.begin, token.end)),
+            ParseError::NonAssociativeChain(chain) => Some((chain.first.begin, chain.second.end)),
+            ParseError::Lexical(error) => Some((error.begin, error.end)),
+            ParseError::InternalError(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FixityError {
+    #[error("Duplicate fixity for {operator:?} at {begin}..{end}, first declared at {first_begin}..{first_end}.")]
+    Duplicate {
+        operator: SmolStr,
+        begin: usize,
+        end: usize,
+        first_begin: usize,
+        first_end: usize,
+    },
+    #[error(
+        "Fixity for {operator:?} at {begin}..{end} refers to unknown identifier {identifier:?}."
+    )]
+    UnknownTarget {
+        operator: SmolStr,
+        identifier: SmolStr,
+        begin: usize,
+        end: usize,
+    },
+    #[error(
+        "Binding power {binding_power} for {operator:?} at {begin}..{end} {}.",
+        binding_power_range()
+    )]
+    BindingPowerOverflow {
+        operator: SmolStr,
+        binding_power: u8,
+        begin: usize,
+        end: usize,
+    },
+    #[error("Fixity for {operator:?} at {begin}..{end} shadows a built-in operator.")]
+    ShadowsBuiltin {
+        operator: SmolStr,
+        begin: usize,
+        end: usize,
+    },
+}
+
+impl FixityError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            FixityError::Duplicate {
+                operator,
+                begin,
+                end,
+                first_begin,
+                first_end,
+            } => Diagnostic::error(format!("duplicate fixity for `{}`", operator))
+                .with_primary(*begin, *end, "")
+                .with_secondary(*first_begin, *first_end, "first declared here"),
+            FixityError::UnknownTarget {
+                operator,
+                identifier,
+                begin,
+                end,
+            } => Diagnostic::error(format!(
+                "fixity for `{}` refers to unknown identifier `{}`",
+                operator, identifier
+            ))
+            .with_primary(*begin, *end, ""),
+            FixityError::BindingPowerOverflow {
+                binding_power,
+                begin,
+                end,
+                ..
+            } => Diagnostic::error(format!("invalid binding power {}", binding_power))
+                .with_primary(*begin, *end, binding_power_range()),
+            FixityError::ShadowsBuiltin {
+                operator,
+                begin,
+                end,
+            } => Diagnostic::error(format!(
+                "fixity for `{}` shadows a built-in operator",
+                operator
+            ))
+            .with_primary(*begin, *end, ""),
+        }
+    }
+}
+
+/// A structural invariant that a module does not uphold, found by
+/// [`validate`](crate::validate::validate). The path names the offending
+/// node, such as `declarations[0].expression.arguments[1]`.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum InvariantError {
+    #[error("Span {begin}..{end} of `{path}` is not a range of the source.")]
+    InvalidSpan {
+        path: String,
+        begin: usize,
+        end: usize,
+    },
+    #[error("Span {begin}..{end} of `{path}` lies outside of its parent at {parent_begin}..{parent_end}.")]
+    OutsideParent {
+        path: String,
+        begin: usize,
+        end: usize,
+        parent_begin: usize,
+        parent_end: usize,
+    },
+    #[error("Span {begin}..{end} of `{path}` overlaps with the node before it, which ends at {previous_end}.")]
+    OverlapsSibling {
+        path: String,
+        begin: usize,
+        end: usize,
+        previous_end: usize,
+    },
+    #[error("Application at {begin}..{end} of `{path}` has no arguments.")]
+    EmptyApplication {
+        path: String,
+        begin: usize,
+        end: usize,
+    },
+    #[error("Name {name:?} of `{path}` does not match the source at {begin}..{end}.")]
+    TextMismatch {
+        path: String,
+        name: SmolStr,
+        begin: usize,
+        end: usize,
+    },
+}
+
+impl InvariantError {
+    pub fn path(&self) -> &str {
+        match self {
+            InvariantError::InvalidSpan { path, .. }
+            | InvariantError::OutsideParent { path, .. }
+            | InvariantError::OverlapsSibling { path, .. }
+            | InvariantError::EmptyApplication { path, .. }
+            | InvariantError::TextMismatch { path, .. } => path,
+        }
+    }
+}
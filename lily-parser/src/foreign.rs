@@ -0,0 +1,53 @@
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::types::{Declaration, DeclarationK, Module};
+
+/// Maps the names of `foreign import` values to their host implementations.
+///
+/// The registry is generic over the implementation type so that each backend
+/// or interpreter can supply its own calling convention.
+#[derive(Debug)]
+pub struct ForeignRegistry<F> {
+    implementations: FxHashMap<SmolStr, F>,
+}
+
+impl<F> Default for ForeignRegistry<F> {
+    fn default() -> Self {
+        Self {
+            implementations: FxHashMap::default(),
+        }
+    }
+}
+
+impl<F> ForeignRegistry<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an implementation, returning the one it replaced, if any.
+    pub fn register(&mut self, name: impl Into<SmolStr>, implementation: F) -> Option<F> {
+        self.implementations.insert(name.into(), implementation)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&F> {
+        self.implementations.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.implementations.contains_key(name)
+    }
+
+    /// Returns the foreign value declarations in a module that have no
+    /// registered implementation.
+    pub fn unimplemented<'m>(&self, module: &'m Module) -> Vec<&'m Declaration> {
+        module
+            .declarations
+            .iter()
+            .filter(|declaration| match &declaration.kind {
+                DeclarationK::ForeignValue(name, _) => !self.contains(name.as_str()),
+                _ => false,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,292 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use lily_lexer::{
+    errors::LexError,
+    indentation::{indentation_errors, Tabs},
+    types::{Token, TokenK},
+};
+
+use crate::{
+    cursor::Cursor,
+    docs,
+    errors::ParseError,
+    syntax::{self, Event},
+    types::{Declaration, DeclarationK, Domain, Fixity, FixityMap, Import, Module},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GroupK {
+    Import,
+    Fixity,
+    Declaration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TopLevel {
+    Import(Import),
+    Fixity(Fixity),
+    Declaration(Declaration),
+}
+
+/// A top-level group of tokens, along with the result of parsing it.
+#[derive(Debug, Clone)]
+pub(crate) struct Group {
+    /// The range of the group within the token stream.
+    pub(crate) begin: usize,
+    pub(crate) end: usize,
+    pub(crate) kind: GroupK,
+    pub(crate) lex_errors: Vec<LexError>,
+    /// `None` until the group is parsed. Groups with lexical errors never
+    /// report a parse error, since it would be a consequence of them.
+    pub(crate) parsed: Option<(TopLevel, Option<ParseError>)>,
+    /// The events of the group's syntax tree, if they were asked for.
+    pub(crate) events: Vec<Event>,
+}
+
+/// Splits `tokens[begin..end]` into unparsed top-level groups, which were
+/// lexed with the given `tabs`.
+///
+/// Finding the groups is a single pass over the tokens, but the lexical
+/// errors of each group are collected in parallel when the `rayon` feature
+/// is enabled.
+pub(crate) fn groups(
+    source: &str,
+    tokens: &[Token],
+    begin: usize,
+    end: usize,
+    tabs: Tabs,
+) -> Vec<Group> {
+    let ranges: Vec<_> = partition(&tokens[begin..end])
+        .map(|(group_begin, group_end)| (begin + group_begin, begin + group_end))
+        .collect();
+    let group = |(group_begin, group_end): (usize, usize)| {
+        let group = &tokens[group_begin..group_end];
+        let first = &group[0];
+        let kind = if first.is_import_identifier() {
+            GroupK::Import
+        } else if first.is_infix_identifier() {
+            GroupK::Fixity
+        } else {
+            GroupK::Declaration
+        };
+        let lex_errors = group
+            .iter()
+            .filter_map(|token| LexError::from_token(source, token))
+            .chain(indentation_errors(source, group, tabs))
+            .collect();
+        Group {
+            begin: group_begin,
+            end: group_end,
+            kind,
+            lex_errors,
+            parsed: None,
+            events: vec![],
+        }
+    };
+
+    #[cfg(feature = "rayon")]
+    return ranges.into_par_iter().map(group).collect();
+    #[cfg(not(feature = "rayon"))]
+    ranges.into_iter().map(group).collect()
+}
+
+/// Collects the fixities declared by the groups that have been parsed.
+fn fixity_maps(groups: &[Group]) -> (FixityMap, FixityMap) {
+    let mut value_fixities = FixityMap::default();
+    let mut type_fixities = FixityMap::default();
+    for group in groups {
+        if let Some((TopLevel::Fixity(fixity), _)) = &group.parsed {
+            match fixity.domain {
+                Domain::Type => type_fixities.insert(fixity.operator, fixity.clone()),
+                Domain::Value => value_fixities.insert(fixity.operator, fixity.clone()),
+            };
+        }
+    }
+    (value_fixities, type_fixities)
+}
+
+fn parse_group(
+    source: &str,
+    tokens: &[Token],
+    group: &mut Group,
+    value_fixities: &FixityMap,
+    type_fixities: &FixityMap,
+    syntax: bool,
+) {
+    let group_tokens = &tokens[group.begin..group.end];
+    let mut cursor = match group.kind {
+        GroupK::Import | GroupK::Fixity => Cursor::new(source, group_tokens, None, None),
+        GroupK::Declaration => Cursor::new(
+            source,
+            group_tokens,
+            Some(value_fixities),
+            Some(type_fixities),
+        ),
+    };
+    if syntax {
+        cursor = cursor.with_events();
+    }
+    let result = match group.kind {
+        GroupK::Import => cursor.complete(Cursor::import).map(TopLevel::Import),
+        GroupK::Fixity => cursor.complete(Cursor::fixity).map(TopLevel::Fixity),
+        GroupK::Declaration => cursor
+            .complete(Cursor::declaration)
+            .map(TopLevel::Declaration),
+    };
+    group.parsed = Some(match result {
+        Ok(top_level) => {
+            group.events = cursor.take_events();
+            (top_level, None)
+        }
+        Err(error) => {
+            if syntax {
+                group.events = syntax::error_events(group_tokens);
+            }
+            let declaration = TopLevel::Declaration(error_declaration(source, group_tokens));
+            if group.lex_errors.is_empty() {
+                (declaration, Some(error))
+            } else {
+                (declaration, None)
+            }
+        }
+    });
+}
+
+/// Parses every group that has not been parsed yet. Imports and fixities
+/// come first, since declarations need the fixities of the whole module.
+///
+/// Declarations are then independent of each other, so they are parsed in
+/// parallel when the `rayon` feature is enabled. Each result is stored in
+/// its own group, which keeps them in source order. With `syntax`, the
+/// events of each group's syntax tree are kept as well.
+pub(crate) fn parse_groups(source: &str, tokens: &[Token], groups: &mut [Group], syntax: bool) {
+    let empty = FixityMap::default();
+    for group in groups.iter_mut() {
+        if group.parsed.is_none() && group.kind != GroupK::Declaration {
+            parse_group(source, tokens, group, &empty, &empty, syntax);
+        }
+    }
+
+    let (value_fixities, type_fixities) = fixity_maps(groups);
+    let parse = |group: &mut Group| {
+        if group.parsed.is_none() {
+            parse_group(
+                source,
+                tokens,
+                group,
+                &value_fixities,
+                &type_fixities,
+                syntax,
+            );
+        }
+    };
+
+    #[cfg(feature = "rayon")]
+    groups.par_iter_mut().for_each(parse);
+    #[cfg(not(feature = "rayon"))]
+    groups.iter_mut().for_each(parse);
+}
+
+/// Builds a module out of parsed groups.
+pub(crate) fn assemble(tokens: &[Token], groups: &[Group]) -> (Module, Vec<ParseError>) {
+    let mut errors: Vec<_> = groups
+        .iter()
+        .flat_map(|group| group.lex_errors.iter().cloned().map(ParseError::Lexical))
+        .collect();
+
+    let mut seen_other = false;
+    for group in groups {
+        if group.kind == GroupK::Import {
+            if seen_other {
+                errors.push(ParseError::MisplacedImport {
+                    token: tokens[group.begin],
+                });
+            }
+        } else {
+            seen_other = true;
+        }
+    }
+
+    let mut imports = vec![];
+    let mut fixities = vec![];
+    let mut declarations = vec![];
+    for kind in [GroupK::Import, GroupK::Fixity, GroupK::Declaration] {
+        for group in groups.iter().filter(|group| group.kind == kind) {
+            let (top_level, error) = group
+                .parsed
+                .as_ref()
+                .expect("groups are parsed before they are assembled");
+            match top_level {
+                TopLevel::Import(import) => imports.push(import.clone()),
+                TopLevel::Fixity(fixity) => fixities.push(fixity.clone()),
+                TopLevel::Declaration(declaration) => declarations.push(declaration.clone()),
+            }
+            errors.extend(error.iter().cloned());
+        }
+    }
+
+    // Imports and fixities are parsed ahead of declarations.
+    declarations.sort_by_key(|declaration| declaration.begin);
+    errors.sort_by_key(|error| error.span());
+
+    (
+        Module {
+            imports,
+            fixities,
+            declarations,
+        },
+        errors,
+    )
+}
+
+fn error_declaration(source: &str, group: &[Token]) -> Declaration {
+    let begin = group.first().map_or(0, |token| token.begin);
+    let docs = group
+        .first()
+        .and_then(|token| docs::doc_comment(&source[token.comment_begin..token.comment_end]));
+    let end = group
+        .iter()
+        .rev()
+        .find(|token| !matches!(token.kind, TokenK::Layout(_)))
+        .map_or(begin, |token| token.end);
+    Declaration {
+        begin,
+        end,
+        docs,
+        kind: DeclarationK::Error,
+    }
+}
+
+/// Splits tokens at separators of depth zero, returning the range of each
+/// top-level group. The end of file token does not belong to any group.
+fn partition(tokens: &[Token]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut tokens_iter = tokens.iter();
+    let mut last_start = 0;
+    std::iter::from_fn(move || {
+        let start = last_start;
+        let mut end = last_start;
+        loop {
+            match tokens_iter.next() {
+                Some(token) => {
+                    if token.is_eof() {
+                        break None;
+                    }
+                    end += 1;
+                    if token.is_separator_zero() {
+                        last_start = end;
+                        break Some((start, end));
+                    }
+                }
+                None => {
+                    if end - start == 0 {
+                        break None;
+                    } else {
+                        last_start = end;
+                        break Some((start, end));
+                    }
+                }
+            }
+        }
+    })
+}
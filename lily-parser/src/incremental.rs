@@ -0,0 +1,417 @@
+//! Incremental re-lexing and re-parsing.
+//!
+//! A [`Document`] keeps the tokens of a module along with the top-level
+//! groups they were partitioned into. When an [`Edit`] is applied, only the
+//! groups that overlap with it are lexed and parsed again; the groups after
+//! it are reused with their offsets shifted. If the edit changes a fixity
+//! declaration, every declaration is parsed again, as their structure may
+//! depend on it.
+
+use lily_lexer::{
+    errors::{LexError, LexErrorK},
+    indentation::Tabs,
+    lex_groups,
+    types::{Token, TokenK},
+    Lexer,
+};
+
+use crate::{
+    errors::{OperatorChain, ParseError},
+    groups::{self, Group, GroupK, TopLevel},
+    types::{
+        Declaration, DoStatement, Expression, Fixity, GreaterPattern, Import, ImportItem,
+        ImportList, LesserPattern, Module, Ty,
+    },
+    visit::{
+        walk_declaration_mut, walk_do_statement_mut, walk_expression_mut, walk_greater_pattern_mut,
+        walk_import_list_mut, walk_import_mut, walk_ty_mut, VisitorMut,
+    },
+};
+
+/// Replaces the text between `begin` and `end` with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub begin: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// A parsed module that can be updated with edits.
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    tabs: Tabs,
+    tokens: Vec<Token>,
+    groups: Vec<Group>,
+    module: Module,
+    errors: Vec<ParseError>,
+    /// The number of groups parsed by the last edit.
+    parsed_groups: usize,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self::with_tabs(source, Tabs::default())
+    }
+
+    /// Creates a document whose tabs count towards layout columns as given
+    /// by `tabs`, for this and every later edit.
+    pub fn with_tabs(source: impl Into<String>, tabs: Tabs) -> Self {
+        let source = source.into();
+        let tokens: Vec<_> = Lexer::with_tabs(&source, tabs).collect();
+        let mut groups = groups::groups(&source, &tokens, 0, tokens.len(), tabs);
+        groups::parse_groups(&source, &tokens, &mut groups, false);
+        let parsed_groups = groups.len();
+        let (module, errors) = groups::assemble(&tokens, &groups);
+        Self {
+            source,
+            tabs,
+            tokens,
+            groups,
+            module,
+            errors,
+            parsed_groups,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// The number of top-level groups that were parsed to produce the
+    /// current module, which is every group for a new document and only the
+    /// affected ones after an edit.
+    pub fn parsed_groups(&self) -> usize {
+        self.parsed_groups
+    }
+
+    /// Applies an edit, producing the same result as parsing the edited
+    /// source from scratch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range of the edit is out of bounds or does not lie on
+    /// character boundaries.
+    pub fn edit(&mut self, edit: &Edit) {
+        let Edit { begin, end, text } = edit;
+        let (begin, end) = (*begin, *end);
+
+        let mut source = self.source.clone();
+        source.replace_range(begin..end, text);
+
+        if !self.reparse(&source, begin, end, text) {
+            *self = Document::with_tabs(source, self.tabs);
+        }
+    }
+
+    /// Re-lexes and re-parses the groups affected by an edit, returning
+    /// `false` if the whole document has to be processed again.
+    fn reparse(&mut self, source: &str, begin: usize, end: usize, text: &str) -> bool {
+        let delta = text.len() as isize - (end - begin) as isize;
+        let line_delta = text.matches('\n').count() as isize
+            - self.source[begin..end].matches('\n').count() as isize;
+
+        // Each group owns the trivia that precedes it, such that an edit right
+        // after the last token of a group belongs to that group.
+        let group_begin = |group: &Group| {
+            let tokens = &self.tokens[group.begin..group.end];
+            tokens
+                .iter()
+                .find(|token| !matches!(token.kind, TokenK::Layout(_)))
+                .unwrap_or(&tokens[0])
+                .comment_begin
+        };
+        let first = self
+            .groups
+            .iter()
+            .rposition(|group| group_begin(group) < begin)
+            .unwrap_or(0);
+        let last = self
+            .groups
+            .iter()
+            .position(|group| group_begin(group) > end);
+
+        let Some(first_group) = self.groups.get(first) else {
+            return false;
+        };
+        let region_begin = if first == 0 {
+            0
+        } else {
+            group_begin(first_group)
+        };
+        let region_end = last.map(|last| shift(self.tokens[self.groups[last].begin].begin, delta));
+
+        let Some(region) = lex_groups(source, region_begin, region_end, self.tabs) else {
+            return false;
+        };
+
+        // The layout tokens that close the group before the region were
+        // produced for the previous first token, so it must be of the same
+        // kind for them to be reused.
+        if first > 0 {
+            match region.first() {
+                Some(token) if token.kind == self.tokens[first_group.begin].kind => (),
+                _ => return false,
+            }
+        }
+
+        // Likewise, the groups after the region were laid out against the
+        // column of the first token in the module.
+        if first == 0 && last.is_some() {
+            let old_root = column(&self.source, self.tokens[0].begin, self.tabs);
+            match region.first() {
+                Some(token) if column(source, token.begin, self.tabs) == old_root => (),
+                _ => return false,
+            }
+        }
+
+        let replaced_begin = first_group.begin;
+        let old_first_begin = self.tokens[replaced_begin].begin;
+        let new_first_begin = region.first().map_or(old_first_begin, |token| token.begin);
+        let replaced_end = last.map_or(self.tokens.len(), |last| self.groups[last].begin);
+        let region_length = region.len();
+        let token_delta = region_length as isize - (replaced_end - replaced_begin) as isize;
+
+        self.tokens.splice(replaced_begin..replaced_end, region);
+        for token in &mut self.tokens[replaced_begin + region_length..] {
+            token.shift(delta);
+        }
+
+        // The trivia before the first token after the region starts where
+        // the last token of the region ends, which the edit may have moved.
+        let trivia_begin = self.tokens[replaced_begin..replaced_begin + region_length]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token.kind, TokenK::Layout(_)))
+            .map_or(region_begin, |token| token.end);
+        let trivia_changed = match self.tokens.get_mut(replaced_begin + region_length) {
+            Some(token) if last.is_some() && token.comment_begin != trivia_begin => {
+                token.comment_begin = trivia_begin;
+                true
+            }
+            _ => false,
+        };
+
+        let region_groups = groups::groups(
+            source,
+            &self.tokens,
+            replaced_begin,
+            replaced_begin + region_length,
+            self.tabs,
+        );
+        let fixities_changed = self.groups[first..last.unwrap_or(self.groups.len())]
+            .iter()
+            .chain(&region_groups)
+            .any(|group| group.kind == GroupK::Fixity);
+
+        let mut tail = self.groups.split_off(last.unwrap_or(self.groups.len()));
+        for group in &mut tail {
+            group.begin = shift(group.begin, token_delta);
+            group.end = shift(group.end, token_delta);
+            for error in &mut group.lex_errors {
+                error.shift(delta);
+                if let LexErrorK::UnfinishedComment { line } = &mut error.kind {
+                    *line = shift(*line, line_delta);
+                }
+            }
+            if let Some((top_level, error)) = &mut group.parsed {
+                top_level.shift(delta);
+                if let Some(error) = error {
+                    error.shift(delta);
+                }
+            }
+        }
+
+        // Layout tokens are placed at the token that follows them, so the
+        // ones closing the previous group move along with the region.
+        if first > 0 && old_first_begin != new_first_begin {
+            let previous = self.tokens[..replaced_begin]
+                .iter_mut()
+                .rev()
+                .take_while(|token| {
+                    matches!(token.kind, TokenK::Layout(_)) && token.begin == old_first_begin
+                });
+            for token in previous {
+                token.comment_begin = new_first_begin;
+                token.comment_end = new_first_begin;
+                token.begin = new_first_begin;
+                token.end = new_first_begin;
+            }
+            self.groups[first - 1].parsed = None;
+        }
+
+        // Doc comments are taken from the trivia, so it has to be parsed again.
+        if trivia_changed {
+            if let Some(group) = tail.first_mut() {
+                group.parsed = None;
+            }
+        }
+
+        self.groups.truncate(first);
+        self.groups.extend(region_groups);
+        self.groups.extend(tail);
+
+        if fixities_changed {
+            for group in &mut self.groups {
+                if group.kind == GroupK::Declaration {
+                    group.parsed = None;
+                }
+            }
+        }
+
+        self.parsed_groups = self
+            .groups
+            .iter()
+            .filter(|group| group.parsed.is_none())
+            .count();
+        groups::parse_groups(source, &self.tokens, &mut self.groups, false);
+        let (module, errors) = groups::assemble(&self.tokens, &self.groups);
+        self.source = source.to_string();
+        self.module = module;
+        self.errors = errors;
+        true
+    }
+}
+
+fn shift(offset: usize, delta: isize) -> usize {
+    offset
+        .checked_add_signed(delta)
+        .expect("offsets after an edit cannot be shifted below zero")
+}
+
+fn column(source: &str, offset: usize, tabs: Tabs) -> usize {
+    let line_begin = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+    tabs.column(&source[line_begin..offset])
+}
+
+/// Moves the offsets in a value by the length difference of an edit.
+trait Shift {
+    fn shift(&mut self, delta: isize);
+}
+
+impl Shift for Token {
+    fn shift(&mut self, delta: isize) {
+        self.comment_begin = shift(self.comment_begin, delta);
+        self.comment_end = shift(self.comment_end, delta);
+        self.begin = shift(self.begin, delta);
+        self.end = shift(self.end, delta);
+    }
+}
+
+impl Shift for LexError {
+    fn shift(&mut self, delta: isize) {
+        self.begin = shift(self.begin, delta);
+        self.end = shift(self.end, delta);
+        if let LexErrorK::InconsistentIndentation {
+            first_begin,
+            first_end,
+        } = &mut self.kind
+        {
+            *first_begin = shift(*first_begin, delta);
+            *first_end = shift(*first_end, delta);
+        }
+    }
+}
+
+impl Shift for ParseError {
+    fn shift(&mut self, delta: isize) {
+        match self {
+            ParseError::UnexpectedEndOfFile { offset } => *offset = shift(*offset, delta),
+            ParseError::UnexpectedToken { token, .. }
+            | ParseError::InvalidBindingPower { token }
+            | ParseError::MisplacedImport { token }
+            | ParseError::UnknownBindingPower { token, .. } => token.shift(delta),
+            ParseError::NonAssociativeChain(chain) => {
+                let OperatorChain { first, second, .. } = chain.as_mut();
+                first.shift(delta);
+                second.shift(delta);
+            }
+            ParseError::Lexical(error) => error.shift(delta),
+            ParseError::InternalError(_) => (),
+        }
+    }
+}
+
+impl Shift for TopLevel {
+    fn shift(&mut self, delta: isize) {
+        let mut shifter = Shifter { delta };
+        match self {
+            TopLevel::Import(import) => shifter.visit_import_mut(import),
+            TopLevel::Fixity(fixity) => shifter.visit_fixity_mut(fixity),
+            TopLevel::Declaration(declaration) => shifter.visit_declaration_mut(declaration),
+        }
+    }
+}
+
+/// Shifts the span of every node in a tree.
+struct Shifter {
+    delta: isize,
+}
+
+impl Shifter {
+    fn span(&self, begin: &mut usize, end: &mut usize) {
+        *begin = shift(*begin, self.delta);
+        *end = shift(*end, self.delta);
+    }
+}
+
+impl VisitorMut for Shifter {
+    fn visit_import_mut(&mut self, import: &mut Import) {
+        self.span(&mut import.begin, &mut import.end);
+        walk_import_mut(self, import);
+    }
+
+    fn visit_import_list_mut(&mut self, list: &mut ImportList) {
+        self.span(&mut list.begin, &mut list.end);
+        walk_import_list_mut(self, list);
+    }
+
+    fn visit_import_item_mut(&mut self, item: &mut ImportItem) {
+        self.span(&mut item.begin, &mut item.end);
+    }
+
+    fn visit_fixity_mut(&mut self, fixity: &mut Fixity) {
+        self.span(&mut fixity.begin, &mut fixity.end);
+    }
+
+    fn visit_declaration_mut(&mut self, declaration: &mut Declaration) {
+        self.span(&mut declaration.begin, &mut declaration.end);
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        self.span(&mut expression.begin, &mut expression.end);
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_do_statement_mut(&mut self, statement: &mut DoStatement) {
+        self.span(&mut statement.begin, &mut statement.end);
+        walk_do_statement_mut(self, statement);
+    }
+
+    fn visit_lesser_pattern_mut(&mut self, pattern: &mut LesserPattern) {
+        self.span(&mut pattern.begin, &mut pattern.end);
+    }
+
+    fn visit_greater_pattern_mut(&mut self, pattern: &mut GreaterPattern) {
+        self.span(&mut pattern.begin, &mut pattern.end);
+        walk_greater_pattern_mut(self, pattern);
+    }
+
+    fn visit_ty_mut(&mut self, ty: &mut Ty) {
+        self.span(&mut ty.begin, &mut ty.end);
+        walk_ty_mut(self, ty);
+    }
+}
@@ -1,85 +1,115 @@
+pub mod arena;
 mod core;
 mod cursor;
-mod errors;
+mod docs;
+pub mod errors;
+pub mod foreign;
+mod groups;
+pub mod incremental;
+pub mod syntax;
 pub mod types;
+pub mod validate;
+pub mod visit;
 
-use lily_lexer::{lex, types::Token};
-use types::Module;
+use lily_lexer::{
+    errors::LexError,
+    indentation::{indentation_errors, Tabs},
+    Lexer,
+};
 
 use crate::{
     cursor::Cursor,
-    types::{Domain, FixityMap},
+    errors::ParseError,
+    syntax::SyntaxTree,
+    types::{Import, Module},
 };
 
-pub fn parse_top_level(source: &str) -> anyhow::Result<Module> {
-    let tokens = lex(source);
-
-    let mut fixity_groups = vec![];
-    let mut declaration_groups = vec![];
-    for group in partition(&tokens) {
-        let token = group.first().unwrap();
-        if token.is_infix_identifier() {
-            fixity_groups.push(group);
-        } else {
-            declaration_groups.push(group);
-        }
-    }
-
-    let mut value_fixities = FixityMap::default();
-    let mut type_fixities = FixityMap::default();
+/// Parses a module, recovering from errors in individual top-level groups.
+///
+/// Each group from `partition` is parsed independently, and groups that fail
+/// to parse are kept in the module as `DeclarationK::Error` nodes. Groups
+/// that contain tokens the lexer could not make sense of, or tabs that layout
+/// cannot measure, only report the lexical error, since any parse error there
+/// would be a consequence of it.
+pub fn parse_top_level(source: &str) -> (Module, Vec<ParseError>) {
+    parse_top_level_with_tabs(source, Tabs::default())
+}
 
-    for fixity_group in fixity_groups {
-        let mut cursor = Cursor::new(source, fixity_group, None, None);
-        let (operator, fixity) = cursor.fixity()?;
-        match fixity.domain {
-            Domain::Type => type_fixities.insert(operator, fixity),
-            Domain::Value => value_fixities.insert(operator, fixity),
-        };
-        debug_assert!(cursor.is_eof());
-    }
+/// Parses a module like [`parse_top_level`], with tabs counting towards
+/// layout columns as given by `tabs`.
+pub fn parse_top_level_with_tabs(source: &str, tabs: Tabs) -> (Module, Vec<ParseError>) {
+    let tokens: Vec<_> = Lexer::with_tabs(source, tabs).collect();
+    let mut groups = groups::groups(source, &tokens, 0, tokens.len(), tabs);
+    groups::parse_groups(source, &tokens, &mut groups, false);
+    groups::assemble(&tokens, &groups)
+}
 
-    let mut declarations = vec![];
-    for declaration_group in declaration_groups {
-        let mut cursor = Cursor::new(
-            source,
-            declaration_group,
-            Some(&value_fixities),
-            Some(&type_fixities),
-        );
-        declarations.push(cursor.declaration()?);
-        debug_assert!(cursor.is_eof());
-    }
+/// Parses a module like [`parse_top_level_with_tabs`], along with its
+/// lossless syntax tree. Groups that fail to parse become `ErrorDeclaration`
+/// nodes.
+pub fn parse_with_syntax(source: &str, tabs: Tabs) -> (Module, SyntaxTree, Vec<ParseError>) {
+    let tokens: Vec<_> = Lexer::with_tabs(source, tabs).collect();
+    let mut groups = groups::groups(source, &tokens, 0, tokens.len(), tabs);
+    groups::parse_groups(source, &tokens, &mut groups, true);
+    let rest = groups.last().map_or(0, |group| group.end);
+    let tree = SyntaxTree::build(
+        source,
+        groups
+            .iter_mut()
+            .map(|group| std::mem::take(&mut group.events)),
+        &tokens[rest..],
+    );
+    let (module, errors) = groups::assemble(&tokens, &groups);
+    (module, tree, errors)
+}
 
-    Ok(Module { declarations })
+/// Parses only the imports at the start of a module, for tools that need the
+/// dependencies of many modules without parsing all of them.
+///
+/// Tokens are streamed from the lexer into the parser, so the source is only
+/// lexed up to the first group that is not an import. Errors are reported
+/// like [`parse_top_level`] does for the same groups.
+pub fn parse_imports(source: &str) -> (Vec<Import>, Vec<ParseError>) {
+    parse_imports_with_tabs(source, Tabs::default())
 }
 
-fn partition(tokens: &[Token]) -> impl Iterator<Item = &[Token]> {
-    let mut tokens_iter = tokens.iter();
-    let mut last_start = 0;
-    std::iter::from_fn(move || {
-        let start = last_start;
-        let mut end = last_start;
-        loop {
-            match tokens_iter.next() {
-                Some(token) => {
-                    if token.is_eof() {
-                        break None;
-                    }
-                    end += 1;
-                    if token.is_separator_zero() {
-                        last_start = end;
-                        break Some(&tokens[start..end]);
-                    }
-                }
-                None => {
-                    if end - start == 0 {
-                        break None;
-                    } else {
-                        last_start = end;
-                        break Some(&tokens[start..end]);
-                    }
-                }
+/// Parses the imports at the start of a module like [`parse_imports`], with
+/// tabs counting towards layout columns as given by `tabs`.
+pub fn parse_imports_with_tabs(source: &str, tabs: Tabs) -> (Vec<Import>, Vec<ParseError>) {
+    let mut tokens = Lexer::with_tabs(source, tabs).peekable();
+    let mut imports = vec![];
+    let mut errors = vec![];
+
+    while tokens
+        .peek()
+        .is_some_and(|token| token.is_import_identifier())
+    {
+        let mut lex_errors = vec![];
+        let mut group_tokens = vec![];
+        let mut finished = false;
+        let mut group = std::iter::from_fn(|| {
+            if finished {
+                return None;
             }
+            let token = tokens.next_if(|token| !token.is_eof())?;
+            lex_errors.extend(LexError::from_token(source, &token));
+            group_tokens.push(token);
+            finished = token.is_separator_zero();
+            Some(token)
+        });
+
+        let result = Cursor::from_stream(source, &mut group, None, None).complete(Cursor::import);
+        group.for_each(drop);
+        lex_errors.extend(indentation_errors(source, &group_tokens, tabs));
+
+        match result {
+            Ok(import) => imports.push(import),
+            Err(error) if lex_errors.is_empty() => errors.push(error),
+            Err(_) => (),
         }
-    })
+        errors.extend(lex_errors.into_iter().map(ParseError::Lexical));
+    }
+
+    errors.sort_by_key(|error| error.span());
+    (imports, errors)
 }
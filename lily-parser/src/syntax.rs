@@ -0,0 +1,739 @@
+//! Lossless concrete syntax trees, kept alongside the AST.
+//!
+//! The tree is split in two layers, in the style of `rowan`. Green nodes are
+//! immutable, position-independent and cheap to share; they store the text of
+//! every token, including trivia, so the source can be reproduced exactly.
+//! Red nodes are built on demand over a green tree and know their absolute
+//! offsets and parents.
+//!
+//! The parser records the structure of the tree as a flat list of events
+//! while it consumes tokens, and the green tree is built from those events
+//! and the token stream. The AST in [`crate::types`] is still the owned
+//! result of parsing; the syntax tree does not replace it. AST types that
+//! have a span implement [`HasSyntax`] to find their node in a [`SyntaxTree`]
+//! from the same call to [`crate::parse_with_syntax`].
+
+use std::{fmt, rc::Rc, sync::Arc};
+
+use lily_lexer::{
+    trivia,
+    types::{Token, TokenK},
+};
+use rustc_hash::FxHashMap;
+use smol_str::SmolStr;
+
+use crate::types::{
+    Declaration, DeclarationK, DoStatement, DoStatementK, Expression, ExpressionK, Fixity,
+    GreaterPattern, GreaterPatternK, Import, ImportItem, ImportList, LesserPattern, LesserPatternK,
+    Ty, TyK,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Token(TokenK),
+
+    Module,
+    Import,
+    ImportList,
+    ImportItem,
+    Fixity,
+
+    ValueDeclaration,
+    TypeDeclaration,
+    ForeignDeclaration,
+    ErrorDeclaration,
+
+    ApplicationExpression,
+    BinaryOperatorExpression,
+    CaseExpression,
+    CaseArm,
+    ConstructorExpression,
+    DoExpression,
+    FloatExpression,
+    IfExpression,
+    IntegerExpression,
+    LetExpression,
+    ParenthesizedExpression,
+    VariableExpression,
+
+    BindStatement,
+    DiscardStatement,
+    LetStatement,
+
+    ApplicationPattern,
+    BinaryOperatorPattern,
+    ConstructorPattern,
+    IntegerPattern,
+    NullPattern,
+    ParenthesizedPattern,
+    VariablePattern,
+
+    ApplicationType,
+    BinaryOperatorType,
+    ConstructorType,
+    ParenthesizedType,
+    VariableType,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, SyntaxKind::Token(TokenK::Trivia(_)))
+    }
+
+    pub fn is_declaration(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::ValueDeclaration
+                | SyntaxKind::TypeDeclaration
+                | SyntaxKind::ForeignDeclaration
+                | SyntaxKind::ErrorDeclaration
+        )
+    }
+
+    pub fn is_expression(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::ApplicationExpression
+                | SyntaxKind::BinaryOperatorExpression
+                | SyntaxKind::CaseExpression
+                | SyntaxKind::ConstructorExpression
+                | SyntaxKind::DoExpression
+                | SyntaxKind::FloatExpression
+                | SyntaxKind::IfExpression
+                | SyntaxKind::IntegerExpression
+                | SyntaxKind::LetExpression
+                | SyntaxKind::ParenthesizedExpression
+                | SyntaxKind::VariableExpression
+        )
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::ApplicationPattern
+                | SyntaxKind::BinaryOperatorPattern
+                | SyntaxKind::ConstructorPattern
+                | SyntaxKind::IntegerPattern
+                | SyntaxKind::NullPattern
+                | SyntaxKind::ParenthesizedPattern
+                | SyntaxKind::VariablePattern
+        )
+    }
+
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::ApplicationType
+                | SyntaxKind::BinaryOperatorType
+                | SyntaxKind::ConstructorType
+                | SyntaxKind::ParenthesizedType
+                | SyntaxKind::VariableType
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: SmolStr,
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &str) -> Self {
+        Self {
+            kind,
+            text: SmolStr::new(text),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn width(&self) -> usize {
+        self.text.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width(),
+            GreenElement::Token(token) => token.width(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    width: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let width = children.iter().map(GreenElement::width).sum();
+        Self {
+            kind,
+            width,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    fn write_text(&self, buffer: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(buffer),
+                GreenElement::Token(token) => buffer.push_str(token.text()),
+            }
+        }
+    }
+}
+
+/// Builds green trees from a flat sequence of events.
+#[derive(Debug, Default)]
+pub struct GreenNodeBuilder {
+    parents: Vec<(SyntaxKind, Vec<GreenElement>)>,
+}
+
+impl GreenNodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, vec![]));
+    }
+
+    pub fn token(&mut self, kind: SyntaxKind, text: &str) {
+        if let Some((_, children)) = self.parents.last_mut() {
+            children.push(GreenElement::Token(Arc::new(GreenToken::new(kind, text))));
+        }
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.parents.pop().expect("unbalanced finish_node");
+        let node = GreenElement::Node(Arc::new(GreenNode::new(kind, children)));
+        if let Some((_, children)) = self.parents.last_mut() {
+            children.push(node);
+        } else {
+            self.parents.push((kind, vec![node]));
+        }
+    }
+
+    /// Returns the root node; every started node must have been finished.
+    pub fn finish(mut self) -> GreenNode {
+        match self.parents.pop() {
+            Some((_, mut children)) if self.parents.is_empty() && children.len() == 1 => {
+                match children.pop() {
+                    Some(GreenElement::Node(node)) => Arc::unwrap_or_clone(node),
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("unbalanced GreenNodeBuilder"),
+        }
+    }
+}
+
+struct SyntaxData {
+    green: Arc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+/// A node in the red tree, positioned at an absolute offset.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<SyntaxData>);
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: GreenNode) -> Self {
+        Self(Rc::new(SyntaxData {
+            green: Arc::new(green),
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &GreenNode {
+        &self.0.green
+    }
+
+    /// The byte range that the node spans, including any trivia inside it.
+    pub fn range(&self) -> (usize, usize) {
+        (self.0.offset, self.0.offset + self.0.green.width)
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children.iter().map(move |child| {
+            let child_offset = offset;
+            offset += child.width();
+            match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(SyntaxData {
+                    green: Arc::clone(green),
+                    offset: child_offset,
+                    parent: Some(self.clone()),
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: Arc::clone(green),
+                    offset: child_offset,
+                    parent: self.clone(),
+                }),
+            }
+        })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Every node in the subtree, in preorder, starting with this one.
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![];
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            let mut children: Vec<_> = node.children().collect();
+            children.reverse();
+            stack.extend(children);
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    /// Every token in the subtree, in source order.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    pub fn text(&self) -> String {
+        let mut buffer = String::with_capacity(self.0.green.width);
+        self.0.green.write_text(&mut buffer);
+        buffer
+    }
+
+    /// The range of the node without leading and trailing trivia.
+    pub fn trimmed_range(&self) -> (usize, usize) {
+        let tokens = self.tokens();
+        let mut significant = tokens.iter().filter(|token| !token.kind().is_trivia());
+        match (significant.next(), significant.next_back()) {
+            (Some(first), Some(last)) => (first.range().0, last.range().1),
+            (Some(first), None) => first.range(),
+            _ => {
+                let (begin, _) = self.range();
+                (begin, begin)
+            }
+        }
+    }
+
+    fn debug_dump(&self, f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+        let (begin, end) = self.range();
+        writeln!(
+            f,
+            "{:indent$}{:?}@{}..{}",
+            "",
+            self.kind(),
+            begin,
+            end,
+            indent = level * 2
+        )?;
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => node.debug_dump(f, level + 1)?,
+                SyntaxElement::Token(token) => {
+                    let (begin, end) = token.range();
+                    writeln!(
+                        f,
+                        "{:indent$}{:?}@{}..{} {:?}",
+                        "",
+                        token.kind(),
+                        begin,
+                        end,
+                        token.text(),
+                        indent = (level + 1) * 2
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    /// The alternate form prints the whole subtree, one element per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.debug_dump(f, 0)
+        } else {
+            let (begin, end) = self.range();
+            write!(f, "{:?}@{}..{}", self.kind(), begin, end)
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        self.green.text()
+    }
+
+    pub fn range(&self) -> (usize, usize) {
+        (self.offset, self.offset + self.green.width())
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+}
+
+/// A step in building a syntax tree, as recorded by the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// Starts a node before the next token. The kind is filled in when the
+    /// node is finished; nodes that were abandoned have none. A forward parent
+    /// is the distance to the `Start` of a node that was started later, but
+    /// that encloses this one.
+    Start {
+        kind: Option<SyntaxKind>,
+        forward_parent: Option<usize>,
+    },
+    Token(Token),
+    Finish,
+}
+
+/// The events of a group that failed to parse, which keep its tokens in a
+/// single `ErrorDeclaration` node.
+pub(crate) fn error_events(tokens: &[Token]) -> Vec<Event> {
+    let mut events = vec![Event::Start {
+        kind: Some(SyntaxKind::ErrorDeclaration),
+        forward_parent: None,
+    }];
+    events.extend(tokens.iter().copied().map(Event::Token));
+    events.push(Event::Finish);
+    events
+}
+
+/// Turns events into a green tree, adding trivia from the source.
+struct TreeSink<'a> {
+    source: &'a str,
+    builder: GreenNodeBuilder,
+    /// The nodes to start before the next token, outermost first.
+    pending: Vec<SyntaxKind>,
+    /// The end of the text added so far.
+    offset: usize,
+}
+
+impl<'a> TreeSink<'a> {
+    /// Adds a token along with the trivia before it.
+    ///
+    /// Trivia goes into the enclosing node before the nodes that start at the
+    /// token are opened, so the range of a node matches the span of its AST
+    /// node. Layout tokens take no space in the source and are left out, but
+    /// still place the nodes that start at them.
+    fn token(&mut self, token: &Token) {
+        if let TokenK::Layout(_) = token.kind {
+            if !self.pending.is_empty() {
+                self.trivia(token.begin);
+                self.start_pending();
+            }
+            return;
+        }
+        self.trivia(token.begin);
+        self.start_pending();
+        if !token.is_eof() {
+            self.builder.token(
+                SyntaxKind::Token(token.kind),
+                &self.source[token.begin..token.end],
+            );
+            self.offset = token.end;
+        }
+    }
+
+    fn trivia(&mut self, end: usize) {
+        for trivia in trivia(self.source, self.offset, end) {
+            self.builder.token(
+                SyntaxKind::Token(trivia.kind),
+                &self.source[trivia.begin..trivia.end],
+            );
+            self.offset = trivia.end;
+        }
+    }
+
+    fn start_pending(&mut self) {
+        for kind in self.pending.drain(..) {
+            self.builder.start_node(kind);
+        }
+    }
+
+    fn events(&mut self, mut events: Vec<Event>) {
+        let mut kinds = vec![];
+        for index in 0..events.len() {
+            match std::mem::replace(&mut events[index], Event::Finish) {
+                Event::Start {
+                    kind,
+                    mut forward_parent,
+                } => {
+                    // The parents that were started later are replaced with
+                    // abandoned nodes, so they are only started from here.
+                    kinds.extend(kind);
+                    let mut parent = index;
+                    while let Some(distance) = forward_parent {
+                        parent += distance;
+                        let abandoned = Event::Start {
+                            kind: None,
+                            forward_parent: None,
+                        };
+                        match std::mem::replace(&mut events[parent], abandoned) {
+                            Event::Start {
+                                kind,
+                                forward_parent: next,
+                            } => {
+                                kinds.extend(kind);
+                                forward_parent = next;
+                            }
+                            _ => unreachable!("a forward parent is a start event"),
+                        }
+                    }
+                    self.pending.extend(kinds.drain(..).rev());
+                }
+                Event::Token(token) => self.token(&token),
+                Event::Finish => {
+                    self.start_pending();
+                    self.builder.finish_node();
+                }
+            }
+        }
+    }
+}
+
+/// A lossless syntax tree, indexed by the kinds and spans of its nodes.
+pub struct SyntaxTree {
+    root: SyntaxNode,
+    nodes: FxHashMap<(SyntaxKind, usize, usize), SyntaxNode>,
+}
+
+impl SyntaxTree {
+    /// Builds the tree of a module out of the events of its groups, in
+    /// source order, and the tokens after them, such as the end of file.
+    pub(crate) fn build(
+        source: &str,
+        groups: impl Iterator<Item = Vec<Event>>,
+        rest: &[Token],
+    ) -> Self {
+        let mut sink = TreeSink {
+            source,
+            builder: GreenNodeBuilder::new(),
+            pending: vec![],
+            offset: 0,
+        };
+        sink.builder.start_node(SyntaxKind::Module);
+        for events in groups {
+            sink.events(events);
+        }
+        for token in rest {
+            sink.token(token);
+        }
+        sink.builder.finish_node();
+
+        let root = SyntaxNode::new_root(sink.builder.finish());
+        let mut nodes = FxHashMap::default();
+        index(&root, &mut nodes);
+        Self { root, nodes }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// The innermost node of the given kind that spans exactly `begin..end`,
+    /// ignoring surrounding trivia.
+    pub fn node(&self, kind: SyntaxKind, begin: usize, end: usize) -> Option<SyntaxNode> {
+        self.nodes.get(&(kind, begin, end)).cloned()
+    }
+}
+
+impl fmt::Debug for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+/// Adds every node below `node` to `nodes`, returning its trimmed range.
+///
+/// Children are indexed before their parents, so the innermost node is kept
+/// when nested nodes share a kind and a range.
+fn index(
+    node: &SyntaxNode,
+    nodes: &mut FxHashMap<(SyntaxKind, usize, usize), SyntaxNode>,
+) -> Option<(usize, usize)> {
+    let mut range: Option<(usize, usize)> = None;
+    for child in node.children_with_tokens() {
+        let child_range = match child {
+            SyntaxElement::Node(child) => index(&child, nodes),
+            SyntaxElement::Token(token) if token.kind().is_trivia() => None,
+            SyntaxElement::Token(token) => Some(token.range()),
+        };
+        if let Some((begin, end)) = child_range {
+            range = Some((range.map_or(begin, |(begin, _)| begin), end));
+        }
+    }
+    let (begin, _) = node.range();
+    let (trimmed_begin, trimmed_end) = range.unwrap_or((begin, begin));
+    nodes
+        .entry((node.kind(), trimmed_begin, trimmed_end))
+        .or_insert_with(|| node.clone());
+    range
+}
+
+/// An AST node with a counterpart in the syntax tree.
+///
+/// Nodes are matched by kind and span, so only AST nodes from the same call
+/// to [`crate::parse_with_syntax`] as the tree are found. An AST whose spans
+/// were changed afterwards, such as by an incremental edit, finds nothing.
+pub trait HasSyntax {
+    fn syntax_kind(&self) -> SyntaxKind;
+
+    fn span(&self) -> (usize, usize);
+
+    /// The node in the syntax tree parsed along with this AST node.
+    fn syntax(&self, tree: &SyntaxTree) -> Option<SyntaxNode> {
+        let (begin, end) = self.span();
+        tree.node(self.syntax_kind(), begin, end)
+    }
+}
+
+macro_rules! has_syntax {
+    ($name:ident, |$node:pat_param| $kind:expr) => {
+        impl HasSyntax for $name {
+            fn syntax_kind(&self) -> SyntaxKind {
+                let $node = self;
+                $kind
+            }
+
+            fn span(&self) -> (usize, usize) {
+                (self.begin, self.end)
+            }
+        }
+    };
+}
+
+has_syntax!(Import, |_| SyntaxKind::Import);
+has_syntax!(ImportList, |_| SyntaxKind::ImportList);
+has_syntax!(ImportItem, |_| SyntaxKind::ImportItem);
+has_syntax!(Fixity, |_| SyntaxKind::Fixity);
+
+has_syntax!(Declaration, |declaration| match declaration.kind {
+    DeclarationK::ValueDeclaration(..) => SyntaxKind::ValueDeclaration,
+    DeclarationK::TypeDeclaration(..) => SyntaxKind::TypeDeclaration,
+    DeclarationK::ForeignValue(..) | DeclarationK::ForeignData(..) => {
+        SyntaxKind::ForeignDeclaration
+    }
+    DeclarationK::Error => SyntaxKind::ErrorDeclaration,
+});
+
+has_syntax!(Expression, |expression| match expression.kind {
+    ExpressionK::Application(..) => SyntaxKind::ApplicationExpression,
+    ExpressionK::BinaryOperator(..) => SyntaxKind::BinaryOperatorExpression,
+    ExpressionK::CaseOf(..) => SyntaxKind::CaseExpression,
+    ExpressionK::Constructor(_) => SyntaxKind::ConstructorExpression,
+    ExpressionK::DoBlock(_) => SyntaxKind::DoExpression,
+    ExpressionK::Float(_) => SyntaxKind::FloatExpression,
+    ExpressionK::IfThenElse(..) => SyntaxKind::IfExpression,
+    ExpressionK::Integer(_) => SyntaxKind::IntegerExpression,
+    ExpressionK::Let(..) => SyntaxKind::LetExpression,
+    ExpressionK::Parenthesized(_) => SyntaxKind::ParenthesizedExpression,
+    ExpressionK::Variable(_) => SyntaxKind::VariableExpression,
+});
+
+has_syntax!(DoStatement, |statement| match statement.kind {
+    DoStatementK::BindExpression(..) => SyntaxKind::BindStatement,
+    DoStatementK::DiscardExpression(_) => SyntaxKind::DiscardStatement,
+    DoStatementK::LetStatement(_) => SyntaxKind::LetStatement,
+});
+
+has_syntax!(LesserPattern, |pattern| match pattern.kind {
+    LesserPatternK::Null => SyntaxKind::NullPattern,
+    LesserPatternK::Variable(_) => SyntaxKind::VariablePattern,
+});
+
+has_syntax!(GreaterPattern, |pattern| match pattern.kind {
+    GreaterPatternK::Application(..) => SyntaxKind::ApplicationPattern,
+    GreaterPatternK::BinaryOperator(..) => SyntaxKind::BinaryOperatorPattern,
+    GreaterPatternK::Constructor(_) => SyntaxKind::ConstructorPattern,
+    GreaterPatternK::Integer(_) => SyntaxKind::IntegerPattern,
+    GreaterPatternK::Null => SyntaxKind::NullPattern,
+    GreaterPatternK::Parenthesized(_) => SyntaxKind::ParenthesizedPattern,
+    GreaterPatternK::Variable(_) => SyntaxKind::VariablePattern,
+});
+
+has_syntax!(Ty, |ty| match ty.kind {
+    TyK::Application(..) => SyntaxKind::ApplicationType,
+    TyK::BinaryOperator(..) => SyntaxKind::BinaryOperatorType,
+    TyK::Constructor(_) => SyntaxKind::ConstructorType,
+    TyK::Parenthesized(_) => SyntaxKind::ParenthesizedType,
+    TyK::Variable(_) => SyntaxKind::VariableType,
+});
@@ -1,143 +1,226 @@
+use lily_lexer::symbol::Symbol;
 use rustc_hash::FxHashMap;
 use smol_str::SmolStr;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Associativity {
+    Infix,
     Infixl,
     Infixr,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Domain {
     Type,
     Value,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Fixity {
     pub begin: usize,
     pub end: usize,
     pub associativity: Associativity,
     pub domain: Domain,
     pub binding_power: u8,
-    pub identifier: SmolStr,
+    pub identifier: Symbol,
+    pub operator: Symbol,
 }
 
 impl Fixity {
+    /// The highest binding power, such that the right binding power of a
+    /// left-associative operator still fits in a `u8`.
+    pub const MAX_BINDING_POWER: u8 = u8::MAX - 1;
+
+    /// Out-of-range binding powers saturate here rather than overflowing;
+    /// `validate_fixities` reports them.
     pub fn as_pair(&self) -> (u8, u8) {
+        let (lower, upper) = (self.binding_power, self.binding_power.saturating_add(1));
         match self.associativity {
-            Associativity::Infixl => (self.binding_power, self.binding_power + 1),
-            Associativity::Infixr => (self.binding_power + 1, self.binding_power),
+            Associativity::Infix | Associativity::Infixl => (lower, upper),
+            Associativity::Infixr => (upper, lower),
         }
     }
 }
 
-pub type FixityMap = FxHashMap<SmolStr, Fixity>;
+/// Fixities keyed by their interned operator.
+pub type FixityMap = FxHashMap<Symbol, Fixity>;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LesserPattern {
     pub begin: usize,
     pub end: usize,
     pub kind: LesserPatternK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LesserPatternK {
     Null,
-    Variable(SmolStr),
+    Variable(Symbol),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GreaterPattern {
     pub begin: usize,
     pub end: usize,
     pub kind: GreaterPatternK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GreaterPatternK {
     Application(Box<GreaterPattern>, Vec<GreaterPattern>),
-    BinaryOperator(Box<GreaterPattern>, SmolStr, Box<GreaterPattern>),
-    Constructor(SmolStr),
+    BinaryOperator(Box<GreaterPattern>, Symbol, Box<GreaterPattern>),
+    Constructor(Symbol),
     Integer(SmolStr),
     Null,
     Parenthesized(Box<GreaterPattern>),
-    Variable(SmolStr),
+    Variable(Symbol),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expression {
     pub begin: usize,
     pub end: usize,
     pub kind: ExpressionK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExpressionK {
     Application(Box<Expression>, Vec<Expression>),
-    BinaryOperator(Box<Expression>, SmolStr, Box<Expression>),
+    BinaryOperator(Box<Expression>, Symbol, Box<Expression>),
     CaseOf(Vec<Expression>, Vec<CaseArm>),
-    Constructor(SmolStr),
+    Constructor(Symbol),
     DoBlock(Vec<DoStatement>),
     Float(SmolStr),
     IfThenElse(Box<Expression>, Box<Expression>, Box<Expression>),
     Integer(SmolStr),
     Let(Vec<Declaration>, Box<Expression>),
     Parenthesized(Box<Expression>),
-    Variable(SmolStr),
+    Variable(Symbol),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DoStatement {
     pub begin: usize,
     pub end: usize,
     pub kind: DoStatementK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DoStatementK {
     BindExpression(LesserPattern, Expression),
     DiscardExpression(Expression),
     LetStatement(Vec<Declaration>),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CaseArm {
     pub patterns: Vec<GreaterPattern>,
     pub condition: Option<Expression>,
     pub expression: Expression,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ty {
     pub begin: usize,
     pub end: usize,
     pub kind: TyK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TyK {
     Application(Box<Ty>, Vec<Ty>),
-    BinaryOperator(Box<Ty>, SmolStr, Box<Ty>),
-    Constructor(SmolStr),
+    BinaryOperator(Box<Ty>, Symbol, Box<Ty>),
+    Constructor(Symbol),
     Parenthesized(Box<Ty>),
-    Variable(SmolStr),
+    Variable(Symbol),
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Declaration {
     pub begin: usize,
     pub end: usize,
+    pub docs: Option<SmolStr>,
     pub kind: DeclarationK,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DeclarationK {
-    ValueDeclaration(SmolStr, Vec<LesserPattern>, Expression),
-    TypeDeclaration(SmolStr, Ty),
+    ValueDeclaration(Symbol, Vec<LesserPattern>, Expression),
+    TypeDeclaration(Symbol, Ty),
+    ForeignValue(Symbol, Ty),
+    ForeignData(Symbol, Ty),
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImportMembers {
+    All,
+    Listed(Vec<Symbol>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportItem {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: ImportItemK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImportItemK {
+    Operator(Symbol),
+    Type(Symbol, Option<ImportMembers>),
+    Value(Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImportList {
+    pub begin: usize,
+    pub end: usize,
+    pub kind: ImportListK,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImportListK {
+    Hiding(Vec<ImportItem>),
+    Only(Vec<ImportItem>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Import {
+    pub begin: usize,
+    pub end: usize,
+    pub module_name: Symbol,
+    pub qualified: bool,
+    pub alias: Option<Symbol>,
+    pub list: Option<ImportList>,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
+    pub imports: Vec<Import>,
+    pub fixities: Vec<Fixity>,
     pub declarations: Vec<Declaration>,
 }
@@ -0,0 +1,551 @@
+use lily_lexer::{symbol::Symbol, types::OperatorK};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    errors::{FixityError, InvariantError},
+    types::{
+        CaseArm, Declaration, DeclarationK, DoStatement, DoStatementK, Domain, Expression,
+        ExpressionK, Fixity, GreaterPattern, GreaterPatternK, Import, ImportItem, ImportItemK,
+        ImportList, ImportListK, LesserPattern, LesserPatternK, Module, Ty, TyK,
+    },
+    visit::{
+        walk_case_arm, walk_declaration, walk_do_statement, walk_expression, walk_greater_pattern,
+        walk_import, walk_import_list, walk_module, walk_ty, Visitor,
+    },
+};
+
+/// Types that every module can refer to without declaring or importing them.
+const BUILTIN_TYPES: &[&str] = &["Function"];
+
+/// Names that a fixity declaration may refer to, per domain.
+struct Scope<'m> {
+    values: FxHashSet<Symbol>,
+    types: FxHashSet<Symbol>,
+    open_imports: Vec<Option<&'m ImportListK>>,
+}
+
+impl<'m> Scope<'m> {
+    fn new(module: &'m Module) -> Self {
+        let mut values = FxHashSet::default();
+        let mut types: FxHashSet<_> = BUILTIN_TYPES
+            .iter()
+            .map(|name| Symbol::intern(name))
+            .collect();
+
+        for declaration in &module.declarations {
+            match &declaration.kind {
+                DeclarationK::ValueDeclaration(name, _, _)
+                | DeclarationK::TypeDeclaration(name, _)
+                | DeclarationK::ForeignValue(name, _) => {
+                    values.insert(*name);
+                }
+                DeclarationK::ForeignData(name, _) => {
+                    types.insert(*name);
+                }
+                DeclarationK::Error => (),
+            }
+        }
+
+        let mut open_imports = vec![];
+        for import in &module.imports {
+            if import.qualified {
+                continue;
+            }
+            match import.list.as_ref().map(|list| &list.kind) {
+                Some(ImportListK::Only(items)) => {
+                    for item in items {
+                        match &item.kind {
+                            ImportItemK::Value(name) => {
+                                values.insert(*name);
+                            }
+                            ImportItemK::Type(name, _) => {
+                                types.insert(*name);
+                            }
+                            ImportItemK::Operator(_) => (),
+                        }
+                    }
+                }
+                list => open_imports.push(list),
+            }
+        }
+
+        Self {
+            values,
+            types,
+            open_imports,
+        }
+    }
+
+    /// Whether an unqualified, unrestricted import could provide the name.
+    fn is_possibly_imported(&self, name: Symbol, domain: Domain) -> bool {
+        self.open_imports.iter().any(|list| match list {
+            Some(ImportListK::Hiding(items)) => {
+                !items.iter().any(|item| match (&item.kind, domain) {
+                    (ImportItemK::Value(hidden), Domain::Value)
+                    | (ImportItemK::Type(hidden, _), Domain::Type) => *hidden == name,
+                    _ => false,
+                })
+            }
+            _ => true,
+        })
+    }
+
+    fn contains(&self, name: Symbol, domain: Domain) -> bool {
+        let declared = match domain {
+            Domain::Type => &self.types,
+            Domain::Value => &self.values,
+        };
+        declared.contains(&name) || self.is_possibly_imported(name, domain)
+    }
+}
+
+fn is_builtin(fixity: &Fixity) -> bool {
+    match OperatorK::from_source(fixity.operator.as_str()) {
+        OperatorK::Source => false,
+        OperatorK::ArrowRight => fixity.domain == Domain::Value,
+        _ => true,
+    }
+}
+
+/// Checks a module's fixity declarations for duplicates, unknown targets,
+/// out-of-range binding powers, and operators that shadow built-ins.
+pub fn validate_fixities(module: &Module) -> Vec<FixityError> {
+    let scope = Scope::new(module);
+    let mut seen: FxHashMap<(Domain, Symbol), &Fixity> = FxHashMap::default();
+    let mut errors = vec![];
+
+    for fixity in &module.fixities {
+        let Fixity {
+            begin,
+            end,
+            operator,
+            ..
+        } = fixity;
+
+        if let Some(first) = seen.get(&(fixity.domain, *operator)) {
+            errors.push(FixityError::Duplicate {
+                operator: operator.as_str().into(),
+                begin: *begin,
+                end: *end,
+                first_begin: first.begin,
+                first_end: first.end,
+            });
+        } else {
+            seen.insert((fixity.domain, *operator), fixity);
+        }
+
+        if !scope.contains(fixity.identifier, fixity.domain) {
+            errors.push(FixityError::UnknownTarget {
+                operator: operator.as_str().into(),
+                identifier: fixity.identifier.as_str().into(),
+                begin: *begin,
+                end: *end,
+            });
+        }
+
+        if fixity.binding_power.checked_add(1).is_none() {
+            errors.push(FixityError::BindingPowerOverflow {
+                operator: operator.as_str().into(),
+                binding_power: fixity.binding_power,
+                begin: *begin,
+                end: *end,
+            });
+        }
+
+        if is_builtin(fixity) {
+            errors.push(FixityError::ShadowsBuiltin {
+                operator: operator.as_str().into(),
+                begin: *begin,
+                end: *end,
+            });
+        }
+    }
+
+    errors
+}
+
+/// The span of the node whose children are being checked, along with the
+/// end of the last child that was checked.
+#[derive(Clone, Copy)]
+struct Parent {
+    begin: usize,
+    end: usize,
+    previous_end: usize,
+}
+
+impl Parent {
+    fn new(begin: usize, end: usize) -> Self {
+        Parent {
+            begin,
+            end,
+            previous_end: begin,
+        }
+    }
+}
+
+/// A run of children that the `walk_*` functions visit in order: either a
+/// single child or a list of them.
+struct Field {
+    name: &'static str,
+    count: Option<usize>,
+}
+
+impl Field {
+    fn one(name: &'static str) -> Self {
+        Field { name, count: None }
+    }
+
+    fn many(name: &'static str, count: usize) -> Self {
+        Field {
+            name,
+            count: Some(count),
+        }
+    }
+}
+
+/// A node whose children are being visited.
+struct Frame {
+    /// Case arms have no span of their own, so their children are checked
+    /// against the nearest ancestor that has one.
+    bounds: Option<Parent>,
+    fields: Vec<Field>,
+    /// Whether each list starts over from the beginning of the node, rather
+    /// than following the list before it.
+    unordered_fields: bool,
+    visited: usize,
+}
+
+impl Frame {
+    fn new(bounds: Option<Parent>, fields: Vec<Field>) -> Self {
+        Frame {
+            bounds,
+            fields,
+            unordered_fields: false,
+            visited: 0,
+        }
+    }
+
+    /// Names the next child, and whether it starts a list.
+    fn next_segment(&mut self) -> (String, bool) {
+        let mut index = self.visited;
+        self.visited += 1;
+        for field in &self.fields {
+            match field.count {
+                None if index == 0 => return (field.name.into(), false),
+                None => index -= 1,
+                Some(count) if index < count => {
+                    return (format!("{}[{}]", field.name, index), index == 0)
+                }
+                Some(count) => index -= count,
+            }
+        }
+        unreachable!("a node has more children than fields")
+    }
+}
+
+/// Checks every node as it is visited, keeping the path from the module
+/// to the node in `frames`.
+struct Invariants<'a> {
+    source: &'a str,
+    path: Vec<String>,
+    frames: Vec<Frame>,
+    errors: Vec<InvariantError>,
+}
+
+impl Invariants<'_> {
+    fn path(&self) -> String {
+        self.path.join(".")
+    }
+
+    /// Checks the span of the next child of the current node and makes it
+    /// the current node, whose own children are named by `fields`.
+    fn enter(&mut self, begin: usize, end: usize, fields: Vec<Field>) {
+        let parent = self.push_segment();
+        let path = self.path();
+        if self.source.get(begin..end).is_none() {
+            self.errors
+                .push(InvariantError::InvalidSpan { path, begin, end });
+        } else if begin < parent.begin || end > parent.end {
+            self.errors.push(InvariantError::OutsideParent {
+                path,
+                begin,
+                end,
+                parent_begin: parent.begin,
+                parent_end: parent.end,
+            });
+        } else if begin < parent.previous_end {
+            self.errors.push(InvariantError::OverlapsSibling {
+                path,
+                begin,
+                end,
+                previous_end: parent.previous_end,
+            });
+        }
+        self.bounds().previous_end = parent.previous_end.max(end);
+        self.frames
+            .push(Frame::new(Some(Parent::new(begin, end)), fields));
+    }
+
+    /// Makes the next child of the current node, which has no span, the
+    /// current node.
+    fn enter_unbounded(&mut self, fields: Vec<Field>) {
+        self.push_segment();
+        self.frames.push(Frame::new(None, fields));
+    }
+
+    /// Adds the name of the next child to the path, returning the bounds it
+    /// is checked against.
+    fn push_segment(&mut self) -> Parent {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("nodes are visited from the module");
+        let (segment, starts_list) = frame.next_segment();
+        if starts_list && frame.unordered_fields {
+            if let Some(bounds) = &mut frame.bounds {
+                bounds.previous_end = bounds.begin;
+            }
+        }
+        self.path.push(segment);
+        *self.bounds()
+    }
+
+    /// The bounds of the innermost node that has a span.
+    fn bounds(&mut self) -> &mut Parent {
+        self.frames
+            .iter_mut()
+            .rev()
+            .find_map(|frame| frame.bounds.as_mut())
+            .expect("the module has a span")
+    }
+
+    fn leave(&mut self) {
+        self.frames.pop();
+        self.path.pop();
+    }
+
+    fn name(&mut self, name: &str, begin: usize, end: usize) {
+        if self.source.get(begin..end).is_some_and(|text| text != name) {
+            self.errors.push(InvariantError::TextMismatch {
+                path: self.path(),
+                name: name.into(),
+                begin,
+                end,
+            });
+        }
+    }
+
+    fn application(&mut self, arguments: usize, begin: usize, end: usize) {
+        if arguments == 0 {
+            self.errors.push(InvariantError::EmptyApplication {
+                path: self.path(),
+                begin,
+                end,
+            });
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Invariants<'_> {
+    fn visit_module(&mut self, module: &'ast Module) {
+        // Fixities and declarations are interleaved in the source, so each
+        // is only ordered among its own kind.
+        let fields = vec![
+            Field::many("imports", module.imports.len()),
+            Field::many("fixities", module.fixities.len()),
+            Field::many("declarations", module.declarations.len()),
+        ];
+        self.frames.push(Frame {
+            unordered_fields: true,
+            ..Frame::new(Some(Parent::new(0, self.source.len())), fields)
+        });
+        walk_module(self, module);
+        self.frames.pop();
+    }
+
+    fn visit_import(&mut self, import: &'ast Import) {
+        let fields = import.list.iter().map(|_| Field::one("list")).collect();
+        self.enter(import.begin, import.end, fields);
+        walk_import(self, import);
+        self.leave();
+    }
+
+    fn visit_import_list(&mut self, list: &'ast ImportList) {
+        let (ImportListK::Hiding(items) | ImportListK::Only(items)) = &list.kind;
+        self.enter(
+            list.begin,
+            list.end,
+            vec![Field::many("items", items.len())],
+        );
+        walk_import_list(self, list);
+        self.leave();
+    }
+
+    fn visit_import_item(&mut self, item: &'ast ImportItem) {
+        self.enter(item.begin, item.end, vec![]);
+        self.leave();
+    }
+
+    fn visit_fixity(&mut self, fixity: &'ast Fixity) {
+        self.enter(fixity.begin, fixity.end, vec![]);
+        self.leave();
+    }
+
+    fn visit_declaration(&mut self, declaration: &'ast Declaration) {
+        let fields = match &declaration.kind {
+            DeclarationK::ValueDeclaration(_, patterns, _) => vec![
+                Field::many("patterns", patterns.len()),
+                Field::one("expression"),
+            ],
+            DeclarationK::TypeDeclaration(..)
+            | DeclarationK::ForeignValue(..)
+            | DeclarationK::ForeignData(..) => vec![Field::one("ty")],
+            DeclarationK::Error => vec![],
+        };
+        self.enter(declaration.begin, declaration.end, fields);
+        walk_declaration(self, declaration);
+        self.leave();
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        let Expression { begin, end, .. } = *expression;
+        let fields = match &expression.kind {
+            ExpressionK::Application(_, arguments) => vec![
+                Field::one("function"),
+                Field::many("arguments", arguments.len()),
+            ],
+            ExpressionK::BinaryOperator(..) => vec![Field::one("left"), Field::one("right")],
+            ExpressionK::CaseOf(expressions, arms) => vec![
+                Field::many("expressions", expressions.len()),
+                Field::many("arms", arms.len()),
+            ],
+            ExpressionK::DoBlock(statements) => vec![Field::many("statements", statements.len())],
+            ExpressionK::IfThenElse(..) => vec![
+                Field::one("condition"),
+                Field::one("then"),
+                Field::one("else"),
+            ],
+            ExpressionK::Let(declarations, _) => vec![
+                Field::many("declarations", declarations.len()),
+                Field::one("expression"),
+            ],
+            ExpressionK::Parenthesized(_) => vec![Field::one("expression")],
+            ExpressionK::Constructor(_)
+            | ExpressionK::Float(_)
+            | ExpressionK::Integer(_)
+            | ExpressionK::Variable(_) => vec![],
+        };
+        self.enter(begin, end, fields);
+        match &expression.kind {
+            ExpressionK::Application(_, arguments) => self.application(arguments.len(), begin, end),
+            ExpressionK::Constructor(name) | ExpressionK::Variable(name) => {
+                self.name(name.as_str(), begin, end)
+            }
+            ExpressionK::Float(literal) | ExpressionK::Integer(literal) => {
+                self.name(literal, begin, end)
+            }
+            _ => (),
+        }
+        walk_expression(self, expression);
+        self.leave();
+    }
+
+    fn visit_case_arm(&mut self, arm: &'ast CaseArm) {
+        let mut fields = vec![Field::many("patterns", arm.patterns.len())];
+        fields.extend(arm.condition.iter().map(|_| Field::one("condition")));
+        fields.push(Field::one("expression"));
+        self.enter_unbounded(fields);
+        walk_case_arm(self, arm);
+        self.leave();
+    }
+
+    fn visit_do_statement(&mut self, statement: &'ast DoStatement) {
+        let fields = match &statement.kind {
+            DoStatementK::BindExpression(..) => {
+                vec![Field::one("pattern"), Field::one("expression")]
+            }
+            DoStatementK::DiscardExpression(_) => vec![Field::one("expression")],
+            DoStatementK::LetStatement(declarations) => {
+                vec![Field::many("declarations", declarations.len())]
+            }
+        };
+        self.enter(statement.begin, statement.end, fields);
+        walk_do_statement(self, statement);
+        self.leave();
+    }
+
+    fn visit_lesser_pattern(&mut self, pattern: &'ast LesserPattern) {
+        self.enter(pattern.begin, pattern.end, vec![]);
+        if let LesserPatternK::Variable(name) = &pattern.kind {
+            self.name(name.as_str(), pattern.begin, pattern.end);
+        }
+        self.leave();
+    }
+
+    fn visit_greater_pattern(&mut self, pattern: &'ast GreaterPattern) {
+        let GreaterPattern { begin, end, .. } = *pattern;
+        let fields = match &pattern.kind {
+            GreaterPatternK::Application(_, arguments) => vec![
+                Field::one("function"),
+                Field::many("arguments", arguments.len()),
+            ],
+            GreaterPatternK::BinaryOperator(..) => vec![Field::one("left"), Field::one("right")],
+            GreaterPatternK::Parenthesized(_) => vec![Field::one("pattern")],
+            GreaterPatternK::Constructor(_)
+            | GreaterPatternK::Integer(_)
+            | GreaterPatternK::Null
+            | GreaterPatternK::Variable(_) => vec![],
+        };
+        self.enter(begin, end, fields);
+        match &pattern.kind {
+            GreaterPatternK::Application(_, arguments) => {
+                self.application(arguments.len(), begin, end)
+            }
+            GreaterPatternK::Constructor(name) | GreaterPatternK::Variable(name) => {
+                self.name(name.as_str(), begin, end)
+            }
+            GreaterPatternK::Integer(literal) => self.name(literal, begin, end),
+            _ => (),
+        }
+        walk_greater_pattern(self, pattern);
+        self.leave();
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        let Ty { begin, end, .. } = *ty;
+        let fields = match &ty.kind {
+            TyK::Application(_, arguments) => vec![
+                Field::one("function"),
+                Field::many("arguments", arguments.len()),
+            ],
+            TyK::BinaryOperator(..) => vec![Field::one("left"), Field::one("right")],
+            TyK::Parenthesized(_) => vec![Field::one("ty")],
+            TyK::Constructor(_) | TyK::Variable(_) => vec![],
+        };
+        self.enter(begin, end, fields);
+        match &ty.kind {
+            TyK::Application(_, arguments) => self.application(arguments.len(), begin, end),
+            TyK::Constructor(name) | TyK::Variable(name) => self.name(name.as_str(), begin, end),
+            _ => (),
+        }
+        walk_ty(self, ty);
+        self.leave();
+    }
+}
+
+/// Checks the structural invariants of a module parsed from `source`.
+///
+/// Every span must be a range of the source that lies within the span of its
+/// parent, after the spans of the nodes before it. Applications must have at
+/// least one argument, and names and literals must match the source text at
+/// their span. Violations are reported in the order they are found.
+pub fn validate(module: &Module, source: &str) -> Vec<InvariantError> {
+    let mut invariants = Invariants {
+        source,
+        path: vec![],
+        frames: vec![],
+        errors: vec![],
+    };
+    invariants.visit_module(module);
+    invariants.errors
+}
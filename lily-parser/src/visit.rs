@@ -0,0 +1,411 @@
+//! Traversals over the AST.
+//!
+//! [`Visitor`] and [`VisitorMut`] have a method for every node type, each of
+//! which defaults to the matching `walk_*` function. Implementations only
+//! override the nodes they are interested in, and call the `walk_*` function
+//! from there to keep descending into children.
+
+use crate::types::{
+    CaseArm, Declaration, DeclarationK, DoStatement, DoStatementK, Expression, ExpressionK, Fixity,
+    GreaterPattern, GreaterPatternK, Import, ImportItem, ImportList, ImportListK, LesserPattern,
+    Module, Ty, TyK,
+};
+
+pub trait Visitor<'ast> {
+    fn visit_module(&mut self, module: &'ast Module) {
+        walk_module(self, module);
+    }
+
+    fn visit_import(&mut self, import: &'ast Import) {
+        walk_import(self, import);
+    }
+
+    fn visit_import_list(&mut self, list: &'ast ImportList) {
+        walk_import_list(self, list);
+    }
+
+    fn visit_import_item(&mut self, _item: &'ast ImportItem) {}
+
+    fn visit_fixity(&mut self, _fixity: &'ast Fixity) {}
+
+    fn visit_declaration(&mut self, declaration: &'ast Declaration) {
+        walk_declaration(self, declaration);
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_case_arm(&mut self, arm: &'ast CaseArm) {
+        walk_case_arm(self, arm);
+    }
+
+    fn visit_do_statement(&mut self, statement: &'ast DoStatement) {
+        walk_do_statement(self, statement);
+    }
+
+    fn visit_lesser_pattern(&mut self, _pattern: &'ast LesserPattern) {}
+
+    fn visit_greater_pattern(&mut self, pattern: &'ast GreaterPattern) {
+        walk_greater_pattern(self, pattern);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        walk_ty(self, ty);
+    }
+}
+
+pub fn walk_module<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, module: &'ast Module) {
+    for import in &module.imports {
+        visitor.visit_import(import);
+    }
+    for fixity in &module.fixities {
+        visitor.visit_fixity(fixity);
+    }
+    for declaration in &module.declarations {
+        visitor.visit_declaration(declaration);
+    }
+}
+
+pub fn walk_import<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, import: &'ast Import) {
+    if let Some(list) = &import.list {
+        visitor.visit_import_list(list);
+    }
+}
+
+pub fn walk_import_list<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, list: &'ast ImportList) {
+    let (ImportListK::Hiding(items) | ImportListK::Only(items)) = &list.kind;
+    for item in items {
+        visitor.visit_import_item(item);
+    }
+}
+
+pub fn walk_declaration<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declaration: &'ast Declaration,
+) {
+    match &declaration.kind {
+        DeclarationK::ValueDeclaration(_, patterns, expression) => {
+            for pattern in patterns {
+                visitor.visit_lesser_pattern(pattern);
+            }
+            visitor.visit_expression(expression);
+        }
+        DeclarationK::TypeDeclaration(_, ty)
+        | DeclarationK::ForeignValue(_, ty)
+        | DeclarationK::ForeignData(_, ty) => visitor.visit_ty(ty),
+        DeclarationK::Error => (),
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    expression: &'ast Expression,
+) {
+    match &expression.kind {
+        ExpressionK::Application(function, arguments) => {
+            visitor.visit_expression(function);
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionK::BinaryOperator(left, _, right) => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        ExpressionK::CaseOf(expressions, arms) => {
+            for expression in expressions {
+                visitor.visit_expression(expression);
+            }
+            for arm in arms {
+                visitor.visit_case_arm(arm);
+            }
+        }
+        ExpressionK::DoBlock(statements) => {
+            for statement in statements {
+                visitor.visit_do_statement(statement);
+            }
+        }
+        ExpressionK::IfThenElse(condition, then_value, else_value) => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then_value);
+            visitor.visit_expression(else_value);
+        }
+        ExpressionK::Let(declarations, expression) => {
+            for declaration in declarations {
+                visitor.visit_declaration(declaration);
+            }
+            visitor.visit_expression(expression);
+        }
+        ExpressionK::Parenthesized(expression) => visitor.visit_expression(expression),
+        ExpressionK::Constructor(_)
+        | ExpressionK::Float(_)
+        | ExpressionK::Integer(_)
+        | ExpressionK::Variable(_) => (),
+    }
+}
+
+pub fn walk_case_arm<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, arm: &'ast CaseArm) {
+    for pattern in &arm.patterns {
+        visitor.visit_greater_pattern(pattern);
+    }
+    if let Some(condition) = &arm.condition {
+        visitor.visit_expression(condition);
+    }
+    visitor.visit_expression(&arm.expression);
+}
+
+pub fn walk_do_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    statement: &'ast DoStatement,
+) {
+    match &statement.kind {
+        DoStatementK::BindExpression(pattern, expression) => {
+            visitor.visit_lesser_pattern(pattern);
+            visitor.visit_expression(expression);
+        }
+        DoStatementK::DiscardExpression(expression) => visitor.visit_expression(expression),
+        DoStatementK::LetStatement(declarations) => {
+            for declaration in declarations {
+                visitor.visit_declaration(declaration);
+            }
+        }
+    }
+}
+
+pub fn walk_greater_pattern<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    pattern: &'ast GreaterPattern,
+) {
+    match &pattern.kind {
+        GreaterPatternK::Application(function, arguments) => {
+            visitor.visit_greater_pattern(function);
+            for argument in arguments {
+                visitor.visit_greater_pattern(argument);
+            }
+        }
+        GreaterPatternK::BinaryOperator(left, _, right) => {
+            visitor.visit_greater_pattern(left);
+            visitor.visit_greater_pattern(right);
+        }
+        GreaterPatternK::Parenthesized(pattern) => visitor.visit_greater_pattern(pattern),
+        GreaterPatternK::Constructor(_)
+        | GreaterPatternK::Integer(_)
+        | GreaterPatternK::Null
+        | GreaterPatternK::Variable(_) => (),
+    }
+}
+
+pub fn walk_ty<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, ty: &'ast Ty) {
+    match &ty.kind {
+        TyK::Application(function, arguments) => {
+            visitor.visit_ty(function);
+            for argument in arguments {
+                visitor.visit_ty(argument);
+            }
+        }
+        TyK::BinaryOperator(left, _, right) => {
+            visitor.visit_ty(left);
+            visitor.visit_ty(right);
+        }
+        TyK::Parenthesized(ty) => visitor.visit_ty(ty),
+        TyK::Constructor(_) | TyK::Variable(_) => (),
+    }
+}
+
+/// Like [`Visitor`], but with mutable access to every node.
+pub trait VisitorMut {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module);
+    }
+
+    fn visit_import_mut(&mut self, import: &mut Import) {
+        walk_import_mut(self, import);
+    }
+
+    fn visit_import_list_mut(&mut self, list: &mut ImportList) {
+        walk_import_list_mut(self, list);
+    }
+
+    fn visit_import_item_mut(&mut self, _item: &mut ImportItem) {}
+
+    fn visit_fixity_mut(&mut self, _fixity: &mut Fixity) {}
+
+    fn visit_declaration_mut(&mut self, declaration: &mut Declaration) {
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_case_arm_mut(&mut self, arm: &mut CaseArm) {
+        walk_case_arm_mut(self, arm);
+    }
+
+    fn visit_do_statement_mut(&mut self, statement: &mut DoStatement) {
+        walk_do_statement_mut(self, statement);
+    }
+
+    fn visit_lesser_pattern_mut(&mut self, _pattern: &mut LesserPattern) {}
+
+    fn visit_greater_pattern_mut(&mut self, pattern: &mut GreaterPattern) {
+        walk_greater_pattern_mut(self, pattern);
+    }
+
+    fn visit_ty_mut(&mut self, ty: &mut Ty) {
+        walk_ty_mut(self, ty);
+    }
+}
+
+pub fn walk_module_mut<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut Module) {
+    for import in &mut module.imports {
+        visitor.visit_import_mut(import);
+    }
+    for fixity in &mut module.fixities {
+        visitor.visit_fixity_mut(fixity);
+    }
+    for declaration in &mut module.declarations {
+        visitor.visit_declaration_mut(declaration);
+    }
+}
+
+pub fn walk_import_mut<V: VisitorMut + ?Sized>(visitor: &mut V, import: &mut Import) {
+    if let Some(list) = &mut import.list {
+        visitor.visit_import_list_mut(list);
+    }
+}
+
+pub fn walk_import_list_mut<V: VisitorMut + ?Sized>(visitor: &mut V, list: &mut ImportList) {
+    let (ImportListK::Hiding(items) | ImportListK::Only(items)) = &mut list.kind;
+    for item in items {
+        visitor.visit_import_item_mut(item);
+    }
+}
+
+pub fn walk_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut Declaration,
+) {
+    match &mut declaration.kind {
+        DeclarationK::ValueDeclaration(_, patterns, expression) => {
+            for pattern in patterns {
+                visitor.visit_lesser_pattern_mut(pattern);
+            }
+            visitor.visit_expression_mut(expression);
+        }
+        DeclarationK::TypeDeclaration(_, ty)
+        | DeclarationK::ForeignValue(_, ty)
+        | DeclarationK::ForeignData(_, ty) => visitor.visit_ty_mut(ty),
+        DeclarationK::Error => (),
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match &mut expression.kind {
+        ExpressionK::Application(function, arguments) => {
+            visitor.visit_expression_mut(function);
+            for argument in arguments {
+                visitor.visit_expression_mut(argument);
+            }
+        }
+        ExpressionK::BinaryOperator(left, _, right) => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_expression_mut(right);
+        }
+        ExpressionK::CaseOf(expressions, arms) => {
+            for expression in expressions {
+                visitor.visit_expression_mut(expression);
+            }
+            for arm in arms {
+                visitor.visit_case_arm_mut(arm);
+            }
+        }
+        ExpressionK::DoBlock(statements) => {
+            for statement in statements {
+                visitor.visit_do_statement_mut(statement);
+            }
+        }
+        ExpressionK::IfThenElse(condition, then_value, else_value) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_expression_mut(then_value);
+            visitor.visit_expression_mut(else_value);
+        }
+        ExpressionK::Let(declarations, expression) => {
+            for declaration in declarations {
+                visitor.visit_declaration_mut(declaration);
+            }
+            visitor.visit_expression_mut(expression);
+        }
+        ExpressionK::Parenthesized(expression) => visitor.visit_expression_mut(expression),
+        ExpressionK::Constructor(_)
+        | ExpressionK::Float(_)
+        | ExpressionK::Integer(_)
+        | ExpressionK::Variable(_) => (),
+    }
+}
+
+pub fn walk_case_arm_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arm: &mut CaseArm) {
+    for pattern in &mut arm.patterns {
+        visitor.visit_greater_pattern_mut(pattern);
+    }
+    if let Some(condition) = &mut arm.condition {
+        visitor.visit_expression_mut(condition);
+    }
+    visitor.visit_expression_mut(&mut arm.expression);
+}
+
+pub fn walk_do_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut DoStatement) {
+    match &mut statement.kind {
+        DoStatementK::BindExpression(pattern, expression) => {
+            visitor.visit_lesser_pattern_mut(pattern);
+            visitor.visit_expression_mut(expression);
+        }
+        DoStatementK::DiscardExpression(expression) => visitor.visit_expression_mut(expression),
+        DoStatementK::LetStatement(declarations) => {
+            for declaration in declarations {
+                visitor.visit_declaration_mut(declaration);
+            }
+        }
+    }
+}
+
+pub fn walk_greater_pattern_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    pattern: &mut GreaterPattern,
+) {
+    match &mut pattern.kind {
+        GreaterPatternK::Application(function, arguments) => {
+            visitor.visit_greater_pattern_mut(function);
+            for argument in arguments {
+                visitor.visit_greater_pattern_mut(argument);
+            }
+        }
+        GreaterPatternK::BinaryOperator(left, _, right) => {
+            visitor.visit_greater_pattern_mut(left);
+            visitor.visit_greater_pattern_mut(right);
+        }
+        GreaterPatternK::Parenthesized(pattern) => visitor.visit_greater_pattern_mut(pattern),
+        GreaterPatternK::Constructor(_)
+        | GreaterPatternK::Integer(_)
+        | GreaterPatternK::Null
+        | GreaterPatternK::Variable(_) => (),
+    }
+}
+
+pub fn walk_ty_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ty: &mut Ty) {
+    match &mut ty.kind {
+        TyK::Application(function, arguments) => {
+            visitor.visit_ty_mut(function);
+            for argument in arguments {
+                visitor.visit_ty_mut(argument);
+            }
+        }
+        TyK::BinaryOperator(left, _, right) => {
+            visitor.visit_ty_mut(left);
+            visitor.visit_ty_mut(right);
+        }
+        TyK::Parenthesized(ty) => visitor.visit_ty_mut(ty),
+        TyK::Constructor(_) | TyK::Variable(_) => (),
+    }
+}
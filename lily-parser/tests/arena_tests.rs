@@ -0,0 +1,299 @@
+use lily_lexer::symbol::Symbol;
+use lily_parser::{
+    arena::{
+        self, ArenaMap, DeclarationId, ExpressionId, ExpressionK, GreaterPatternId,
+        GreaterPatternK, TyId, TyK,
+    },
+    parse_top_level, types,
+    visit::{walk_expression, walk_greater_pattern, walk_ty, Visitor},
+};
+
+const SOURCE: &str = r"
+infixl 4 add as +
+infixr 9 type Function as ->
+
+-- | Picks a value.
+example : Maybe Int -> Int
+example x = case x of
+  Just y if positive y -> y + offset
+  Nothing -> do
+    z <- pure (offset + 1)
+    let
+      w = if z then z else 0
+    pure w
+
+other = let a = 1.5 in Cons a Nil
+";
+
+/// Rebuilds the boxed tree from the arenas, to check that lowering keeps
+/// every node.
+struct Raise<'a>(&'a arena::Module);
+
+impl Raise<'_> {
+    fn declaration(&self, id: DeclarationId) -> types::Declaration {
+        let declaration = &self.0[id];
+        let kind = match &declaration.kind {
+            arena::DeclarationK::ValueDeclaration(name, patterns, expression) => {
+                let expression = self.expression(*expression);
+                types::DeclarationK::ValueDeclaration(
+                    name.as_str().into(),
+                    patterns.clone(),
+                    expression,
+                )
+            }
+            arena::DeclarationK::TypeDeclaration(name, ty) => {
+                types::DeclarationK::TypeDeclaration(name.as_str().into(), self.ty(*ty))
+            }
+            arena::DeclarationK::ForeignValue(name, ty) => {
+                types::DeclarationK::ForeignValue(name.as_str().into(), self.ty(*ty))
+            }
+            arena::DeclarationK::ForeignData(name, ty) => {
+                types::DeclarationK::ForeignData(name.as_str().into(), self.ty(*ty))
+            }
+            arena::DeclarationK::Error => types::DeclarationK::Error,
+        };
+        types::Declaration {
+            begin: declaration.begin,
+            end: declaration.end,
+            docs: declaration.docs.clone(),
+            kind,
+        }
+    }
+
+    fn expressions(&self, ids: &[ExpressionId]) -> Vec<types::Expression> {
+        ids.iter().map(|id| self.expression(*id)).collect()
+    }
+
+    fn expression(&self, id: ExpressionId) -> types::Expression {
+        let expression = &self.0[id];
+        let boxed = |id| Box::new(self.expression(id));
+        let kind = match &expression.kind {
+            ExpressionK::Application(function, arguments) => {
+                types::ExpressionK::Application(boxed(*function), self.expressions(arguments))
+            }
+            ExpressionK::BinaryOperator(left, operator, right) => {
+                types::ExpressionK::BinaryOperator(
+                    boxed(*left),
+                    operator.as_str().into(),
+                    boxed(*right),
+                )
+            }
+            ExpressionK::CaseOf(expressions, arms) => types::ExpressionK::CaseOf(
+                self.expressions(expressions),
+                arms.iter()
+                    .map(|arm| types::CaseArm {
+                        patterns: arm.patterns.iter().map(|id| self.pattern(*id)).collect(),
+                        condition: arm.condition.map(|id| self.expression(id)),
+                        expression: self.expression(arm.expression),
+                    })
+                    .collect(),
+            ),
+            ExpressionK::Constructor(name) => types::ExpressionK::Constructor(name.as_str().into()),
+            ExpressionK::DoBlock(statements) => types::ExpressionK::DoBlock(
+                statements
+                    .iter()
+                    .map(|statement| types::DoStatement {
+                        begin: statement.begin,
+                        end: statement.end,
+                        kind: match &statement.kind {
+                            arena::DoStatementK::BindExpression(pattern, expression) => {
+                                types::DoStatementK::BindExpression(
+                                    pattern.clone(),
+                                    self.expression(*expression),
+                                )
+                            }
+                            arena::DoStatementK::DiscardExpression(expression) => {
+                                types::DoStatementK::DiscardExpression(self.expression(*expression))
+                            }
+                            arena::DoStatementK::LetStatement(declarations) => {
+                                types::DoStatementK::LetStatement(
+                                    declarations
+                                        .iter()
+                                        .map(|id| self.declaration(*id))
+                                        .collect(),
+                                )
+                            }
+                        },
+                    })
+                    .collect(),
+            ),
+            ExpressionK::Float(value) => types::ExpressionK::Float(value.clone()),
+            ExpressionK::IfThenElse(condition, then, otherwise) => {
+                types::ExpressionK::IfThenElse(boxed(*condition), boxed(*then), boxed(*otherwise))
+            }
+            ExpressionK::Integer(value) => types::ExpressionK::Integer(value.clone()),
+            ExpressionK::Let(declarations, expression) => types::ExpressionK::Let(
+                declarations
+                    .iter()
+                    .map(|id| self.declaration(*id))
+                    .collect(),
+                boxed(*expression),
+            ),
+            ExpressionK::Parenthesized(expression) => {
+                types::ExpressionK::Parenthesized(boxed(*expression))
+            }
+            ExpressionK::Variable(name) => types::ExpressionK::Variable(name.as_str().into()),
+        };
+        types::Expression {
+            begin: expression.begin,
+            end: expression.end,
+            kind,
+        }
+    }
+
+    fn pattern(&self, id: GreaterPatternId) -> types::GreaterPattern {
+        let pattern = &self.0[id];
+        let boxed = |id| Box::new(self.pattern(id));
+        let kind = match &pattern.kind {
+            GreaterPatternK::Application(function, arguments) => {
+                types::GreaterPatternK::Application(
+                    boxed(*function),
+                    arguments.iter().map(|id| self.pattern(*id)).collect(),
+                )
+            }
+            GreaterPatternK::BinaryOperator(left, operator, right) => {
+                types::GreaterPatternK::BinaryOperator(
+                    boxed(*left),
+                    operator.as_str().into(),
+                    boxed(*right),
+                )
+            }
+            GreaterPatternK::Constructor(name) => {
+                types::GreaterPatternK::Constructor(name.as_str().into())
+            }
+            GreaterPatternK::Integer(value) => types::GreaterPatternK::Integer(value.clone()),
+            GreaterPatternK::Null => types::GreaterPatternK::Null,
+            GreaterPatternK::Parenthesized(pattern) => {
+                types::GreaterPatternK::Parenthesized(boxed(*pattern))
+            }
+            GreaterPatternK::Variable(name) => {
+                types::GreaterPatternK::Variable(name.as_str().into())
+            }
+        };
+        types::GreaterPattern {
+            begin: pattern.begin,
+            end: pattern.end,
+            kind,
+        }
+    }
+
+    fn ty(&self, id: TyId) -> types::Ty {
+        let ty = &self.0[id];
+        let boxed = |id| Box::new(self.ty(id));
+        let kind = match &ty.kind {
+            TyK::Application(function, arguments) => types::TyK::Application(
+                boxed(*function),
+                arguments.iter().map(|id| self.ty(*id)).collect(),
+            ),
+            TyK::BinaryOperator(left, operator, right) => {
+                types::TyK::BinaryOperator(boxed(*left), operator.as_str().into(), boxed(*right))
+            }
+            TyK::Constructor(name) => types::TyK::Constructor(name.as_str().into()),
+            TyK::Parenthesized(ty) => types::TyK::Parenthesized(boxed(*ty)),
+            TyK::Variable(name) => types::TyK::Variable(name.as_str().into()),
+        };
+        types::Ty {
+            begin: ty.begin,
+            end: ty.end,
+            kind,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Count {
+    expressions: usize,
+    greater_patterns: usize,
+    types: usize,
+}
+
+impl<'ast> Visitor<'ast> for Count {
+    fn visit_expression(&mut self, expression: &'ast types::Expression) {
+        self.expressions += 1;
+        walk_expression(self, expression);
+    }
+
+    fn visit_greater_pattern(&mut self, pattern: &'ast types::GreaterPattern) {
+        self.greater_patterns += 1;
+        walk_greater_pattern(self, pattern);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast types::Ty) {
+        self.types += 1;
+        walk_ty(self, ty);
+    }
+}
+
+#[test]
+fn lowering() {
+    let (module, errors) = parse_top_level(SOURCE);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let lowered = arena::lower(&module);
+
+    let mut count = Count::default();
+    count.visit_module(&module);
+    assert_eq!(lowered.arenas.expressions.len(), count.expressions);
+    assert_eq!(
+        lowered.arenas.greater_patterns.len(),
+        count.greater_patterns
+    );
+    assert_eq!(lowered.arenas.types.len(), count.types);
+    // The two top-level declarations, along with `w` and `a`.
+    assert_eq!(lowered.arenas.declarations.len(), 5);
+
+    let raise = Raise(&lowered);
+    let declarations: Vec<_> = lowered
+        .declarations
+        .iter()
+        .map(|id| raise.declaration(*id))
+        .collect();
+    assert_eq!(declarations, module.declarations);
+    assert_eq!(lowered.imports, module.imports);
+    assert_eq!(lowered.fixities, module.fixities);
+}
+
+#[test]
+fn children_come_first() {
+    let (module, _) = parse_top_level(SOURCE);
+    let lowered = arena::lower(&module);
+
+    for (id, expression) in lowered.arenas.expressions.iter() {
+        let children = match &expression.kind {
+            ExpressionK::Application(function, arguments) => std::iter::once(*function)
+                .chain(arguments.iter().copied())
+                .collect(),
+            ExpressionK::BinaryOperator(left, _, right) => vec![*left, *right],
+            ExpressionK::IfThenElse(condition, then, otherwise) => {
+                vec![*condition, *then, *otherwise]
+            }
+            ExpressionK::Parenthesized(expression) => vec![*expression],
+            _ => vec![],
+        };
+        assert!(children.iter().all(|child| *child < id), "{:?}", expression);
+    }
+}
+
+#[test]
+fn side_tables() {
+    let (module, _) = parse_top_level(SOURCE);
+    let lowered = arena::lower(&module);
+
+    let mut names = ArenaMap::new();
+    for (id, expression) in lowered.arenas.expressions.iter() {
+        if let ExpressionK::Variable(name) = &expression.kind {
+            names.insert(id, *name);
+        }
+    }
+    let variables: Vec<&str> = names.iter().map(|(_, name)| name.as_str()).collect();
+    assert_eq!(
+        variables,
+        ["x", "positive", "y", "y", "offset", "pure", "offset", "z", "z", "pure", "w", "a"]
+    );
+
+    let (first, _) = names.iter().next().unwrap();
+    let x = Symbol::intern("x");
+    assert_eq!(names[first], x);
+    assert_eq!(names.remove(first), Some(x));
+    assert!(!names.contains(first));
+    assert_eq!(lowered[first].kind, ExpressionK::Variable(x));
+}
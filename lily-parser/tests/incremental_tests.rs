@@ -0,0 +1,204 @@
+use lily_lexer::indentation::Tabs;
+use lily_parser::{
+    incremental::{Document, Edit},
+    parse_top_level, parse_top_level_with_tabs,
+    validate::validate,
+};
+use proptest::prelude::*;
+
+const FRAGMENTS: &[&str] = &[
+    "foo = 1\n",
+    "  bar\n",
+    "infixl 4 add as +\n",
+    "infixr 4 add as +\n",
+    "a + b",
+    "do\n",
+    "-- c\n",
+    "-- | d\n",
+    "{- ",
+    " -}",
+    "case x of\n  y -> z\n",
+    "import A\n",
+    "if a then b else c",
+    "字",
+    "1.",
+    "\n",
+    " ",
+    "(",
+    ")",
+];
+
+fn assert_same(document: &Document) {
+    let fresh = Document::new(document.source());
+    assert_eq!(document.tokens(), fresh.tokens());
+    assert_eq!(document.module(), fresh.module());
+    assert_eq!(document.errors(), fresh.errors());
+    assert_eq!(validate(document.module(), document.source()), vec![]);
+}
+
+fn source() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(FRAGMENTS), 0..12).prop_map(|parts| parts.concat())
+}
+
+fn edit(source: &str) -> impl Strategy<Value = Edit> {
+    let boundaries: Vec<_> = source
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain([source.len()])
+        .collect();
+    let text = prop::collection::vec(prop::sample::select(FRAGMENTS), 0..3);
+    (
+        prop::sample::select(boundaries.clone()),
+        prop::sample::select(boundaries),
+        text,
+    )
+        .prop_map(|(a, b, text)| Edit {
+            begin: a.min(b),
+            end: a.max(b),
+            text: text.concat(),
+        })
+}
+
+fn source_and_edits() -> impl Strategy<Value = (String, Vec<Edit>)> {
+    source().prop_flat_map(|source| {
+        // Later edits are generated against the original source and clamped
+        // to the current one when applied.
+        let edits = prop::collection::vec(edit(&source), 1..4);
+        (Just(source), edits)
+    })
+}
+
+fn clamp(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+proptest! {
+    #[test]
+    fn edits_match_fresh_parse((source, edits) in source_and_edits()) {
+        let mut document = Document::new(source);
+        for edit in edits {
+            let edit = Edit {
+                begin: clamp(document.source(), edit.begin),
+                end: clamp(document.source(), edit.end),
+                text: edit.text,
+            };
+            document.edit(&edit);
+            assert_same(&document);
+        }
+    }
+
+    #[test]
+    fn document_matches_parse_top_level(source in source()) {
+        let document = Document::new(source.as_str());
+        let (module, errors) = parse_top_level(&source);
+        prop_assert_eq!(document.module(), &module);
+        prop_assert_eq!(document.errors(), &errors[..]);
+        prop_assert_eq!(validate(&module, &source), vec![]);
+    }
+}
+
+#[test]
+fn edit_inside_declaration() {
+    let source = "first = 1\n\nsecond = a + b\n\nthird = 3\n";
+    let mut document = Document::new(source);
+    let begin = source.find("a + b").unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin + 1,
+        text: "f x".into(),
+    });
+    assert_eq!(
+        document.source(),
+        "first = 1\n\nsecond = f x + b\n\nthird = 3\n"
+    );
+    assert_same(&document);
+}
+
+#[test]
+fn edit_parses_affected_groups() {
+    let source = "first = 1\n\nsecond = a + b\n\nthird = 3\n\nfourth = 4\n";
+    let mut document = Document::new(source);
+    assert_eq!(document.parsed_groups(), 4);
+
+    let begin = source.find("a + b").unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin + 1,
+        text: "f x".into(),
+    });
+    assert_same(&document);
+    assert_eq!(document.parsed_groups(), 1);
+
+    // Splitting a group in two parses both halves.
+    let begin = document.source().find("third").unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin,
+        text: "between = 2\n\n".into(),
+    });
+    assert_same(&document);
+    assert_eq!(document.parsed_groups(), 2);
+}
+
+#[test]
+fn edit_fixity_declaration() {
+    let source = "infixl 4 add as +\ninfixl 5 mul as *\n\nexample = a * b + c\n";
+    let mut document = Document::new(source);
+    let begin = source.find('5').unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin + 1,
+        text: "3".into(),
+    });
+    assert_same(&document);
+    // Declarations depend on every fixity, so they are all parsed again.
+    assert_eq!(document.parsed_groups(), 2);
+}
+
+#[test]
+fn edit_opens_comment() {
+    let source = "first = 1\n\nsecond = 2\n\nthird = 3\n";
+    let mut document = Document::new(source);
+    let begin = source.find("second").unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin,
+        text: "{- ".into(),
+    });
+    assert_same(&document);
+    assert!(!document.errors().is_empty());
+
+    let end = document.source().len();
+    document.edit(&Edit {
+        begin: end,
+        end,
+        text: " -}".into(),
+    });
+    assert_same(&document);
+    assert!(document.errors().is_empty());
+}
+
+#[test]
+fn edit_with_tabs() {
+    let source = "first = do\n\ta\n\tb\n\nsecond = 2\n";
+    let mut document = Document::with_tabs(source, Tabs::Expand(4));
+    let begin = source.find("\tb").unwrap();
+    document.edit(&Edit {
+        begin,
+        end: begin,
+        text: "\tc\n".into(),
+    });
+    assert_eq!(
+        document.source(),
+        "first = do\n\ta\n\tc\n\tb\n\nsecond = 2\n"
+    );
+
+    let (module, errors) = parse_top_level_with_tabs(document.source(), Tabs::Expand(4));
+    assert_eq!(document.module(), &module);
+    assert_eq!(document.errors(), &errors[..]);
+    assert_eq!(errors, vec![]);
+}
@@ -1,4 +1,16 @@
-use lily_parser::parse_top_level;
+use lily_lexer::{
+    diagnostics::Renderer,
+    errors::{LexError, LexErrorK},
+    indentation::Tabs,
+    types::{IdentifierK, QualifiedK, TokenK},
+};
+use lily_parser::{
+    errors::ParseError,
+    foreign::ForeignRegistry,
+    parse_imports, parse_imports_with_tabs, parse_top_level, parse_top_level_with_tabs,
+    types::{DeclarationK, ExpressionK},
+    validate::{validate, validate_fixities},
+};
 
 #[test]
 pub fn top_level_0() {
@@ -122,3 +134,498 @@ example : A a -> B -> C -> D (d e)
 ";
     insta::assert_debug_snapshot!(parse_top_level(source));
 }
+
+#[test]
+fn top_level_11() {
+    let source = "
+import Data.List (map, filter)
+import Data.Map as Map
+import Data.Maybe (Maybe(..), maybe, (<|>))
+import Prelude hiding (map)
+import Data.Set qualified as S
+
+example = map f xs
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn contextual_import_keywords() {
+    let source = "
+import Prelude hiding (map)
+import Data.Set qualified as S
+
+hiding = 1
+qualified x = x
+f hiding = hiding
+";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+    assert!(module.imports[0].list.is_some());
+    assert!(module.imports[1].qualified);
+    let names: Vec<_> = module
+        .declarations
+        .iter()
+        .map(|declaration| match &declaration.kind {
+            DeclarationK::ValueDeclaration(name, _, _) => name.as_str(),
+            kind => panic!("unexpected declaration {:?}", kind),
+        })
+        .collect();
+    assert_eq!(names, ["hiding", "qualified", "f"]);
+}
+
+#[test]
+fn top_level_12() {
+    let source = "
+example = a
+
+import Data.List (map)
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn top_level_13() {
+    let source = "
+import Data.Map qualified as Map
+
+infixl 6 add as +
+
+example = Map.insert k (Maybe.Just v) m Map.+ Map.empty
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn top_level_14() {
+    let source = "
+infixr 9 type Function as ->
+
+foreign import data Ref : Type -> Type
+
+foreign import readFile : String -> Effect String
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn foreign_registry() {
+    let source = "
+infixr 9 type Function as ->
+
+foreign import readFile : String -> Effect String
+
+foreign import writeFile : String -> String -> Effect Unit
+";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+
+    let mut registry: ForeignRegistry<fn(&str) -> String> = ForeignRegistry::new();
+    registry.register("readFile", |path| format!("contents of {}", path));
+
+    assert_eq!(
+        registry.get("readFile").unwrap()("a.txt"),
+        "contents of a.txt"
+    );
+    let unimplemented: Vec<_> = registry
+        .unimplemented(&module)
+        .into_iter()
+        .map(|declaration| match &declaration.kind {
+            DeclarationK::ForeignValue(name, _) => name.as_str(),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(unimplemented, vec!["writeFile"]);
+}
+
+#[test]
+fn top_level_15() {
+    let source = "
+infix 4 eq as ==
+infixl 6 add as +
+
+example = a + b == c + d
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn top_level_16() {
+    let source = "
+infix 4 eq as ==
+infix 4 lt as <=
+
+example = a == b <= c
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn top_level_17() {
+    let source = "
+infix 4 eq as ==
+infixr 4 cons as :|
+
+example = a :| b == c
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn fixity_validation_0() {
+    let source = "
+import Data.Function (apply)
+import Prelude hiding (sub)
+import Data.Map qualified as Map
+
+infixl 6 add as +
+infixl 6 add as +
+infixl 6 sub as -
+infixr 0 apply as $
+infixl 254 mul as *
+infixl 1 bind as >>=
+infix 4 eq as =
+infixr 9 type Function as ->
+infixr 9 arrow as ->
+
+add a b = a
+";
+    let (mut module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+    // The parser rejects binding powers that are out of range, but a module
+    // can also be built by hand.
+    let mul = module
+        .fixities
+        .iter_mut()
+        .find(|fixity| fixity.identifier.as_str() == "mul")
+        .unwrap();
+    mul.binding_power = 255;
+    insta::assert_debug_snapshot!(validate_fixities(&module));
+}
+
+#[test]
+fn fixity_validation_1() {
+    let source = "
+infixl 6 add as +
+
+add a b = a
+";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+    assert_eq!(validate_fixities(&module), vec![]);
+}
+
+#[test]
+fn fixity_validation_2() {
+    let source = "infixr 9 type Function as ->\n\nid : a -> a\nid x = x\n";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+    assert_eq!(validate_fixities(&module), vec![]);
+}
+
+#[test]
+fn top_level_18() {
+    let source = "
+infixr 9 type Function as ->
+
+example : Int -> )
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn typed_parse_error() {
+    for binding_power in ["255", "300"] {
+        let source = format!("\ninfixl {} add as +\n", binding_power);
+        match &parse_top_level(&source).1[..] {
+            [ParseError::InvalidBindingPower { token }] => {
+                assert_eq!(&source[token.begin..token.end], binding_power);
+            }
+            errors => panic!("Expected an invalid binding power, got {:?}", errors),
+        }
+    }
+}
+
+#[test]
+fn do_statement_error() {
+    let source = "main = do\n  x <- )\n";
+    match &parse_top_level(source).1[..] {
+        [ParseError::UnexpectedToken { token, expected }] => {
+            assert_eq!(&source[token.begin..token.end], ")");
+            for kind in [
+                TokenK::Qualified(QualifiedK::Lower),
+                TokenK::Qualified(QualifiedK::Upper),
+                TokenK::Identifier(IdentifierK::Case),
+                TokenK::Identifier(IdentifierK::Let),
+            ] {
+                assert!(expected.contains(&kind), "{:?} is not expected", kind);
+            }
+        }
+        errors => panic!("Expected an unexpected token, got {:?}", errors),
+    }
+}
+
+#[test]
+fn top_level_recovery() {
+    let source = "
+infixl 6 add as +
+infixl x add as -
+
+first = a + b
+
+second = ) a
+
+import Data.List (map)
+
+third = c + d
+";
+    insta::assert_debug_snapshot!(parse_top_level(source));
+}
+
+#[test]
+fn parse_error_diagnostics() {
+    let source = "
+infix 4 eq as ==
+
+example = a == b == c
+
+other = )
+";
+    let (_, errors) = parse_top_level(source);
+    let renderer = Renderer::new("Main.lily", source);
+    let rendered: Vec<_> = errors
+        .iter()
+        .map(|error| renderer.render(&error.to_diagnostic()))
+        .collect();
+    insta::assert_snapshot!(rendered.join("\n"));
+}
+
+#[test]
+fn lexical_error_recovery() {
+    let source = "
+first = 1. + 2
+
+second = a 字 b
+
+third = c {- unterminated
+";
+    let (module, errors) = parse_top_level(source);
+    let kinds: Vec<_> = module
+        .declarations
+        .iter()
+        .map(|declaration| &declaration.kind)
+        .collect();
+    assert!(matches!(
+        kinds[..],
+        [
+            DeclarationK::Error,
+            DeclarationK::Error,
+            DeclarationK::Error
+        ]
+    ));
+    let renderer = Renderer::new("Main.lily", source);
+    let rendered: Vec<_> = errors
+        .iter()
+        .map(|error| renderer.render(&error.to_diagnostic()))
+        .collect();
+    insta::assert_snapshot!(rendered.join("\n"));
+}
+
+#[test]
+fn tab_error_recovery() {
+    let source = "import\tPrelude\n\nfirst = do\n\tpure 1\n\nsecond = do\n  a\n\t(\n";
+    let tab = |begin| {
+        ParseError::Lexical(LexError {
+            begin,
+            end: begin + 1,
+            kind: LexErrorK::Tab,
+        })
+    };
+
+    let (module, errors) = parse_top_level(source);
+    let kinds: Vec<_> = module
+        .declarations
+        .iter()
+        .map(|declaration| &declaration.kind)
+        .collect();
+    // Only the group that fails to parse is an error, and it only reports
+    // the tab that caused it.
+    assert!(matches!(
+        kinds[..],
+        [DeclarationK::ValueDeclaration(..), DeclarationK::Error]
+    ));
+    assert_eq!(errors, vec![tab(6), tab(27), tab(52)]);
+
+    let (imports, errors) = parse_imports(source);
+    assert_eq!(imports.len(), 1);
+    assert_eq!(errors, vec![tab(6)]);
+}
+
+#[test]
+fn tab_expansion() {
+    let source = "main = do\n\tfoo\n\tbar\n";
+    let (module, errors) = parse_top_level_with_tabs(source, Tabs::Expand(4));
+    assert_eq!(errors, vec![]);
+    let [declaration] = &module.declarations[..] else {
+        panic!("expected one declaration, got {:?}", module.declarations);
+    };
+    let DeclarationK::ValueDeclaration(_, _, expression) = &declaration.kind else {
+        panic!("expected a value declaration, got {:?}", declaration.kind);
+    };
+    assert!(matches!(
+        &expression.kind,
+        ExpressionK::DoBlock(statements) if statements.len() == 2
+    ));
+
+    // The same tabs are rejected by default.
+    let (_, errors) = parse_top_level(source);
+    assert_eq!(errors.len(), 2);
+
+    let (imports, errors) = parse_imports_with_tabs("import\tPrelude\n", Tabs::Expand(4));
+    assert_eq!(imports.len(), 1);
+    assert_eq!(errors, vec![]);
+}
+
+#[test]
+fn doc_comments() {
+    let source = "
+infixr 9 type Function as ->
+
+-- | Adds two numbers.
+--
+-- Uses the built-in addition.
+add : Int -> Int -> Int
+
+-- A plain comment.
+add a b = a
+
+{-| The identity function. -}
+foreign import identity : a -> a
+
+-- | Detached documentation.
+
+-- Not a doc comment.
+plain = 1
+
+{-| Nested {- comments -} are kept. -}
+nested = 2
+";
+    let (module, errors) = parse_top_level(source);
+    assert_eq!(errors, vec![]);
+    let docs: Vec<_> = module
+        .declarations
+        .iter()
+        .map(|declaration| declaration.docs.as_deref())
+        .collect();
+    assert_eq!(
+        docs,
+        vec![
+            Some("Adds two numbers.\n\nUses the built-in addition."),
+            None,
+            Some("The identity function."),
+            None,
+            Some("Nested {- comments -} are kept."),
+        ]
+    );
+}
+
+#[test]
+fn streamed_imports() {
+    let source = r"
+import Data.List (map)
+import Data.Map qualified as Map
+import Broken (
+import Data.Set 字
+
+example = a b
+import Misplaced
+";
+    let (module, errors) = parse_top_level(source);
+    let (imports, import_errors) = parse_imports(source);
+    assert_eq!(imports, module.imports[..2]);
+    assert_eq!(import_errors, errors[..2]);
+
+    // Everything after the imports is left unlexed.
+    let source = "import A\nexample = {- unfinished";
+    assert_eq!(parse_imports(source).0.len(), 1);
+    assert!(parse_imports(source).1.is_empty());
+}
+
+#[test]
+fn declaration_order() {
+    let mut source = String::from("infixl 4 add as +\n");
+    for index in 0..500 {
+        if index % 50 == 7 {
+            source.push_str(&format!("broken{} = )\n", index));
+        } else {
+            source.push_str(&format!("example{} x = x + {}\n", index, index));
+        }
+    }
+    let (module, errors) = parse_top_level(&source);
+    assert_eq!(errors.len(), 10);
+    assert!(errors
+        .windows(2)
+        .all(|pair| pair[0].span() < pair[1].span()));
+    assert_eq!(module.declarations.len(), 500);
+    for (index, declaration) in module.declarations.iter().enumerate() {
+        match &declaration.kind {
+            DeclarationK::ValueDeclaration(name, _, _) => {
+                assert_eq!(name.as_str(), format!("example{}", index));
+            }
+            DeclarationK::Error => assert_eq!(index % 50, 7),
+            kind => panic!("unexpected declaration {:?}", kind),
+        }
+    }
+}
+
+#[test]
+fn structural_invariants() {
+    let source = "
+import Data.Map qualified as Map (insert, Map(..))
+
+infixl 4 add as +
+infixr 9 type Function as ->
+
+example : Map.Map Int -> Int
+example m = case m of
+  Just (Cons a _), b if a -> Map.insert a b + 1
+  _, _ -> do
+    x <- pure 1.5
+    let
+      y = x
+    pure y
+
+broken = )
+";
+    let (mut module, errors) = parse_top_level(source);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(validate(&module, source), vec![]);
+
+    let DeclarationK::ValueDeclaration(_, _, expression) = &mut module.declarations[1].kind else {
+        panic!("expected a value declaration");
+    };
+    let ExpressionK::CaseOf(expressions, arms) = &mut expression.kind else {
+        panic!("expected a case expression");
+    };
+    expressions[0].kind = ExpressionK::Variable("n".into());
+    let condition = arms[0].condition.as_mut().unwrap();
+    condition.begin = condition.end + 1;
+    let ExpressionK::BinaryOperator(left, _, right) = &mut arms[0].expression.kind else {
+        panic!("expected an operator");
+    };
+    right.begin = left.begin;
+    let ExpressionK::Application(_, arguments) = &mut left.kind else {
+        panic!("expected an application");
+    };
+    arguments.clear();
+    let ExpressionK::DoBlock(statements) = &mut arms[1].expression.kind else {
+        panic!("expected a do block");
+    };
+    statements.last_mut().unwrap().end = source.len();
+
+    let errors: Vec<_> = validate(&module, source)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    insta::assert_debug_snapshot!(errors);
+}
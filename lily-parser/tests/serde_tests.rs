@@ -0,0 +1,47 @@
+#![cfg(feature = "serde")]
+
+use lily_parser::{errors::ParseError, parse_top_level, types::Module};
+
+const SOURCE: &str = "
+import Data.Map qualified as Map (Map, insert)
+
+infixl 4 add as +
+infixr 9 type Function as ->
+
+-- | Doubles a number.
+double : Int -> Int
+double x = x + x
+
+example = case a of
+  Just b if b -> do
+    let c = 1
+    d <- pure c
+    pure d
+  _ -> if a then b else let e = 1.5 in e
+
+other = ) a
+";
+
+#[test]
+fn module_round_trip() {
+    let parsed = parse_top_level(SOURCE);
+    assert_eq!(parsed.1.len(), 1);
+
+    let json = serde_json::to_string(&parsed).unwrap();
+    let loaded: (Module, Vec<ParseError>) = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, parsed);
+}
+
+#[test]
+fn module_cache() {
+    let (module, _) = parse_top_level(SOURCE);
+    let path = std::env::temp_dir().join(format!("lily-module-{}.json", std::process::id()));
+
+    let file = std::fs::File::create(&path).unwrap();
+    serde_json::to_writer(std::io::BufWriter::new(file), &module).unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let loaded: Module = serde_json::from_reader(std::io::BufReader::new(file)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, module);
+}
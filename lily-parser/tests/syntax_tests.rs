@@ -0,0 +1,324 @@
+use lily_lexer::indentation::Tabs;
+use lily_parser::{
+    parse_top_level, parse_with_syntax,
+    syntax::{HasSyntax, SyntaxKind, SyntaxTree},
+    types::{
+        Declaration, DeclarationK, DoStatement, Expression, ExpressionK, GreaterPattern, Import,
+        ImportItem, ImportList, LesserPattern, Ty,
+    },
+    visit::{self, Visitor},
+};
+use proptest::prelude::*;
+
+const CORPUS: &[&str] = &[
+    "",
+    "\n\n  \n",
+    "-- only a comment",
+    "{- only a block comment -}\n",
+    r"
+infixl 1 add as +
+infixl 2 mul as *
+infixr 3 pow as ^
+
+example = a + b * (c + d) + e ^ f
+",
+    r"
+import Data.List (map, Maybe(..), (<>))
+import Data.Map qualified as Map hiding (insert)
+
+-- | Documented.
+example a b c = a b c -- trailing
+",
+    "
+example = do
+  let
+    u = 21
+    v = 21
+  w <- pure 21
+  attempt do
+    y <- pure 21
+",
+    "
+example = case a, b of
+  Cons a b, _ if hello -> do
+    world
+  _, Nil -> do
+    example
+",
+    "
+example = f
+  let
+    a = b
+    c = d
+  in
+    a c
+",
+    "
+infixr 9 type Function as ->
+
+identity : forall a. (a -> a) -> Maybe a b
+identity = {- inline -} x
+",
+    "
+foreign import data Effect : Type -> Type
+foreign import log : String -> Effect Unit
+",
+    "example = if a then b else c\r\nother = 1.5\r\n",
+    "
+first = 1. + 2
+
+second = ) a
+
+third = 字 {- unterminated
+",
+    "{- a {- b -} {- c",
+    "example = 1\n{- a {- b -} {- c -}\n",
+    "
+infix 4 eq as ==
+
+example = a == b == c
+import Misplaced
+",
+];
+
+const FRAGMENTS: &[&str] = &[
+    "example",
+    "a",
+    "B",
+    "Data.List",
+    "Map.insert",
+    "=",
+    ":",
+    "+",
+    "*",
+    "->",
+    "<-",
+    "_",
+    "1",
+    "1.5",
+    "(",
+    ")",
+    ",",
+    "..",
+    "do",
+    "let",
+    "in",
+    "case",
+    "of",
+    "if",
+    "then",
+    "else",
+    "import",
+    "qualified",
+    "hiding",
+    "as",
+    "infixl 1 add as +",
+    "foreign",
+    "data",
+    " ",
+    "  ",
+    "\t",
+    "\n",
+    "\r\n",
+    "-- c\n",
+    "-- | d\n",
+    "{- e -}",
+    "{- ",
+    " -}",
+    "字",
+];
+
+/// Checks that the tree reproduces the source, and that every node of the
+/// module has a counterpart in it.
+fn assert_lossless(source: &str) {
+    let (module, tree, _) = parse_with_syntax(source, Tabs::default());
+    let root = tree.root();
+    assert_eq!(root.kind(), SyntaxKind::Module);
+    assert_eq!(root.range(), (0, source.len()));
+    assert_eq!(root.text(), source);
+    assert_eq!(module, parse_top_level(source).0);
+
+    let mut views = Views {
+        tree: &tree,
+        missing: vec![],
+    };
+    views.visit_module(&module);
+    assert!(
+        views.missing.is_empty(),
+        "no nodes for {:?} in {:?}",
+        views.missing,
+        source
+    );
+}
+
+struct Views<'a> {
+    tree: &'a SyntaxTree,
+    missing: Vec<(SyntaxKind, (usize, usize))>,
+}
+
+impl<'a> Views<'a> {
+    fn check(&mut self, node: &impl HasSyntax) {
+        match node.syntax(self.tree) {
+            Some(syntax) => {
+                assert_eq!(syntax.kind(), node.syntax_kind());
+                assert_eq!(syntax.trimmed_range(), node.span());
+            }
+            None => self.missing.push((node.syntax_kind(), node.span())),
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for Views<'a> {
+    fn visit_import(&mut self, import: &'ast Import) {
+        self.check(import);
+        visit::walk_import(self, import);
+    }
+
+    fn visit_import_list(&mut self, list: &'ast ImportList) {
+        self.check(list);
+        visit::walk_import_list(self, list);
+    }
+
+    fn visit_import_item(&mut self, item: &'ast ImportItem) {
+        self.check(item);
+    }
+
+    fn visit_declaration(&mut self, declaration: &'ast Declaration) {
+        self.check(declaration);
+        visit::walk_declaration(self, declaration);
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        self.check(expression);
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_do_statement(&mut self, statement: &'ast DoStatement) {
+        self.check(statement);
+        visit::walk_do_statement(self, statement);
+    }
+
+    fn visit_lesser_pattern(&mut self, pattern: &'ast LesserPattern) {
+        self.check(pattern);
+    }
+
+    fn visit_greater_pattern(&mut self, pattern: &'ast GreaterPattern) {
+        self.check(pattern);
+        visit::walk_greater_pattern(self, pattern);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        self.check(ty);
+        visit::walk_ty(self, ty);
+    }
+}
+
+#[test]
+fn round_trip() {
+    for source in CORPUS {
+        assert_lossless(source);
+    }
+}
+
+/// A corpus source with trivia inserted at arbitrary character boundaries.
+fn corpus_with_trivia() -> impl Strategy<Value = String> {
+    let trivia = prop::sample::select(&[" ", "\n", "\t", "-- c\n", "{- e -}", "{- "][..]);
+    prop::sample::select(CORPUS).prop_flat_map(move |source| {
+        let boundaries: Vec<_> = source
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain([source.len()])
+            .collect();
+        prop::collection::vec((prop::sample::select(boundaries), trivia.clone()), 0..4).prop_map(
+            move |mut insertions| {
+                insertions.sort();
+                let mut source = source.to_string();
+                for (offset, text) in insertions.into_iter().rev() {
+                    source.insert_str(offset, text);
+                }
+                source
+            },
+        )
+    })
+}
+
+proptest! {
+    #[test]
+    fn round_trip_fragments(parts in prop::collection::vec(prop::sample::select(FRAGMENTS), 0..24)) {
+        assert_lossless(&parts.concat());
+    }
+
+    #[test]
+    fn round_trip_corpus_with_trivia(source in corpus_with_trivia()) {
+        assert_lossless(&source);
+    }
+}
+
+#[test]
+fn ast_nodes_find_syntax() {
+    let source = "
+infixl 1 add as +
+
+-- | Adds one.
+increment x = x + 1
+";
+    let (module, tree, _) = parse_with_syntax(source, Tabs::default());
+
+    let fixity = module.fixities[0].syntax(&tree).unwrap();
+    assert_eq!(fixity.text(), "infixl 1 add as +");
+
+    let declaration = &module.declarations[0];
+    let DeclarationK::ValueDeclaration(_, patterns, expression) = &declaration.kind else {
+        panic!("expected a value declaration");
+    };
+    let node = declaration.syntax(&tree).unwrap();
+    assert_eq!(node.kind(), SyntaxKind::ValueDeclaration);
+    assert_eq!(patterns[0].syntax(&tree).unwrap().text(), "x");
+
+    let node = expression.syntax(&tree).unwrap();
+    assert_eq!(node.kind(), SyntaxKind::BinaryOperatorExpression);
+    assert_eq!(node.text(), "x + 1");
+    assert_eq!(
+        node.parent().map(|parent| parent.kind()),
+        Some(SyntaxKind::ValueDeclaration)
+    );
+    let ExpressionK::BinaryOperator(left, _, right) = &expression.kind else {
+        panic!("expected a binary operator");
+    };
+    let operands: Vec<_> = node.children().map(|child| child.text()).collect();
+    assert_eq!(
+        operands,
+        vec![
+            left.syntax(&tree).unwrap().text(),
+            right.syntax(&tree).unwrap().text()
+        ]
+    );
+    // Nodes are matched by span, so an AST that was moved finds nothing.
+    let mut moved = expression.clone();
+    moved.begin += 1;
+    moved.end += 1;
+    assert_eq!(moved.syntax(&tree), None);
+}
+
+#[test]
+fn syntax_tree_dump() {
+    let source = "
+-- | Doc.
+example a = f a {- b -} c
+";
+    let (_, tree, _) = parse_with_syntax(source, Tabs::default());
+    insta::assert_snapshot!(format!("{:#?}", tree.root()));
+}
+
+#[test]
+fn syntax_tree_operators() {
+    let source = "
+infixl 1 add as +
+infixl 2 mul as *
+
+example = f a * b + c do
+  d
+";
+    let (_, tree, _) = parse_with_syntax(source, Tabs::default());
+    insta::assert_snapshot!(format!("{:#?}", tree.root()));
+}
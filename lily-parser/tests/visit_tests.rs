@@ -0,0 +1,98 @@
+use lily_lexer::symbol::Symbol;
+use lily_parser::{
+    parse_top_level,
+    types::{Expression, ExpressionK, GreaterPattern, GreaterPatternK, Ty, TyK},
+    visit::{
+        walk_expression, walk_expression_mut, walk_greater_pattern, walk_ty, Visitor, VisitorMut,
+    },
+};
+
+const SOURCE: &str = r"
+infixl 4 add as +
+infixr 9 type Function as ->
+
+example : Maybe Int -> Int
+example x = case x of
+  Just y if positive y -> y + offset
+  Nothing -> do
+    z <- pure offset
+    let
+      w = z
+    pure w
+";
+
+#[derive(Default)]
+struct Names {
+    variables: Vec<&'static str>,
+    constructors: Vec<&'static str>,
+    types: Vec<&'static str>,
+}
+
+impl<'ast> Visitor<'ast> for Names {
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        if let ExpressionK::Variable(name) = &expression.kind {
+            self.variables.push(name.as_str());
+        }
+        walk_expression(self, expression);
+    }
+
+    fn visit_greater_pattern(&mut self, pattern: &'ast GreaterPattern) {
+        if let GreaterPatternK::Constructor(name) = &pattern.kind {
+            self.constructors.push(name.as_str());
+        }
+        walk_greater_pattern(self, pattern);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let TyK::Constructor(name) = &ty.kind {
+            self.types.push(name.as_str());
+        }
+        walk_ty(self, ty);
+    }
+}
+
+#[test]
+fn visitor() {
+    let (module, errors) = parse_top_level(SOURCE);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let mut names = Names::default();
+    names.visit_module(&module);
+    assert_eq!(
+        names.variables,
+        ["x", "positive", "y", "y", "offset", "pure", "offset", "z", "pure", "w"]
+    );
+    assert_eq!(names.constructors, ["Just", "Nothing"]);
+    assert_eq!(names.types, ["Maybe", "Int", "Int"]);
+}
+
+struct Rename {
+    from: Symbol,
+    to: Symbol,
+}
+
+impl VisitorMut for Rename {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExpressionK::Variable(name) if *name == self.from => *name = self.to,
+            _ => walk_expression_mut(self, expression),
+        }
+    }
+}
+
+#[test]
+fn visitor_mut() {
+    let (mut module, _) = parse_top_level(SOURCE);
+    Rename {
+        from: Symbol::intern("offset"),
+        to: Symbol::intern("base"),
+    }
+    .visit_module_mut(&mut module);
+
+    let mut names = Names::default();
+    names.visit_module(&module);
+    assert_eq!(
+        names.variables,
+        ["x", "positive", "y", "y", "base", "pure", "base", "z", "pure", "w"]
+    );
+}